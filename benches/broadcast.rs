@@ -0,0 +1,98 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::node::Node;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::{runtime::Runtime, sync::broadcast};
+
+
+const NODES: u16 = 6;  // t = 1: large enough for the two-step protocol
+
+
+struct Cluster {
+    nodes: Vec<Arc<Node>>,
+    deliveries: Vec<broadcast::Receiver<asynchronous_broadcast_protocols::node::Delivery>>,
+}
+
+
+async fn start_cluster(protocol: &str, base_port: u16) -> Cluster {
+    let settings = serde_json::json!({ "protocol": protocol });
+    let nodes = common::start_all(
+        (0..NODES).map(|id| common::config_with(&common::ids(NODES), id, base_port, settings.clone()))
+    ).await;
+    let deliveries = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+    Cluster { nodes, deliveries }
+}
+
+
+/// Broadcast one payload from node 0 and wait until every node delivered it
+async fn broadcast_once(cluster: &mut Cluster, payload: &[u8]) {
+    let id = cluster.nodes[0].broadcast(payload.to_vec()).await.unwrap();
+    for rx in &mut cluster.deliveries {
+        loop {
+            let delivery = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await
+                .expect("broadcast stalled")
+                .unwrap();
+            if delivery.id == id {
+                break;
+            }
+        }
+    }
+}
+
+
+fn bench_protocols(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("broadcast_latency");
+    group.sample_size(30);
+
+    for (i, protocol) in ["reliable_broadcast", "two_step_broadcast"].into_iter().enumerate() {
+        let mut cluster = runtime.block_on(start_cluster(protocol, 39500 + 100 * i as u16));
+        for size in [64usize, 4096] {
+            let payload = vec![0xab; size];
+            group.bench_with_input(BenchmarkId::new(protocol, size), &payload, |b, payload| {
+                b.iter(|| runtime.block_on(broadcast_once(&mut cluster, payload)));
+            });
+        }
+    }
+    group.finish();
+}
+
+
+/// Bytes put on the wire by the whole cluster for one broadcast
+async fn bytes_per_broadcast(cluster: &mut Cluster, payload: &[u8]) -> u64 {
+    let total = |cluster: &Cluster| cluster.nodes.iter().map(|node| node.link().bytes_sent()).sum::<u64>();
+    let before = total(cluster);
+    broadcast_once(cluster, payload).await;
+    // Let the last acks and fragments go out
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    total(cluster) - before
+}
+
+
+fn bench_large_payloads(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("large_payload");
+    group.sample_size(10);
+
+    for (i, protocol) in ["reliable_broadcast", "merkle_broadcast"].into_iter().enumerate() {
+        let mut cluster = runtime.block_on(start_cluster(protocol, 39700 + 100 * i as u16));
+        for size in [64 * 1024usize, 512 * 1024] {
+            let payload = vec![0xab; size];
+            let bytes = runtime.block_on(bytes_per_broadcast(&mut cluster, &payload));
+            println!("{}/{}: {} bytes sent per broadcast ({:.1}x payload)", protocol, size, bytes, bytes as f64 / size as f64);
+
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(protocol, size), &payload, |b, payload| {
+                b.iter(|| runtime.block_on(broadcast_once(&mut cluster, payload)));
+            });
+        }
+    }
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_protocols, bench_large_payloads);
+criterion_main!(benches);
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{polynomial::Committee, quorum::QuorumSystem};
+
+use super::{binary_agreement::BinaryAgreement, coin::Coin, types::{AgreementMessage, Slot, PROPOSAL_TAG}};
+
+
+/// Bracha broadcast state of one slot
+#[derive(Default)]
+struct Broadcast {
+    echo_sent: bool,
+    ready_sent: bool,
+    delivered: bool,
+    echoed: HashSet<u16>,
+    readied: HashSet<u16>,
+    echo_messages: HashMap<Vec<u8>, HashSet<u16>>,
+    ready_messages: HashMap<Vec<u8>, HashSet<u16>>,
+}
+
+
+/// Agreement on a common subset of the nodes' proposals (after Ben-Or, Kelmer and Rabin,
+/// as used by HoneyBadgerBFT).
+///
+/// Every node reliably broadcasts a proposal, and the nodes run one binary agreement per
+/// proposal on whether to include it. A node votes 1 for a proposal once the application
+/// `accept`s it, and 0 for all the rest once n-t agreements decided 1. Every correct node ends
+/// up with the same set of at least n-t proposers, and every included proposal was accepted
+/// by at least one correct node.
+///
+/// The session is a plain state machine: the caller feeds it messages and sends whatever
+/// it returns to every node, this one included. It can carry further reliably broadcast
+/// values next to the proposals, one per node and tag.
+///
+/// The binary agreements flip local coins, unless the session is created `with_coin`.
+pub struct Agreement {
+    my_id: u16,
+    nodes: Vec<u16>,
+    t: usize,
+    quorums: QuorumSystem,
+    broadcasts: HashMap<Slot, Broadcast>,
+    values: HashMap<(u16, u8), Vec<u8>>,  // delivered value slots
+    agreements: HashMap<u16, BinaryAgreement>,
+    coin: Option<Coin>,
+}
+
+
+impl Agreement {
+    /// Session of `my_id` among the members of `committee`, at most t of them faulty
+    pub fn new(my_id: u16, committee: &Committee) -> Self {
+        Self::create(my_id, committee, None)
+    }
+
+    /// Session whose binary agreements use the common `coin`. Every member must create it the same way.
+    pub fn with_coin(my_id: u16, committee: &Committee, coin: Coin) -> Self {
+        Self::create(my_id, committee, Some(coin))
+    }
+
+    fn create(my_id: u16, committee: &Committee, coin: Option<Coin>) -> Self {
+        let n = committee.n();
+        let t = committee.t;
+        let common_coin = coin.is_some();
+        Self {
+            my_id,
+            agreements: committee.nodes.iter().map(|&id| (id, BinaryAgreement::new(id, my_id, n, t, common_coin))).collect(),
+            nodes: committee.nodes.clone(),
+            t,
+            quorums: QuorumSystem::threshold(n, t),
+            broadcasts: HashMap::new(),
+            values: HashMap::new(),
+            coin,
+        }
+    }
+
+    /// Reliably broadcast `value` under `tag`. Only the first value per tag counts.
+    pub fn broadcast(&mut self, tag: u8, value: Vec<u8>) -> Vec<AgreementMessage> {
+        vec![AgreementMessage::Send(Slot::Value { broadcaster: self.my_id, tag }, value)]
+    }
+
+    pub fn propose(&mut self, proposal: Vec<u8>) -> Vec<AgreementMessage> {
+        self.broadcast(PROPOSAL_TAG, proposal)
+    }
+
+    /// The value `node` broadcast under `tag`, once delivered
+    pub fn value(&self, node: u16, tag: u8) -> Option<&[u8]> {
+        self.values.get(&(node, tag)).map(Vec::as_slice)
+    }
+
+    /// Delivered values under `tag`
+    pub fn values(&self, tag: u8) -> impl Iterator<Item = (u16, &[u8])> {
+        self.values.iter()
+            .filter(move |((_, t), _)| *t == tag)
+            .map(|((node, _), value)| (*node, value.as_slice()))
+    }
+
+    /// Delivered proposals the application has not ruled on yet
+    pub fn pending_proposals(&self) -> Vec<(u16, &[u8])> {
+        self.values(PROPOSAL_TAG)
+            .filter(|(node, _)| !self.agreements[node].has_input())
+            .collect()
+    }
+
+    /// Vote to include the proposal of `node`
+    pub fn accept(&mut self, node: u16) -> Vec<AgreementMessage> {
+        let Some(agreement) = self.agreements.get_mut(&node) else { return Vec::new() };
+        let votes = agreement.input(true);
+        let mut messages = self.send(votes);
+        messages.extend(self.reject_rest());
+        messages
+    }
+
+    /// The agreed set of proposers, once every binary agreement has decided.
+    /// Their proposals may still be on the way.
+    pub fn decided(&self) -> Option<Vec<u16>> {
+        let mut included = Vec::new();
+        for &node in &self.nodes {
+            if self.agreements[&node].decision()? {
+                included.push(node);
+            }
+        }
+        Some(included)
+    }
+
+    pub fn handle(&mut self, sender: u16, message: AgreementMessage) -> Vec<AgreementMessage> {
+        if !self.nodes.contains(&sender) {
+            return Vec::new();
+        }
+        let slot = message.slot();
+        match slot {
+            Slot::Step { ba, .. } if !self.agreements.contains_key(&ba) => return Vec::new(),
+            Slot::Coin { ba, .. } if self.coin.is_none() || !self.agreements.contains_key(&ba) => return Vec::new(),
+            _ => {}
+        }
+
+        let broadcast = self.broadcasts.entry(slot).or_default();
+        let mut messages = Vec::new();
+        let mut delivered = None;
+        match message {
+            AgreementMessage::Send(_, value) => {
+                if sender == slot.broadcaster() && !broadcast.echo_sent {
+                    broadcast.echo_sent = true;
+                    messages.push(AgreementMessage::Echo(slot, value));
+                }
+            }
+            AgreementMessage::Echo(_, value) => {
+                if !broadcast.echoed.insert(sender) {
+                    return messages;
+                }
+                let voters = broadcast.echo_messages.entry(value.clone()).or_default();
+                voters.insert(sender);
+                if !broadcast.ready_sent && self.quorums.is_echo_quorum(voters) {
+                    broadcast.ready_sent = true;
+                    messages.push(AgreementMessage::Ready(slot, value));
+                }
+            }
+            AgreementMessage::Ready(_, value) => {
+                if !broadcast.readied.insert(sender) {
+                    return messages;
+                }
+                let voters = broadcast.ready_messages.entry(value.clone()).or_default();
+                voters.insert(sender);
+                if !broadcast.ready_sent && self.quorums.is_ready_amplification(voters) {
+                    broadcast.ready_sent = true;
+                    messages.push(AgreementMessage::Ready(slot, value.clone()));
+                }
+                if !broadcast.delivered && self.quorums.is_delivery_quorum(voters) {
+                    broadcast.delivered = true;
+                    delivered = Some(value);
+                }
+            }
+        }
+
+        if let Some(value) = delivered {
+            match slot {
+                Slot::Value { broadcaster, tag } => {
+                    self.values.insert((broadcaster, tag), value);
+                }
+                Slot::Step { broadcaster, ba, round, step } => {
+                    let votes = self.agreements.get_mut(&ba)
+                        .map(|agreement| agreement.deliver(broadcaster, round, step, &value))
+                        .unwrap_or_default();
+                    messages.extend(self.send(votes));
+                    messages.extend(self.reject_rest());
+                }
+                Slot::Coin { broadcaster, ba, round } => {
+                    let coin = self.coin.as_mut().and_then(|coin| coin.deliver(broadcaster, ba, round, &value));
+                    let votes = match (coin, self.agreements.get_mut(&ba)) {
+                        (Some(coin), Some(agreement)) => agreement.coin(round, coin),
+                        _ => Vec::new(),
+                    };
+                    messages.extend(self.send(votes));
+                    messages.extend(self.reject_rest());
+                }
+            }
+        }
+        messages
+    }
+
+    /// Once n-t proposals are in, vote 0 on every proposal not voted on yet
+    fn reject_rest(&mut self) -> Vec<AgreementMessage> {
+        let included = self.agreements.values().filter(|agreement| agreement.decision() == Some(true)).count();
+        if included < self.nodes.len() - self.t {
+            return Vec::new();
+        }
+        let votes: Vec<(Slot, Vec<u8>)> = self.agreements.values_mut()
+            .filter(|agreement| !agreement.has_input())
+            .flat_map(|agreement| agreement.input(false))
+            .collect();
+        self.send(votes)
+    }
+
+    /// Broadcast votes, signing the coin shares the binary agreements released
+    fn send(&self, votes: Vec<(Slot, Vec<u8>)>) -> Vec<AgreementMessage> {
+        votes.into_iter()
+            .filter_map(|(slot, vote)| match (slot, &self.coin) {
+                (Slot::Coin { ba, round, .. }, Some(coin)) => Some(AgreementMessage::Send(slot, coin.share(ba, round))),
+                (Slot::Coin { .. }, None) => None,
+                _ => Some(AgreementMessage::Send(slot, vote)),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use rand_core::{OsRng, RngCore};
+
+use super::types::Slot;
+
+
+// A faulty node could otherwise open slots for rounds nobody will ever reach
+pub const MAX_ROUNDS: u32 = 1024;
+
+// Step votes on the wire
+const VOTE_VALUE: u8 = 1;
+const VOTE_MARKED: u8 = 2;
+
+
+/// A vote in one step. Only step 3 votes can be marked, meaning "a majority of n voted this in step 2".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Vote {
+    value: bool,
+    marked: bool,
+}
+
+
+impl Vote {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut byte = 0;
+        if self.value {
+            byte |= VOTE_VALUE;
+        }
+        if self.marked {
+            byte |= VOTE_MARKED;
+        }
+        vec![byte]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [byte] if *byte & !(VOTE_VALUE | VOTE_MARKED) == 0 => Some(Self {
+                value: byte & VOTE_VALUE != 0,
+                marked: byte & VOTE_MARKED != 0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+
+/// Ways to pick `quorum` votes out of `zeros` votes for 0 and `ones` votes for 1: (picked 0s, picked 1s)
+fn splits(zeros: usize, ones: usize, quorum: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..=ones.min(quorum)).map(move |k| (quorum - k, k)).filter(move |(k0, _)| *k0 <= zeros)
+}
+
+/// Step 1 → 2: the majority, 0 on a tie
+fn majority(zeros: usize, ones: usize) -> bool {
+    ones > zeros
+}
+
+/// Step 2 → 3: a value voted by more than half of all n nodes
+fn strict_majority(zeros: usize, ones: usize, n: usize) -> Option<bool> {
+    if 2 * ones > n {
+        Some(true)
+    } else if 2 * zeros > n {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Step 3 → next round: a value marked more than t times, otherwise the coin
+fn adopted(marked_zeros: usize, marked_ones: usize, t: usize) -> Option<bool> {
+    if marked_ones > t {
+        Some(true)
+    } else if marked_zeros > t {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+
+/// Randomized binary Byzantine agreement, after Bracha (1987), with a local or a common coin.
+///
+/// Every vote travels by reliable broadcast, so a faulty node cannot tell different nodes
+/// different things, and is only counted once it is *valid*: some n-t valid votes of the previous
+/// step would have let a correct node cast it. Each round has three steps:
+/// 1. vote the current value, wait for n-t votes and take the majority;
+/// 2. vote that, wait for n-t votes; a value with more than n/2 of them is voted *marked*;
+/// 3. wait for n-t votes: more than 2t marked votes for v decide v, more than t adopt v,
+///    anything else takes the coin.
+///
+/// A node keeps voting for one round after deciding, by the end of which every correct node
+/// has decided the same value. Safety needs n ≥ 3t+1 and nothing from the coin; termination
+/// is with probability 1. With a local coin, a private flip, the expected number of rounds grows
+/// exponentially with n when the scheduler works against the nodes. With a common coin every
+/// node releases its share in step 3 and waits for the coin, which all nodes see the same and
+/// the scheduler cannot know in advance, and the expected number of rounds is constant.
+pub struct BinaryAgreement {
+    ba: u16,
+    my_id: u16,
+    n: usize,
+    t: usize,
+    common_coin: bool,
+    coins: HashMap<u32, bool>,  // common coins by round
+    released: u32,  // last round this node released its coin share for
+    started: bool,
+    round: u32,
+    step: u8,
+    value: bool,
+    decision: Option<(bool, u32)>,  // value, round
+    halted: bool,
+    received: HashMap<(u32, u8), HashMap<u16, Vote>>,  // delivered but not (yet) valid
+    valid: HashMap<(u32, u8), Vec<(u16, Vote)>>,  // in the order they became valid
+}
+
+
+impl BinaryAgreement {
+    /// Agreement on `ba`'s proposal, flipping a local coin unless `common_coin`
+    pub fn new(ba: u16, my_id: u16, n: usize, t: usize, common_coin: bool) -> Self {
+        Self {
+            ba,
+            my_id,
+            n,
+            t,
+            common_coin,
+            coins: HashMap::new(),
+            released: 0,
+            started: false,
+            round: 1,
+            step: 1,
+            value: false,
+            decision: None,
+            halted: false,
+            received: HashMap::new(),
+            valid: HashMap::new(),
+        }
+    }
+
+    pub fn has_input(&self) -> bool {
+        self.started
+    }
+
+    pub fn decision(&self) -> Option<bool> {
+        self.decision.map(|(value, _)| value)
+    }
+
+    /// Start with `value`. Returns the votes to broadcast; later inputs are ignored.
+    pub fn input(&mut self, value: bool) -> Vec<(Slot, Vec<u8>)> {
+        if self.started {
+            return Vec::new();
+        }
+        self.started = true;
+        self.value = value;
+        let mut votes = vec![self.vote(Vote { value, marked: false })];
+        votes.extend(self.progress());
+        votes
+    }
+
+    /// A vote reliably delivered from `sender`. Returns the votes to broadcast in response.
+    pub fn deliver(&mut self, sender: u16, round: u32, step: u8, bytes: &[u8]) -> Vec<(Slot, Vec<u8>)> {
+        if round == 0 || round > MAX_ROUNDS || !(1..=3).contains(&step) {
+            return Vec::new();
+        }
+        let Some(vote) = Vote::from_bytes(bytes) else { return Vec::new() };
+        if vote.marked && step != 3 {
+            return Vec::new();
+        }
+        self.received.entry((round, step)).or_default().entry(sender).or_insert(vote);
+        self.progress()
+    }
+
+    /// The common coin of `round`. Returns the votes to broadcast in response.
+    pub fn coin(&mut self, round: u32, value: bool) -> Vec<(Slot, Vec<u8>)> {
+        if !self.common_coin || round == 0 || round > MAX_ROUNDS {
+            return Vec::new();
+        }
+        self.coins.entry(round).or_insert(value);
+        self.progress()
+    }
+
+    fn vote(&self, vote: Vote) -> (Slot, Vec<u8>) {
+        let slot = Slot::Step { broadcaster: self.my_id, ba: self.ba, round: self.round, step: self.step };
+        (slot, vote.to_bytes())
+    }
+
+    /// (votes for 0, votes for 1) among valid votes
+    fn count(votes: &[(u16, Vote)], marked_only: bool) -> (usize, usize) {
+        votes.iter()
+            .filter(|(_, vote)| vote.marked || !marked_only)
+            .fold((0, 0), |(zeros, ones), (_, vote)| if vote.value { (zeros, ones + 1) } else { (zeros + 1, ones) })
+    }
+
+    fn is_valid(&self, round: u32, step: u8, sender: u16, vote: Vote) -> bool {
+        let quorum = self.n - self.t;
+        let empty = Vec::new();
+        match step {
+            1 if round == 1 => true,  // Anybody's input
+            1 => {
+                let previous = self.valid.get(&(round - 1, 3)).unwrap_or(&empty);
+                let (marked_zeros, marked_ones) = Self::count(previous, true);
+                let plain = previous.len() - marked_zeros - marked_ones;
+                (0..=quorum.min(plain)).any(|p| {
+                    splits(marked_zeros, marked_ones, quorum - p).any(|(k0, k1)| {
+                        adopted(k0, k1, self.t).is_none_or(|value| value == vote.value)
+                    })
+                })
+            }
+            2 => {
+                let (zeros, ones) = Self::count(self.valid.get(&(round, 1)).unwrap_or(&empty), false);
+                splits(zeros, ones, quorum).any(|(k0, k1)| majority(k0, k1) == vote.value)
+            }
+            _ => {
+                let previous = self.valid.get(&(round, 2)).unwrap_or(&empty);
+                let (zeros, ones) = Self::count(previous, false);
+                if vote.marked {
+                    splits(zeros, ones, quorum).any(|(k0, k1)| strict_majority(k0, k1, self.n) == Some(vote.value))
+                } else {
+                    // An unmarked vote repeats the sender's own step 2 vote
+                    previous.iter().any(|(s, v)| *s == sender && v.value == vote.value)
+                        && splits(zeros, ones, quorum).any(|(k0, k1)| strict_majority(k0, k1, self.n).is_none())
+                }
+            }
+        }
+    }
+
+    /// Move received votes that have become valid over, until nothing changes
+    fn validate(&mut self) {
+        loop {
+            let mut newly_valid: Vec<(u32, u8, u16, Vote)> = self.received.iter()
+                .flat_map(|(&(round, step), votes)| votes.iter().map(move |(&sender, &vote)| (round, step, sender, vote)))
+                .filter(|&(round, step, sender, vote)| self.is_valid(round, step, sender, vote))
+                .collect();
+            if newly_valid.is_empty() {
+                return;
+            }
+            newly_valid.sort_by_key(|&(round, step, sender, _)| (round, step, sender));
+            for (round, step, sender, vote) in newly_valid {
+                if let Some(votes) = self.received.get_mut(&(round, step)) {
+                    votes.remove(&sender);
+                }
+                self.valid.entry((round, step)).or_default().push((sender, vote));
+            }
+        }
+    }
+
+    fn progress(&mut self) -> Vec<(Slot, Vec<u8>)> {
+        let mut votes = Vec::new();
+        loop {
+            self.validate();
+            if !self.started || self.halted {
+                return votes;
+            }
+            let quorum = self.n - self.t;
+            let current = match self.valid.get(&(self.round, self.step)) {
+                Some(current) if current.len() >= quorum => &current[..quorum],
+                _ => return votes,
+            };
+
+            match self.step {
+                1 => {
+                    let (zeros, ones) = Self::count(current, false);
+                    self.value = majority(zeros, ones);
+                    self.step = 2;
+                    votes.push(self.vote(Vote { value: self.value, marked: false }));
+                }
+                2 => {
+                    let (zeros, ones) = Self::count(current, false);
+                    let marked = strict_majority(zeros, ones, self.n);
+                    self.value = marked.unwrap_or(self.value);
+                    self.step = 3;
+                    votes.push(self.vote(Vote { value: self.value, marked: marked.is_some() }));
+                }
+                _ => {
+                    let (marked_zeros, marked_ones) = Self::count(current, true);
+                    if self.common_coin && self.released < self.round {
+                        // The share itself is up to the caller, who holds the key
+                        self.released = self.round;
+                        votes.push((Slot::Coin { broadcaster: self.my_id, ba: self.ba, round: self.round }, Vec::new()));
+                    }
+                    let coin = if self.common_coin {
+                        match self.coins.get(&self.round) {
+                            Some(&coin) => coin,
+                            None if adopted(marked_zeros, marked_ones, self.t).is_none() => return votes,
+                            None => false,  // Not needed
+                        }
+                    } else {
+                        OsRng.next_u32() & 1 == 1
+                    };
+                    let decided = if marked_ones > 2 * self.t {
+                        Some(true)
+                    } else if marked_zeros > 2 * self.t {
+                        Some(false)
+                    } else {
+                        None
+                    };
+                    if let (Some(value), None) = (decided, self.decision) {
+                        self.decision = Some((value, self.round));
+                    }
+                    self.value = adopted(marked_zeros, marked_ones, self.t).unwrap_or(coin);
+
+                    if self.decision.is_some_and(|(_, round)| round < self.round) {
+                        self.halted = true;
+                        return votes;
+                    }
+                    self.round += 1;
+                    self.step = 1;
+                    votes.push(self.vote(Vote { value: self.value, marked: false }));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::{collections::HashMap, sync::Arc};
+
+use sha2::{Digest, Sha256};
+
+use crate::{dkg::KeyShare, threshold::{self, Signature, SignatureShare}, Config};
+
+
+// Domain separation of coin signatures from anything else signed with the key
+const COIN_TAG: &[u8] = b"asynchronous_broadcast_protocols/coin";
+
+
+/// A common coin from the cluster's threshold key (after Cachin, Kursawe and Shoup): the coin of
+/// a round is a bit of the threshold signature on the session, agreement and round. Every node
+/// gets the same bit, and nobody can predict it before t+1 members released their shares, so at
+/// least one correct member has reached the round.
+///
+/// The agreement's members must be the nodes of `config`, each holding its share of the key.
+pub struct Coin {
+    key: KeyShare,
+    config: Arc<Config>,
+    session: Vec<u8>,
+    shares: HashMap<(u16, u32), Vec<SignatureShare>>,  // valid shares by (ba, round)
+}
+
+
+impl Coin {
+    /// Coin of agreement session `session`, which must be unique among the sessions using `key`
+    pub fn new(key: KeyShare, config: Arc<Config>, session: &[u8]) -> Self {
+        Self { key, config, session: session.to_vec(), shares: HashMap::new() }
+    }
+
+    fn message(&self, ba: u16, round: u32) -> Vec<u8> {
+        let mut message = COIN_TAG.to_vec();
+        message.extend_from_slice(&self.session);
+        message.extend_from_slice(&ba.to_be_bytes());
+        message.extend_from_slice(&round.to_be_bytes());
+        message
+    }
+
+    /// This node's share of the coin of `round` of the agreement on `ba`'s proposal
+    pub fn share(&self, ba: u16, round: u32) -> Vec<u8> {
+        Signature(threshold::sign_share(&self.key, &self.message(ba, round)).signature).to_bytes().to_vec()
+    }
+
+    /// A share of `sender`, reliably delivered. Returns the coin once t+1 valid shares are in.
+    pub fn deliver(&mut self, sender: u16, ba: u16, round: u32, bytes: &[u8]) -> Option<bool> {
+        let Ok(Signature(signature)) = Signature::from_bytes(bytes) else { return None };
+        let share = SignatureShare { node: sender, signature };
+        if !threshold::verify_share(&self.key, &share, &self.message(ba, round), &self.config) {
+            return None;
+        }
+        let shares = self.shares.entry((ba, round)).or_default();
+        shares.push(share);
+        let signature = threshold::combine(shares, self.key.threshold(), &self.config)?;
+        self.shares.remove(&(ba, round));
+        Some(Sha256::digest(signature.to_bytes())[0] & 1 == 1)
+    }
+}
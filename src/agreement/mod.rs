@@ -0,0 +1,11 @@
+pub mod types;
+pub mod binary_agreement;
+pub mod coin;
+#[allow(clippy::module_inception)]
+pub mod agreement;
+
+// re-export all public items from agreement module
+pub use agreement::*;
+pub use binary_agreement::BinaryAgreement;
+pub use coin::Coin;
+pub use types::*;
@@ -0,0 +1,154 @@
+use crate::{Error, Result};
+
+
+// Protocol Identifier
+pub const AGREEMENT_IDENTIFIER: u8 = 7;
+const PROTOCOL: &str = "agreement";
+
+// Message Types
+const MSG_SEND: u8 = 0;
+const MSG_ECHO: u8 = 1;
+const MSG_READY: u8 = 2;
+
+// Slot kinds
+const SLOT_VALUE: u8 = 0;
+const SLOT_STEP: u8 = 1;
+const SLOT_COIN: u8 = 2;
+
+// Values are proposals, step votes and coin shares; anything bigger belongs into a real broadcast
+pub const MAX_VALUE_SIZE: usize = 16 * 1024;
+
+/// Tag of the value slot carrying a node's proposal for the common subset
+pub const PROPOSAL_TAG: u8 = 0;
+
+
+/// One reliably broadcast value inside an agreement session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// A value of the application's choosing, one per tag and node
+    Value { broadcaster: u16, tag: u8 },
+    /// A vote in step 1, 2 or 3 of a round of the binary agreement on `ba`'s proposal
+    Step { broadcaster: u16, ba: u16, round: u32, step: u8 },
+    /// A share of the common coin of a round of the binary agreement on `ba`'s proposal
+    Coin { broadcaster: u16, ba: u16, round: u32 },
+}
+
+
+impl Slot {
+    pub fn broadcaster(&self) -> u16 {
+        match self {
+            Self::Value { broadcaster, .. } | Self::Step { broadcaster, .. } | Self::Coin { broadcaster, .. } => *broadcaster,
+        }
+    }
+
+    fn to_bytes(self, result: &mut Vec<u8>) {
+        match self {
+            Self::Value { broadcaster, tag } => {
+                result.push(SLOT_VALUE);
+                result.extend_from_slice(&broadcaster.to_be_bytes());
+                result.push(tag);
+            }
+            Self::Step { broadcaster, ba, round, step } => {
+                result.push(SLOT_STEP);
+                result.extend_from_slice(&broadcaster.to_be_bytes());
+                result.extend_from_slice(&ba.to_be_bytes());
+                result.extend_from_slice(&round.to_be_bytes());
+                result.push(step);
+            }
+            Self::Coin { broadcaster, ba, round } => {
+                result.push(SLOT_COIN);
+                result.extend_from_slice(&broadcaster.to_be_bytes());
+                result.extend_from_slice(&ba.to_be_bytes());
+                result.extend_from_slice(&round.to_be_bytes());
+            }
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let truncated = || Error::decode(PROTOCOL, "slot truncated");
+        let (&kind, rest) = bytes.split_first().ok_or_else(truncated)?;
+        match kind {
+            SLOT_VALUE => {
+                if rest.len() < 3 {
+                    return Err(truncated());
+                }
+                let broadcaster = u16::from_be_bytes([rest[0], rest[1]]);
+                Ok((Self::Value { broadcaster, tag: rest[2] }, &rest[3..]))
+            }
+            SLOT_STEP => {
+                if rest.len() < 9 {
+                    return Err(truncated());
+                }
+                let broadcaster = u16::from_be_bytes([rest[0], rest[1]]);
+                let ba = u16::from_be_bytes([rest[2], rest[3]]);
+                let round = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]);
+                Ok((Self::Step { broadcaster, ba, round, step: rest[8] }, &rest[9..]))
+            }
+            SLOT_COIN => {
+                if rest.len() < 8 {
+                    return Err(truncated());
+                }
+                let broadcaster = u16::from_be_bytes([rest[0], rest[1]]);
+                let ba = u16::from_be_bytes([rest[2], rest[3]]);
+                let round = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]);
+                Ok((Self::Coin { broadcaster, ba, round }, &rest[8..]))
+            }
+            _ => Err(Error::decode(PROTOCOL, format!("unknown slot kind: {}", kind))),
+        }
+    }
+}
+
+
+/// Bracha broadcast of small values, echoed in full so that `Ready`s alone suffice to deliver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgreementMessage {
+    Send(Slot, Vec<u8>),
+    Echo(Slot, Vec<u8>),
+    Ready(Slot, Vec<u8>),
+}
+
+
+impl AgreementMessage {
+    pub fn slot(&self) -> Slot {
+        match self {
+            Self::Send(slot, _) | Self::Echo(slot, _) | Self::Ready(slot, _) => *slot,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(AGREEMENT_IDENTIFIER);
+
+        let (kind, slot, value) = match self {
+            Self::Send(slot, value) => (MSG_SEND, slot, value),
+            Self::Echo(slot, value) => (MSG_ECHO, slot, value),
+            Self::Ready(slot, value) => (MSG_READY, slot, value),
+        };
+        result.push(kind);
+        slot.to_bytes(&mut result);
+        result.extend_from_slice(value);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != AGREEMENT_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not an agreement message"));
+        }
+
+        let (slot, value) = Slot::from_bytes(&bytes[2..])?;
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(Error::decode(PROTOCOL, format!("value of {} bytes", value.len())));
+        }
+        let value = value.to_vec();
+        match bytes[1] {
+            MSG_SEND => Ok(Self::Send(slot, value)),
+            MSG_ECHO => Ok(Self::Echo(slot, value)),
+            MSG_READY => Ok(Self::Ready(slot, value)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use tokio::sync::mpsc;
+
+use crate::{erasure, merkle::{Hash, MerkleTree, ProvenFragment}, transport::ReliableLink, wal::{Record, Wal}, Authentication, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{AvidMessage, Commitment, FragmentStore};
+
+
+/// (k, n): any t+1 of the n fragments recover the blob
+pub fn code_parameters(config: &Config) -> (usize, usize) {
+    (config.fault_tolerance() + 1, config.nodes.len())
+}
+
+
+/// Disperse `blob` as instance `id`: send every node its fragment and collect signed
+/// `Stored` acknowledgements from `rx` until they form a certificate. The nodes that
+/// have not acknowledged by then get the commitment, to recover their fragment from the others.
+pub async fn disperse(id: Identifier, blob: &[u8], mut rx: mpsc::Receiver<Message>, config: &Config, link: &ReliableLink) -> Result<Commitment> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
+    if config.authentication == Authentication::Unauthenticated {
+        return Err(Error::Config("dispersal certificates need signed acknowledgements".to_string()));
+    }
+
+    let (k, n) = code_parameters(config);
+    let fragments = erasure::encode(blob, k, n)?;
+    let tree = MerkleTree::new(&fragments);
+    let root = tree.root();
+    for (index, (target, data)) in config.node_ids().into_iter().zip(fragments).enumerate() {
+        let message = Message::new_authenticated(
+            id,
+            my_node.id,
+            MessageType::Avid(AvidMessage::Store(ProvenFragment { root, proof: tree.proof(index), data })),
+            &my_node.privkey,
+            config,
+        );
+        link.send(target, &message.to_bytes()).await?;
+    }
+
+    let mut certificate: Vec<Message> = Vec::new();
+    let mut signers = HashSet::new();
+    while let Some(message) = rx.recv().await {
+        if !matches!(&message.payload, MessageType::Avid(AvidMessage::Stored(r)) if *r == root) {
+            continue;
+        }
+        // Acks reach us authenticated by the link or by their signature; only signed ones are transferable
+        let Some(signer) = config.get_node(message.sender) else { continue };
+        let pubkey = ed25519_dalek::SigningKey::from_bytes(&signer.privkey).verifying_key();
+        if !message.verify(pubkey) || !signers.insert(message.sender) {
+            continue;
+        }
+        certificate.push(message);
+        if certificate.len() > 2 * config.fault_tolerance() {
+            let commitment = Commitment { id, root, certificate };
+            let message = Message::new_authenticated(
+                id,
+                my_node.id,
+                MessageType::Avid(AvidMessage::Commit(commitment.clone())),
+                &my_node.privkey,
+                config,
+            );
+            for target in config.node_ids().into_iter().filter(|node| !signers.contains(node)) {
+                link.send(target, &message.to_bytes()).await?;
+            }
+            return Ok(commitment);
+        }
+    }
+    Err(Error::InstanceClosed(id))
+}
+
+
+/// Retrieve the blob behind `commitment`: ask every node for its fragment and
+/// decode from the first t+1 that match the root.
+///
+/// A client that encoded inconsistently is caught by re-encoding the decoded blob,
+/// so every retriever gets the same blob or the same error.
+pub async fn retrieve(commitment: &Commitment, mut rx: mpsc::Receiver<Message>, config: &Config, link: &ReliableLink) -> Result<Vec<u8>> {
+    commitment.verify(config)?;
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+
+    let message = Message::new_authenticated(
+        commitment.id,
+        my_node.id,
+        MessageType::Avid(AvidMessage::Request),
+        &my_node.privkey,
+        config,
+    );
+    link.send_to_all(&message.to_bytes()).await?;
+
+    let (k, n) = code_parameters(config);
+    let order = config.node_ids();
+    let mut fragments: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut answered = HashSet::new();
+    while let Some(message) = rx.recv().await {
+        let MessageType::Avid(AvidMessage::Answer(fragment)) = message.payload else { continue };
+        let Ok(index) = order.binary_search(&message.sender) else { continue };
+        if fragment.root != commitment.root || !fragment.verify(index, n) || !answered.insert(message.sender) {
+            continue;
+        }
+        fragments.push((index, fragment.data));
+        if fragments.len() < k {
+            continue;
+        }
+        let blob = erasure::decode(&fragments, k, n)?;
+        if MerkleTree::new(&erasure::encode(&blob, k, n)?).root() != commitment.root {
+            return Err(Error::Erasure("fragments do not match the commitment".to_string()));
+        }
+        return Ok(blob);
+    }
+    Err(Error::InstanceClosed(commitment.id))
+}
+
+
+/// This node's fragment of `blob`, as dispersed under `root`. Used to recover a fragment
+/// that never arrived, from the blob retrieved from the other nodes.
+pub fn fragment_of(blob: &[u8], root: Hash, config: &Config) -> Result<ProvenFragment> {
+    let my_index = config.node_ids().binary_search(&config.my_id)
+        .map_err(|_| Error::UnknownNode(config.my_id))?;
+    let (k, n) = code_parameters(config);
+    let mut fragments = erasure::encode(blob, k, n)?;
+    let tree = MerkleTree::new(&fragments);
+    if tree.root() != root {
+        return Err(Error::Erasure("blob does not match the commitment".to_string()));
+    }
+    Ok(ProvenFragment { root, proof: tree.proof(my_index), data: fragments.swap_remove(my_index) })
+}
+
+
+/// Keep `fragment` of blob `id`, logged first if the node has a write-ahead log, so that it
+/// survives a restart. Returns whether the node holds this fragment now, stored before included,
+/// so that a client whose acknowledgement was lost gets another; false for another fragment
+/// of the blob, or one over the client's quota.
+pub async fn store(id: Identifier, fragment: ProvenFragment, store: &Mutex<FragmentStore>, wal: Option<&Wal>) -> Result<bool> {
+    {
+        let store = store.lock().unwrap();
+        if let Some(stored) = store.get(&id) {
+            return Ok(*stored == fragment);
+        }
+        if !store.admits(id, &fragment) {
+            return Ok(false);
+        }
+    }
+    if let Some(wal) = wal {
+        wal.append(&Record::Fragment { id, fragment: fragment.clone() }).await?;
+    }
+    let mut store = store.lock().unwrap();
+    match store.get(&id) {
+        Some(stored) => Ok(*stored == fragment),
+        None => Ok(store.insert(id, fragment)),
+    }
+}
+
+
+/// Server side: store our fragment and acknowledge it, again if asked again, or answer a retrieval request
+pub async fn serve(message: Message, fragments: &Mutex<FragmentStore>, wal: Option<&Wal>, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let MessageType::Avid(avid_message) = message.payload else { return Ok(()) };
+
+    match avid_message {
+        AvidMessage::Store(fragment) => {
+            let my_index = config.node_ids().binary_search(&my_node.id)
+                .map_err(|_| Error::UnknownNode(my_node.id))?;
+            if message.sender != message.id.sender || !fragment.verify(my_index, config.nodes.len()) {
+                return Ok(());
+            }
+            let root = fragment.root;
+            if store(message.id, fragment, fragments, wal).await? {
+                let ack = Message::new_authenticated(
+                    message.id,
+                    my_node.id,
+                    MessageType::Avid(AvidMessage::Stored(root)),
+                    &my_node.privkey,
+                    config,
+                );
+                link.send(message.sender, &ack.to_bytes()).await?;
+            }
+        }
+        AvidMessage::Request => {
+            let fragment = fragments.lock().unwrap().get(&message.id).cloned();
+            if let Some(fragment) = fragment {
+                let answer = Message::new_authenticated(
+                    message.id,
+                    my_node.id,
+                    MessageType::Avid(AvidMessage::Answer(fragment)),
+                    &my_node.privkey,
+                    config,
+                );
+                link.send(message.sender, &answer.to_bytes()).await?;
+            }
+        }
+        AvidMessage::Stored(_) | AvidMessage::Answer(_) | AvidMessage::Commit(_) => {
+            // Handled by the waiting disperse or retrieve, or by the node's recovery
+        }
+    }
+    Ok(())
+}
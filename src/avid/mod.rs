@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod avid;
+
+// re-export all public items from avid module
+pub use avid::*;
+pub use types::*;
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{constants::{DIGEST_SIZE, IDENTIFIER_SIZE}, merkle::{Hash, ProvenFragment}, Config, Error, Identifier, Message, MessageType, Result};
+
+
+// Protocol Identifier
+pub const AVID_IDENTIFIER: u8 = 5;
+const PROTOCOL: &str = "avid";
+
+// Message Types
+const MSG_STORE: u8 = 0;
+const MSG_STORED: u8 = 1;
+const MSG_REQUEST: u8 = 2;
+const MSG_ANSWER: u8 = 3;
+const MSG_COMMIT: u8 = 4;
+
+
+#[derive(Debug, Clone)]
+pub enum AvidMessage {
+    Store(ProvenFragment),  // the recipient's fragment, from the client
+    Stored(Hash),  // signed storage acknowledgement
+    Request,
+    Answer(ProvenFragment),  // the sender's fragment
+    Commit(Commitment),  // the client's commitment, for the nodes that did not acknowledge
+}
+
+
+impl AvidMessage {
+    /// Storage acknowledgements make up the certificate, so they stay signed
+    /// even on authenticated channels
+    pub fn requires_signature(&self) -> bool {
+        matches!(self, Self::Stored(_))
+    }
+
+    /// Message types an honest node sends at most once per instance
+    pub fn vote_kind(&self) -> Option<u8> {
+        match self {
+            Self::Stored(_) => Some(MSG_STORED),
+            _ => None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(AVID_IDENTIFIER);
+
+        match self {
+            Self::Store(fragment) => {
+                result.push(MSG_STORE);
+                fragment.to_bytes(&mut result);
+            }
+            Self::Stored(root) => {
+                result.push(MSG_STORED);
+                result.extend_from_slice(root);
+            }
+            Self::Request => {
+                result.push(MSG_REQUEST);
+            }
+            Self::Answer(fragment) => {
+                result.push(MSG_ANSWER);
+                fragment.to_bytes(&mut result);
+            }
+            Self::Commit(commitment) => {
+                result.push(MSG_COMMIT);
+                result.extend_from_slice(&commitment.to_bytes());
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != AVID_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not an AVID message"));
+        }
+
+        match bytes[1] {
+            MSG_STORE => Ok(Self::Store(ProvenFragment::from_bytes(&bytes[2..])?)),
+            MSG_STORED => {
+                let root = bytes[2..]
+                    .try_into()
+                    .map_err(|_| Error::decode(PROTOCOL, format!("STORED: expected {} bytes", 2 + DIGEST_SIZE)))?;
+                Ok(Self::Stored(root))
+            }
+            MSG_REQUEST => {
+                if bytes.len() != 2 {
+                    return Err(Error::decode(PROTOCOL, "REQUEST: expected 2 bytes"));
+                }
+                Ok(Self::Request)
+            }
+            MSG_ANSWER => Ok(Self::Answer(ProvenFragment::from_bytes(&bytes[2..])?)),
+            MSG_COMMIT => Ok(Self::Commit(Commitment::from_bytes(&bytes[2..])?)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
+
+
+/// Proof that a blob was dispersed: its Merkle root and 2t+1 signed storage acknowledgements.
+///
+/// At least t+1 correct nodes hold a fragment under `root`, so the blob can be retrieved.
+#[derive(Debug, Clone)]
+pub struct Commitment {
+    pub id: Identifier,
+    pub root: Hash,
+    pub certificate: Vec<Message>,
+}
+
+
+impl Commitment {
+    pub fn verify(&self, config: &Config) -> Result<()> {
+        let invalid = |reason| Err(Error::InvalidCertificate(reason));
+
+        let mut signers = HashSet::new();
+        for ack in &self.certificate {
+            if ack.id != self.id {
+                return invalid("acknowledgement for another blob");
+            }
+            if !matches!(&ack.payload, MessageType::Avid(AvidMessage::Stored(root)) if *root == self.root) {
+                return invalid("not an acknowledgement of this root");
+            }
+            if ack.domain.check(&config.domain()).is_err() {
+                return invalid("signed for another cluster or epoch");
+            }
+            let signer = config.get_node(ack.sender)
+                .ok_or(Error::UnknownNode(ack.sender))?;
+            let pubkey = ed25519_dalek::SigningKey::from_bytes(&signer.privkey).verifying_key();
+            if !ack.verify(pubkey) {
+                return Err(Error::BadSignature(ack.sender));
+            }
+            if !signers.insert(ack.sender) {
+                return invalid("duplicate signer");
+            }
+        }
+        if signers.len() < 2 * config.fault_tolerance() + 1 {
+            return invalid("fewer than 2t+1 acknowledgements");
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(&self.id.to_bytes());
+        result.extend_from_slice(&self.root);
+        for ack in &self.certificate {
+            let ack = ack.to_bytes();
+            result.extend_from_slice(&(ack.len() as u32).to_be_bytes());
+            result.extend_from_slice(&ack);
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < IDENTIFIER_SIZE + DIGEST_SIZE {
+            return Err(Error::decode(PROTOCOL, "commitment truncated"));
+        }
+        let id = Identifier::from_bytes(bytes[..IDENTIFIER_SIZE].try_into().unwrap());
+        let root = bytes[IDENTIFIER_SIZE..IDENTIFIER_SIZE + DIGEST_SIZE].try_into().unwrap();
+
+        let mut certificate = Vec::new();
+        let mut rest = &bytes[IDENTIFIER_SIZE + DIGEST_SIZE..];
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(Error::decode(PROTOCOL, "acknowledgement length truncated"));
+            }
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            if rest.len() < 4 + len {
+                return Err(Error::decode(PROTOCOL, format!("acknowledgement truncated ({} bytes)", len)));
+            }
+            certificate.push(Message::from_bytes(&rest[4..4 + len])?);
+            rest = &rest[4 + len..];
+        }
+        Ok(Self { id, root, certificate })
+    }
+}
+
+
+/// The fragments this node holds for clients, up to a quota of bytes per client
+#[derive(Debug, Default)]
+pub struct FragmentStore {
+    fragments: HashMap<Identifier, ProvenFragment>,
+    bytes: HashMap<u16, usize>,  // stored per client
+    max_bytes_per_client: usize,
+}
+
+
+impl FragmentStore {
+    pub fn new(max_bytes_per_client: usize) -> Self {
+        Self { max_bytes_per_client, ..Self::default() }
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&ProvenFragment> {
+        self.fragments.get(id)
+    }
+
+    pub fn contains(&self, id: &Identifier) -> bool {
+        self.fragments.contains_key(id)
+    }
+
+    /// Whether `insert` would store `fragment` of blob `id`
+    pub fn admits(&self, id: Identifier, fragment: &ProvenFragment) -> bool {
+        let used = self.bytes.get(&id.sender).copied().unwrap_or(0);
+        !self.fragments.contains_key(&id) && used.saturating_add(Self::size(fragment)) <= self.max_bytes_per_client
+    }
+
+    /// Store `fragment` of blob `id` for its client `id.sender`. Returns false if a fragment
+    /// of `id` is stored already, or if it does not fit into the client's quota.
+    pub fn insert(&mut self, id: Identifier, fragment: ProvenFragment) -> bool {
+        if !self.admits(id, &fragment) {
+            return false;
+        }
+        *self.bytes.entry(id.sender).or_default() += Self::size(&fragment);
+        self.fragments.insert(id, fragment);
+        true
+    }
+
+    fn size(fragment: &ProvenFragment) -> usize {
+        DIGEST_SIZE * (1 + fragment.proof.len()) + fragment.data.len()
+    }
+}
@@ -0,0 +1,210 @@
+use std::{collections::hash_map::Entry, sync::Arc};
+
+use bls12_381::{G1Projective, Scalar};
+use ff::Field;
+use rand_core::OsRng;
+use tokio::sync::mpsc;
+
+use crate::{polynomial::{self, Committee, Polynomial}, transport::ReliableLink, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{AvssMessage, BivariateCommitment, Instance, Sharing};
+
+
+/// Deal `secret` as instance `id`: pick a random symmetric bivariate polynomial f of degree t
+/// with f(0, 0) = secret, and send every node i the commitment and f(i, y).
+pub async fn share(id: Identifier, secret: Scalar, config: &Config, link: &ReliableLink) -> Result<()> {
+    deal(id, secret, &Committee::of(config), MessageType::Avss, config, link).await
+}
+
+
+/// Deal `secret` to the members of `committee` (with its degree t), wrapping every message
+/// in `envelope`. The dealer itself need not be a member.
+pub async fn deal(id: Identifier, secret: Scalar, committee: &Committee, envelope: fn(AvssMessage) -> MessageType, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
+
+    let dimension = committee.t + 1;
+    let random: Vec<Vec<Scalar>> = (0..dimension)
+        .map(|_| (0..dimension).map(|_| Scalar::random(OsRng)).collect())
+        .collect();
+    // Mirror the lower triangle so that f is symmetric
+    let mut coefficients: Vec<Vec<Scalar>> = (0..dimension)
+        .map(|j| (0..dimension).map(|l| random[j.max(l)][j.min(l)]).collect())
+        .collect();
+    coefficients[0][0] = secret;
+    let commitment = BivariateCommitment::new(&coefficients);
+
+    for &target in &committee.nodes {
+        let x = committee.point(target)?;
+        // f(x, y) = Σ_l (Σ_j f_jl x^j) y^l
+        let row: Vec<Scalar> = (0..dimension).map(|l| {
+            Polynomial { coefficients: coefficients.iter().map(|c| c[l]).collect() }.evaluate(x)
+        }).collect();
+        let message = Message::new_authenticated(
+            id,
+            my_node.id,
+            envelope(AvssMessage::Send(commitment.clone(), row)),
+            &my_node.privkey,
+            config,
+        );
+        link.send(target, &message.to_bytes()).await?;
+    }
+    Ok(())
+}
+
+
+/// f(me, x) for the commitment with `digest`: from the dealer's polynomial,
+/// or interpolated from t+1 verified points f(m, me) = f(me, m)
+fn own_value(instance: &Instance, digest: &[u8; 32], x: Scalar) -> Option<Scalar> {
+    if let Some((d, coefficients)) = &instance.polynomial {
+        if d == digest {
+            return Some(Polynomial { coefficients: coefficients.clone() }.evaluate(x));
+        }
+    }
+    let t = instance.committee.t;
+    let points: Vec<(Scalar, Scalar)> = instance.points.get(digest)?.iter()
+        .take(t + 1)
+        .filter_map(|(sender, value)| instance.committee.point(*sender).ok().map(|xm| (xm, *value)))
+        .collect();
+    if points.len() < t + 1 {
+        return None;
+    }
+    Some(polynomial::interpolate(&points, x))
+}
+
+
+/// Send every node j our point f(me, j) as an `Echo` or a `Ready`
+async fn send_points(instance: &Instance, digest: &[u8; 32], ready: bool, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let commitment = &instance.commitments[digest];
+
+    for &target in &instance.committee.nodes {
+        let x = instance.committee.point(target)?;
+        let Some(value) = own_value(instance, digest, x) else { return Ok(()) };
+        let payload = if ready {
+            AvssMessage::Ready(commitment.clone(), value)
+        } else {
+            AvssMessage::Echo(commitment.clone(), value)
+        };
+        let message = Message::new_authenticated(
+            instance.id,
+            my_node.id,
+            (instance.envelope)(payload),
+            &my_node.privkey,
+            config,
+        );
+        link.send(target, &message.to_bytes()).await?;
+    }
+    Ok(())
+}
+
+
+/// Asynchronous verifiable secret sharing with bivariate Feldman commitments
+/// (after Cachin, Kursawe, Lysyanskaya and Strobl).
+///
+/// A node echoes the dealer's `Send` to every node with a point on its row, sends `Ready`
+/// after ⌈(n+t+1)/2⌉ echoes or t+1 readies for one commitment, interpolating its row from the
+/// points received if the dealer never sent it, and completes after n-t readies. If any correct
+/// node completes, every correct node completes with the same commitment, even if the dealer
+/// is faulty. Points are checked against the commitment, so faulty nodes cannot spoil them.
+pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, config: &Config, link: Arc<ReliableLink>) -> Result<Sharing> {
+    let n = instance.committee.n();
+    let t = instance.committee.t;
+    let echo_threshold = (n + t + 1).div_ceil(2);
+    let my_x = instance.committee.point(instance.my_id)?;
+
+    while let Some(message) = rx.recv().await {
+        let MessageType::Avss(avss_message) = message.payload else { continue };
+
+        let (commitment, value, ready) = match avss_message {
+            AvssMessage::Send(commitment, coefficients) => {
+                if message.sender != instance.id.sender || instance.echo_sent {
+                    continue;
+                }
+                if commitment.degree() != t || !commitment.is_symmetric() || !commitment.verify_polynomial(my_x, &coefficients) {
+                    continue;
+                }
+                let digest = commitment.digest();
+                instance.rows.entry(digest).or_insert_with(|| commitment.row(my_x));
+                instance.commitments.entry(digest).or_insert(commitment);
+                instance.polynomial = Some((digest, coefficients));
+                instance.echo_sent = true;
+                send_points(&instance, &digest, false, config, &link).await?;
+                continue;
+            }
+            AvssMessage::Echo(commitment, value) => (commitment, value, false),
+            AvssMessage::Ready(commitment, value) => (commitment, value, true),
+            AvssMessage::Reveal(_) | AvssMessage::Answer(_) => continue,  // Handled by the node
+        };
+
+        let Ok(sender_x) = instance.committee.point(message.sender) else { continue };
+        let first = if ready { &mut instance.readied } else { &mut instance.echoed };
+        if !first.insert(message.sender) {  // Not first time
+            continue;
+        }
+
+        let digest = commitment.digest();
+        if let Entry::Vacant(entry) = instance.rows.entry(digest) {
+            if commitment.degree() != t || !commitment.is_symmetric() {
+                continue;
+            }
+            entry.insert(commitment.row(my_x));
+            instance.commitments.insert(digest, commitment);
+        }
+        // f(sender, me) lies on our row f(me, y)
+        if polynomial::evaluate_commitment(&instance.rows[&digest], sender_x) != G1Projective::generator() * value {
+            continue;
+        }
+        instance.points.entry(digest).or_default().entry(message.sender).or_insert(value);
+        let votes = if ready { &mut instance.ready_messages } else { &mut instance.echo_messages };
+        let voters = votes.entry(digest).or_default();
+        voters.insert(message.sender);
+        let count = voters.len();
+
+        if !instance.ready_sent && ((!ready && count >= echo_threshold) || (ready && count > t)) {
+            instance.ready_sent = true;
+            send_points(&instance, &digest, true, config, &link).await?;
+        }
+        if ready && count >= n - t {
+            let Some(share) = own_value(&instance, &digest, Scalar::zero()) else { continue };
+            return Ok(Sharing {
+                id: instance.id,
+                commitment: instance.commitments[&digest].share_commitment(),
+                share,
+            });
+        }
+    }
+    Err(Error::InstanceClosed(instance.id))
+}
+
+
+/// Check a revealed share of node `sender` against the sharing's commitment
+pub fn verify_share(sharing: &Sharing, sender: u16, share: Scalar, config: &Config) -> bool {
+    let Ok(x) = polynomial::node_point(config, sender) else { return false };
+    polynomial::evaluate_commitment(&sharing.commitment, x) == G1Projective::generator() * share
+}
+
+
+/// Recover the secret from revealed (node id, share) pairs.
+/// Shares that do not match the commitment are skipped, so t faulty nodes cannot change the result.
+pub fn reconstruct(sharing: &Sharing, shares: &[(u16, Scalar)], config: &Config) -> Option<Scalar> {
+    let t = config.fault_tolerance();
+    let mut points: Vec<(Scalar, Scalar)> = Vec::with_capacity(t + 1);
+    let mut seen = std::collections::HashSet::new();
+    for (sender, share) in shares {
+        if points.len() == t + 1 {
+            break;
+        }
+        if seen.insert(*sender) && verify_share(sharing, *sender, *share, config) {
+            points.push((polynomial::node_point(config, *sender).ok()?, *share));
+        }
+    }
+    if points.len() < t + 1 {
+        return None;
+    }
+    Some(polynomial::interpolate(&points, Scalar::zero()))
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod avss;
+
+// re-export all public items from avss module
+pub use avss::*;
+pub use types::*;
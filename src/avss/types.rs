@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use group::Curve;
+use sha2::{Digest, Sha256};
+
+use crate::{constants::DIGEST_SIZE, polynomial::{self, Committee}, Error, Identifier, MessageType, Result};
+
+
+pub struct Instance {
+    pub id: Identifier,
+    pub my_id: u16,
+    pub committee: Committee,  // nodes receiving shares
+    pub envelope: fn(AvssMessage) -> MessageType,  // how points travel on the wire
+    pub commitments: HashMap<[u8; DIGEST_SIZE], BivariateCommitment>,
+    pub rows: HashMap<[u8; DIGEST_SIZE], Vec<G1Projective>>,  // digest -> commitment to f(me, y)
+    pub points: HashMap<[u8; DIGEST_SIZE], HashMap<u16, Scalar>>,  // digest -> sender -> f(sender, me)
+    pub polynomial: Option<([u8; DIGEST_SIZE], Vec<Scalar>)>,  // f(me, y) from the dealer or interpolated
+    pub echo_messages: HashMap<[u8; DIGEST_SIZE], HashSet<u16>>,
+    pub ready_messages: HashMap<[u8; DIGEST_SIZE], HashSet<u16>>,
+    pub echoed: HashSet<u16>,  // senders of any Echo
+    pub readied: HashSet<u16>,  // senders of any Ready
+    pub echo_sent: bool,
+    pub ready_sent: bool,
+}
+
+
+impl Instance {
+    pub fn new(id: Identifier, my_id: u16, committee: Committee) -> Self {
+        Self {
+            id, my_id, committee,
+            envelope: MessageType::Avss,
+            commitments: HashMap::new(),
+            rows: HashMap::new(),
+            points: HashMap::new(),
+            polynomial: None,
+            echo_messages: HashMap::new(),
+            ready_messages: HashMap::new(),
+            echoed: HashSet::new(),
+            readied: HashSet::new(),
+            echo_sent: false,
+            ready_sent: false,
+        }
+    }
+}
+
+
+/// Feldman commitment to a symmetric bivariate polynomial f(x, y) = Σ f_jl x^j y^l
+/// of degree t in each variable: `points[j * (t+1) + l]` = g^f_jl.
+///
+/// The secret is f(0, 0) and node i's share is f(0, i), so row 0 commits to the shares.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BivariateCommitment {
+    pub dimension: usize,  // t+1
+    pub points: Vec<G1Projective>,
+}
+
+
+impl BivariateCommitment {
+    /// Commit to the bivariate polynomial with coefficients `coefficients[j][l]`
+    pub fn new(coefficients: &[Vec<Scalar>]) -> Self {
+        let points = coefficients.iter().flatten().map(|c| G1Projective::generator() * c).collect();
+        Self { dimension: coefficients.len(), points }
+    }
+
+    fn at(&self, j: usize, l: usize) -> G1Projective {
+        self.points[j * self.dimension + l]
+    }
+
+    pub fn degree(&self) -> usize {
+        self.dimension - 1
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        (0..self.dimension).all(|j| (0..j).all(|l| self.at(j, l) == self.at(l, j)))
+    }
+
+    /// Commitment to the univariate polynomial f(x, y) for fixed x
+    pub fn row(&self, x: Scalar) -> Vec<G1Projective> {
+        (0..self.dimension).map(|l| {
+            let column: Vec<G1Projective> = (0..self.dimension).map(|j| self.at(j, l)).collect();
+            polynomial::evaluate_commitment(&column, x)
+        }).collect()
+    }
+
+    /// Feldman commitment to the shares f(0, y); its constant term commits to the secret
+    pub fn share_commitment(&self) -> Vec<G1Projective> {
+        self.row(Scalar::zero())
+    }
+
+    /// Check that `coefficients` are those of f(x, y) for this x
+    pub fn verify_polynomial(&self, x: Scalar, coefficients: &[Scalar]) -> bool {
+        coefficients.len() == self.dimension
+            && self.row(x).iter().zip(coefficients).all(|(c, a)| *c == G1Projective::generator() * a)
+    }
+
+    /// Check that `value` = f(x, y)
+    pub fn verify_point(&self, x: Scalar, y: Scalar, value: Scalar) -> bool {
+        polynomial::evaluate_commitment(&self.row(x), y) == G1Projective::generator() * value
+    }
+
+    pub fn digest(&self) -> [u8; DIGEST_SIZE] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(2 + self.points.len() * 48);
+        result.extend_from_slice(&(self.dimension as u16).to_be_bytes());
+        for point in &self.points {
+            result.extend_from_slice(&point.to_affine().to_compressed());
+        }
+        result
+    }
+
+    /// Parse a commitment from the front of `bytes`, returning it and the rest
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "commitment truncated"));
+        }
+        let dimension = u16::from_be_bytes(bytes[..2].try_into().unwrap()) as usize;
+        if dimension == 0 || dimension > MAX_DIMENSION {
+            return Err(Error::decode(PROTOCOL, format!("commitment dimension {}", dimension)));
+        }
+        let end = 2 + dimension * dimension * 48;
+        if bytes.len() < end {
+            return Err(Error::decode(PROTOCOL, "commitment truncated"));
+        }
+        let points = bytes[2..end].chunks(48)
+            .map(|chunk| Option::<G1Affine>::from(G1Affine::from_compressed(chunk.try_into().unwrap()))
+                .map(G1Projective::from)
+                .ok_or_else(|| Error::decode(PROTOCOL, "invalid group element")))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((Self { dimension, points }, &bytes[end..]))
+    }
+}
+
+
+fn scalar_from_bytes(bytes: &[u8]) -> Result<Scalar> {
+    let bytes: [u8; 32] = bytes.try_into()
+        .map_err(|_| Error::decode(PROTOCOL, "expected a 32 byte scalar"))?;
+    Option::from(Scalar::from_bytes(&bytes))
+        .ok_or_else(|| Error::decode(PROTOCOL, "non-canonical scalar"))
+}
+
+
+/// Completed sharing as seen by one node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sharing {
+    pub id: Identifier,
+    pub commitment: Vec<G1Projective>,  // Feldman commitment to the shares
+    pub share: Scalar,
+}
+
+
+impl Sharing {
+    /// g^secret
+    pub fn public_key(&self) -> G1Projective {
+        self.commitment[0]
+    }
+}
+
+
+// Protocol Identifier
+pub const AVSS_IDENTIFIER: u8 = 6;
+const PROTOCOL: &str = "avss";
+
+// Node evaluation points are 1..=n, and a commitment must not outgrow a message
+const MAX_DIMENSION: usize = 128;
+
+// Message Types
+const MSG_SEND: u8 = 0;
+const MSG_ECHO: u8 = 1;
+const MSG_READY: u8 = 2;
+const MSG_REVEAL: u8 = 3;
+const MSG_ANSWER: u8 = 4;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvssMessage {
+    Send(BivariateCommitment, Vec<Scalar>),  // commitment, f(recipient, y)
+    Echo(BivariateCommitment, Scalar),  // commitment, f(sender, recipient)
+    Ready(BivariateCommitment, Scalar),  // commitment, f(sender, recipient)
+    Reveal(Scalar),  // the sender's share, asking the recipient to reveal its own
+    Answer(Scalar),  // the sender's share
+}
+
+
+impl AvssMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(AVSS_IDENTIFIER);
+
+        match self {
+            Self::Send(commitment, coefficients) => {
+                result.push(MSG_SEND);
+                result.extend_from_slice(&commitment.to_bytes());
+                for c in coefficients {
+                    result.extend_from_slice(&c.to_bytes());
+                }
+            }
+            Self::Echo(commitment, point) => {
+                result.push(MSG_ECHO);
+                result.extend_from_slice(&commitment.to_bytes());
+                result.extend_from_slice(&point.to_bytes());
+            }
+            Self::Ready(commitment, point) => {
+                result.push(MSG_READY);
+                result.extend_from_slice(&commitment.to_bytes());
+                result.extend_from_slice(&point.to_bytes());
+            }
+            Self::Reveal(share) => {
+                result.push(MSG_REVEAL);
+                result.extend_from_slice(&share.to_bytes());
+            }
+            Self::Answer(share) => {
+                result.push(MSG_ANSWER);
+                result.extend_from_slice(&share.to_bytes());
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != AVSS_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not an AVSS message"));
+        }
+
+        match bytes[1] {
+            MSG_SEND => {
+                let (commitment, rest) = BivariateCommitment::from_bytes(&bytes[2..])?;
+                if rest.len() != commitment.dimension * 32 {
+                    return Err(Error::decode(PROTOCOL, format!("SEND: expected {} coefficients", commitment.dimension)));
+                }
+                let coefficients = rest.chunks(32).map(scalar_from_bytes).collect::<Result<Vec<_>>>()?;
+                Ok(Self::Send(commitment, coefficients))
+            }
+            MSG_ECHO => {
+                let (commitment, rest) = BivariateCommitment::from_bytes(&bytes[2..])?;
+                Ok(Self::Echo(commitment, scalar_from_bytes(rest)?))
+            }
+            MSG_READY => {
+                let (commitment, rest) = BivariateCommitment::from_bytes(&bytes[2..])?;
+                Ok(Self::Ready(commitment, scalar_from_bytes(rest)?))
+            }
+            MSG_REVEAL => Ok(Self::Reveal(scalar_from_bytes(&bytes[2..])?)),
+            MSG_ANSWER => Ok(Self::Answer(scalar_from_bytes(&bytes[2..])?)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
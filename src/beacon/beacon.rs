@@ -0,0 +1,123 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+    time::MissedTickBehavior,
+};
+
+use crate::{constants::{DIGEST_SIZE, HTTP_REQUEST_TIMEOUT_MS, MAX_HTTP_CONNECTIONS, MAX_HTTP_REQUEST_SIZE}, node::Node, Error, Identifier, Result};
+
+use super::types::BeaconValue;
+
+
+/// Round `round` is signed as instance `Identifier(0, round)`
+pub fn round_id(round: u64) -> Identifier {
+    Identifier::new(0, round)
+}
+
+
+/// Produce a beacon value every `interval`, continuing after the latest value the node knows,
+/// or the one kept in `head_file` by a previous run.
+///
+/// Each round the node releases its signature share on the round number and the previous
+/// output (see `Node::sign`); the round ends once t+1 shares combine into the threshold
+/// signature. Nodes that fell behind get the signatures of past rounds from the others, and
+/// go on to the next round right away as long as they do, until they have caught up. Only
+/// rounds the others still keep can be caught up on this way.
+pub async fn run(node: Arc<Node>, interval: Duration, head_file: Option<PathBuf>) -> Result<()> {
+    let key = node.key().ok_or(Error::NoKey)?;
+    if let Some(path) = &head_file {
+        if let Some(head) = BeaconValue::load(path)? {
+            if !head.verify(&key.public_key()) {
+                return Err(Error::BeaconHead { path: path.display().to_string(), reason: "not signed with this key".into() });
+            }
+            if node.latest_beacon().is_none_or(|latest| latest.round < head.round) {
+                node.restore_beacon(head);
+            }
+        }
+    }
+    let (mut round, mut previous) = node.latest_beacon()
+        .map(|value| (value.round, value.randomness))
+        .unwrap_or((0, [0; DIGEST_SIZE]));
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut late = false;
+    loop {
+        if !late {
+            ticker.tick().await;
+        }
+        round += 1;
+        let (signature, from_peer) = node.approve(round_id(round), &BeaconValue::message(round, &previous)).await?;
+        late = from_peer;
+        let value = BeaconValue::new(round, previous, signature);
+        previous = value.randomness;
+        if let Some(path) = &head_file {
+            value.save(path)?;
+        }
+        node.publish_beacon(value);
+    }
+}
+
+
+/// Serve beacon values as JSON over HTTP at `address`:
+/// `GET /beacon/latest` and `GET /beacon/<round>` for rounds still kept by the node.
+///
+/// At most `MAX_HTTP_CONNECTIONS` are served at once, and a connection that has not been
+/// answered within `HTTP_REQUEST_TIMEOUT_MS` is closed, so idle clients cannot pile up.
+pub async fn serve(node: Arc<Node>, address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    let connections = Arc::new(Semaphore::new(MAX_HTTP_CONNECTIONS));
+    loop {
+        let permit = connections.clone().acquire_owned().await.expect("semaphore is never closed");
+        let (stream, _) = listener.accept().await?;
+        let node = node.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            match tokio::time::timeout(Duration::from_millis(HTTP_REQUEST_TIMEOUT_MS), respond(stream, &node)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Failed to answer beacon request: {}", e),
+                Err(_) => eprintln!("Beacon request timed out"),
+            }
+        });
+    }
+}
+
+
+async fn respond(mut stream: TcpStream, node: &Node) -> Result<()> {
+    // Only the request line matters
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_HTTP_REQUEST_SIZE {
+        let len = stream.read(&mut buffer).await?;
+        if len == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..len]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+
+    let value = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/beacon/latest")) => node.latest_beacon(),
+        (Some("GET"), Some(path)) => path.strip_prefix("/beacon/")
+            .and_then(|round| round.parse().ok())
+            .and_then(|round| node.beacon(round)),
+        _ => None,
+    };
+    let (status, body) = match value {
+        Some(value) => ("200 OK", value.to_json().to_string()),
+        None => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
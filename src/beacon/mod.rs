@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod beacon;
+
+// re-export all public items from beacon module
+pub use beacon::*;
+pub use types::*;
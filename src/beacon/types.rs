@@ -0,0 +1,112 @@
+use std::{fs, path::Path};
+
+use bls12_381::G1Projective;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{constants::DIGEST_SIZE, hex, threshold::{self, Signature, SIGNATURE_SIZE}, Error, Result};
+
+
+/// Randomness beacon settings
+#[derive(Deserialize, Clone, Debug)]
+pub struct BeaconConfig {
+    /// This node's key share from the dealer, see `KeyShare::save`
+    pub key_file: String,
+    /// Time between rounds in milliseconds
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    /// Where to serve beacon values over HTTP, e.g. "127.0.0.1:8080"
+    #[serde(default)]
+    pub http_address: Option<String>,
+    /// Where to keep the latest value, so that the beacon continues its chain after a restart
+    #[serde(default)]
+    pub head_file: Option<String>,
+}
+
+
+fn default_interval_ms() -> u64 {
+    1000
+}
+
+
+/// The beacon output of one round: the cluster's threshold signature on the round number
+/// and the previous output, and its hash. The signature is unique, so nobody can bias the
+/// output, and nobody can predict it before t+1 nodes released their shares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeaconValue {
+    pub round: u64,
+    pub previous: [u8; DIGEST_SIZE],  // randomness of the previous round, zeros before round 1
+    pub signature: Signature,
+    pub randomness: [u8; DIGEST_SIZE],  // SHA-256 of the signature
+}
+
+
+// Domain separation of beacon signatures from other threshold signatures
+const BEACON_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/beacon";
+
+// Round, previous output and signature; the randomness follows from the signature
+const HEAD_SIZE: usize = 8 + DIGEST_SIZE + SIGNATURE_SIZE;
+
+
+impl BeaconValue {
+    /// What round `round` signs
+    pub fn message(round: u64, previous: &[u8; DIGEST_SIZE]) -> Vec<u8> {
+        let mut message = BEACON_CONTEXT.to_vec();
+        message.extend_from_slice(&round.to_be_bytes());
+        message.extend_from_slice(previous);
+        message
+    }
+
+    pub fn new(round: u64, previous: [u8; DIGEST_SIZE], signature: Signature) -> Self {
+        let randomness = Sha256::digest(signature.to_bytes()).into();
+        Self { round, previous, signature, randomness }
+    }
+
+    /// Check the signature against the cluster's public key and the randomness against the signature
+    pub fn verify(&self, public_key: &G1Projective) -> bool {
+        threshold::verify(&self.signature, public_key, &Self::message(self.round, &self.previous))
+            && self.randomness == <[u8; DIGEST_SIZE]>::from(Sha256::digest(self.signature.to_bytes()))
+    }
+
+    /// Write this value to the head file at `path`, replacing the previous head in one step
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut bytes = self.round.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.previous);
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, bytes).map_err(|e| head_error(path, e))?;
+        fs::rename(&temporary, path).map_err(|e| head_error(path, e))
+    }
+
+    /// The value kept in the head file at `path`, None if there is none yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(head_error(path, e)),
+        };
+        if bytes.len() != HEAD_SIZE {
+            return Err(head_error(path, format!("expected {} bytes, got {}", HEAD_SIZE, bytes.len())));
+        }
+        let round = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+        let previous = bytes[8..8 + DIGEST_SIZE].try_into().unwrap();
+        let signature = Signature::from_bytes(&bytes[8 + DIGEST_SIZE..]).map_err(|e| head_error(path, e))?;
+        Ok(Some(Self::new(round, previous, signature)))
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "round": self.round,
+            "previous": hex::encode(&self.previous),
+            "signature": hex::encode(&self.signature.to_bytes()),
+            "randomness": hex::encode(&self.randomness),
+        })
+    }
+}
+
+
+fn head_error(path: &Path, reason: impl ToString) -> Error {
+    Error::BeaconHead { path: path.display().to_string(), reason: reason.to_string() }
+}
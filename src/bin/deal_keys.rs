@@ -0,0 +1,23 @@
+use asynchronous_broadcast_protocols::{dkg::KeyShare, Config};
+use bls12_381::Scalar;
+use ff::Field;
+use rand_core::OsRng;
+use std::{env::args, path::Path};
+
+/// Trusted dealer: write a key file with a share of a fresh random key for every node of a config
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = args().collect();
+    if args.len() < 3 {
+        return Err("Usage: deal_keys <config file> <output directory>".into());
+    }
+
+    let config = Config::load(&args[1]).await?;
+    let keys = KeyShare::deal(&config, Scalar::random(OsRng));
+    for key in &keys {
+        let path = Path::new(&args[2]).join(format!("key-{}.json", key.node));
+        key.save(&path)?;
+        println!("Wrote the key share of node {} to {}", key.node, path.display());
+    }
+    Ok(())
+}
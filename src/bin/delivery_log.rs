@@ -0,0 +1,28 @@
+use asynchronous_broadcast_protocols::{delivery_log::DeliveryLog, hex};
+use std::env::args;
+
+/// Audit a delivery log: check its hash chain and list its entries, all of them or those of one sender
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = args().collect();
+    if args.len() < 2 {
+        return Err("Usage: delivery_log <log file> [<sender> [<first sequence> [<last sequence>]]]".into());
+    }
+    // The node may be appending to the log right now, so it is only read
+    let log = DeliveryLog::open_read_only(&args[1])?;
+    let entries = match args.get(2) {
+        Some(sender) => {
+            let first: u64 = args.get(3).map_or(Ok(0), |s| s.parse())?;
+            let last: u64 = args.get(4).map_or(Ok(u64::MAX), |s| s.parse())?;
+            log.by_sender(sender.parse()?, first..=last)
+        }
+        None => log.range(..),
+    };
+    for entry in &entries {
+        println!("{} {} {} {} {}", entry.index, entry.id.sender(), entry.id.sequence(), hex::encode(&entry.digest), hex::encode(&entry.hash));
+    }
+    match log.head() {
+        Some(head) => println!("{} entries, head {}", log.len(), hex::encode(&head.hash)),
+        None => println!("empty"),
+    }
+    Ok(())
+}
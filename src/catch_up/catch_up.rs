@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use crate::{constants::*, transport::ReliableLink, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{CatchUpMessage, Certificate, RecentCertificates};
+
+
+/// Catch-up messages belong to no instance and carry the id of their sender
+fn message_id(config: &Config) -> Identifier {
+    Identifier::new(config.my_id, 0)
+}
+
+
+/// Ask `targets` for what they delivered at or above the requester's `watermarks`, starting with
+/// the first page or the one after certificate `after`
+pub async fn request(watermarks: Vec<(u16, u64)>, after: Option<Identifier>, targets: &[u16], config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let message = Message::new_authenticated(
+        message_id(config),
+        my_node.id,
+        MessageType::CatchUp(CatchUpMessage::Request { watermarks, after }),
+        &my_node.privkey,
+        config,
+    );
+    let bytes = message.to_bytes();
+    for target in targets {
+        link.send(*target, &bytes).await?;
+    }
+    Ok(())
+}
+
+
+/// Order of the certificates in responses: lowest sequence numbers first
+fn page_key(id: Identifier) -> (u64, u16) {
+    (id.sequence, id.sender)
+}
+
+
+/// The certificates a node with `watermarks` is missing, lowest sequence numbers first, as many
+/// as fit into one response. Pages go on after certificate `after`, so that a requester with a gap
+/// below instances it delivered gets past their certificates, which would otherwise fill every page.
+pub fn missing(watermarks: &[(u16, u64)], after: Option<Identifier>, certificates: &RecentCertificates) -> Vec<Certificate> {
+    let watermarks: BTreeMap<u16, u64> = watermarks.iter().copied().collect();
+    let mut candidates: Vec<&Certificate> = certificates.iter()
+        .filter(|certificate| certificate.id.sequence >= watermarks.get(&certificate.id.sender).copied().unwrap_or(0))
+        .filter(|certificate| after.is_none_or(|after| page_key(certificate.id) > page_key(after)))
+        .collect();
+    candidates.sort_by_key(|certificate| page_key(certificate.id));
+
+    // Leave room for the header, signature and length prefixes
+    let mut budget = MAX_MESSAGE_SIZE - MIN_MESSAGE_SIZE - 2;
+    let mut result = Vec::new();
+    for certificate in candidates.into_iter().take(MAX_CATCH_UP_CERTIFICATES) {
+        let size = 4 + certificate.to_bytes().len();
+        if size > budget {
+            break;
+        }
+        budget -= size;
+        result.push(certificate.clone());
+    }
+    result
+}
+
+
+/// Send `certificates` to node `to`, which asked for them
+pub async fn answer(to: u16, certificates: Vec<Certificate>, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let message = Message::new_authenticated(
+        message_id(config),
+        my_node.id,
+        MessageType::CatchUp(CatchUpMessage::Response(certificates)),
+        &my_node.privkey,
+        config,
+    );
+    link.send(to, &message.to_bytes()).await
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod catch_up;
+
+// re-export all public items from catch_up module
+pub use catch_up::*;
+pub use types::*;
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    constants::{HEADER_SIZE, IDENTIFIER_SIZE}, reliable_broadcast::{ReliableBroadcastMessage, RBC_IDENTIFIER},
+    Config, Error, Identifier, Message, MessageType, Result,
+};
+
+
+// Protocol Identifier
+pub const CATCH_UP_IDENTIFIER: u8 = 10;
+const PROTOCOL: &str = "catch-up";
+
+// Message Types
+const MSG_REQUEST: u8 = 0;
+const MSG_RESPONSE: u8 = 1;
+
+const WATERMARK_SIZE: usize = 2 + 8;
+
+
+#[derive(Debug, Clone)]
+pub enum CatchUpMessage {
+    Request {
+        watermarks: Vec<(u16, u64)>,  // per sender, the first sequence number the requester has not delivered
+        after: Option<Identifier>,  // the last certificate of the previous page
+    },
+    Response(Vec<Certificate>),  // deliveries above the requester's watermarks, one page
+}
+
+
+/// A delivered payload with the Ready messages of a delivery quorum, which prove to
+/// anyone holding the config that the instance delivered it
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub id: Identifier,
+    pub payload: Vec<u8>,
+    pub readies: Vec<Message>,
+}
+
+
+impl Certificate {
+    /// The epoch the instance ran in
+    pub fn epoch(&self) -> Option<u64> {
+        self.readies.first().map(|ready| ready.domain.epoch)
+    }
+
+    /// Check that the readies are signed votes for this payload by a delivery quorum of `config`
+    pub fn verify(&self, config: &Config) -> Result<()> {
+        let invalid = |reason| Err(Error::InvalidDeliveryCertificate(reason));
+        let digest: [u8; 32] = Sha256::digest(&self.payload).into();
+        let domain = config.domain();
+
+        let mut voters = HashSet::new();
+        for ready in &self.readies {
+            if ready.id != self.id {
+                return invalid("ready for another instance");
+            }
+            if ready.domain != domain {
+                return invalid("ready signed for another cluster or epoch");
+            }
+            if !matches!(ready.payload, MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d)) if d == digest) {
+                return invalid("not a ready for the payload");
+            }
+            let sender_config = config.get_node(ready.sender)
+                .ok_or(Error::UnknownNode(ready.sender))?;
+            let pubkey = ed25519_dalek::SigningKey::from_bytes(&sender_config.privkey).verifying_key();
+            if !ready.verify(pubkey) {
+                return Err(Error::BadSignature(ready.sender));
+            }
+            voters.insert(ready.sender);
+        }
+        if !config.quorums().is_delivery_quorum(&voters) {
+            return invalid("readies do not form a delivery quorum");
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(&self.id.to_bytes());
+        result.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        result.extend_from_slice(&self.payload);
+        for ready in &self.readies {
+            let bytes = ready.to_bytes();
+            result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+            result.extend_from_slice(&bytes);
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < IDENTIFIER_SIZE {
+            return Err(Error::decode(PROTOCOL, "certificate: missing identifier"));
+        }
+        let (id, mut rest) = bytes.split_at(IDENTIFIER_SIZE);
+        let id = Identifier::from_bytes(id.try_into().unwrap());
+        let payload = take_prefixed(&mut rest, "certificate payload")?.to_vec();
+
+        let mut readies = Vec::new();
+        while !rest.is_empty() {
+            let ready = take_prefixed(&mut rest, "ready")?;
+            // Only readies may be nested, which keeps certificates from nesting further
+            if ready.get(HEADER_SIZE) != Some(&RBC_IDENTIFIER) {
+                return Err(Error::decode(PROTOCOL, "certificate holds a message other than a ready"));
+            }
+            readies.push(Message::from_bytes(ready)?);
+        }
+        Ok(Self { id, payload, readies })
+    }
+}
+
+
+/// The certificates of the most recent deliveries, up to a capacity in bytes
+pub struct RecentCertificates {
+    certificates: BTreeMap<Identifier, (Certificate, usize)>,  // with their encoded size
+    order: VecDeque<Identifier>,  // oldest first
+    bytes: usize,
+    capacity: usize,
+}
+
+
+impl RecentCertificates {
+    pub fn new(capacity: usize) -> Self {
+        Self { certificates: BTreeMap::new(), order: VecDeque::new(), bytes: 0, capacity }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Certificate> {
+        self.certificates.values().map(|(certificate, _)| certificate)
+    }
+
+    /// Keep `certificate`, forgetting the oldest ones beyond the capacity
+    pub fn insert(&mut self, certificate: Certificate) {
+        let id = certificate.id;
+        if self.certificates.contains_key(&id) {
+            return;
+        }
+        let size = certificate.to_bytes().len();
+        self.certificates.insert(id, (certificate, size));
+        self.order.push_back(id);
+        self.bytes += size;
+        while self.bytes > self.capacity {
+            let Some(oldest) = self.order.pop_front() else { break };
+            if let Some((_, size)) = self.certificates.remove(&oldest) {
+                self.bytes -= size;
+            }
+        }
+    }
+}
+
+
+/// Split a length-prefixed field (4 bytes big-endian) off the front of `bytes`
+fn take_prefixed<'a>(bytes: &mut &'a [u8], what: &str) -> Result<&'a [u8]> {
+    if bytes.len() < 4 {
+        return Err(Error::decode(PROTOCOL, format!("{}: missing length", what)));
+    }
+    let len = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+    let Some(field) = bytes.get(4..4 + len) else {
+        return Err(Error::decode(PROTOCOL, format!("{}: truncated ({} bytes)", what, len)));
+    };
+    *bytes = &bytes[4 + len..];
+    Ok(field)
+}
+
+
+impl CatchUpMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(CATCH_UP_IDENTIFIER);
+
+        match self {
+            Self::Request { watermarks, after } => {
+                result.push(MSG_REQUEST);
+                match after {
+                    Some(after) => {
+                        result.push(1);
+                        result.extend_from_slice(&after.to_bytes());
+                    }
+                    None => result.push(0),
+                }
+                for (sender, watermark) in watermarks {
+                    result.extend_from_slice(&sender.to_be_bytes());
+                    result.extend_from_slice(&watermark.to_be_bytes());
+                }
+            }
+            Self::Response(certificates) => {
+                result.push(MSG_RESPONSE);
+                for certificate in certificates {
+                    let bytes = certificate.to_bytes();
+                    result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                    result.extend_from_slice(&bytes);
+                }
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != CATCH_UP_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a catch-up message"));
+        }
+
+        match bytes[1] {
+            MSG_REQUEST => {
+                let (after, rest) = match bytes.get(2) {
+                    Some(0) => (None, &bytes[3..]),
+                    Some(1) if bytes.len() >= 3 + IDENTIFIER_SIZE => {
+                        let (after, rest) = bytes[3..].split_at(IDENTIFIER_SIZE);
+                        (Some(Identifier::from_bytes(after.try_into().unwrap())), rest)
+                    }
+                    _ => return Err(Error::decode(PROTOCOL, "REQUEST: missing page")),
+                };
+                let chunks = rest.chunks_exact(WATERMARK_SIZE);
+                if !chunks.remainder().is_empty() {
+                    return Err(Error::decode(PROTOCOL, format!("REQUEST: expected a multiple of {} bytes", WATERMARK_SIZE)));
+                }
+                let watermarks = chunks
+                    .map(|chunk| (
+                        u16::from_be_bytes(chunk[..2].try_into().unwrap()),
+                        u64::from_be_bytes(chunk[2..].try_into().unwrap()),
+                    ))
+                    .collect();
+                Ok(Self::Request { watermarks, after })
+            }
+            MSG_RESPONSE => {
+                let mut rest = &bytes[2..];
+                let mut certificates = Vec::new();
+                while !rest.is_empty() {
+                    certificates.push(Certificate::from_bytes(take_prefixed(&mut rest, "certificate")?)?);
+                }
+                Ok(Self::Response(certificates))
+            }
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
+
+
+/// The instances a node delivered, per sender: every sequence number below the
+/// sender's watermark, and those in its set above it
+#[derive(Debug, Default)]
+pub struct Watermarks {
+    senders: BTreeMap<u16, (u64, BTreeSet<u64>)>,
+}
+
+
+impl Watermarks {
+    /// Record `id` as delivered. Returns false if it was already.
+    pub fn insert(&mut self, id: Identifier) -> bool {
+        let (watermark, above) = self.senders.entry(id.sender).or_default();
+        if id.sequence < *watermark || !above.insert(id.sequence) {
+            return false;
+        }
+        while above.remove(watermark) {
+            *watermark += 1;
+        }
+        true
+    }
+
+    pub fn contains(&self, id: Identifier) -> bool {
+        self.senders.get(&id.sender)
+            .is_some_and(|(watermark, above)| id.sequence < *watermark || above.contains(&id.sequence))
+    }
+
+    /// The first sequence number of every sender that is not delivered yet
+    pub fn to_vec(&self) -> Vec<(u16, u64)> {
+        self.senders.iter().map(|(sender, (watermark, _))| (*sender, *watermark)).collect()
+    }
+}
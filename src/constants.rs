@@ -6,5 +6,71 @@ pub const SIGNATURE_SIZE: usize = 64;
 pub const PRIVATE_KEY_SIZE: usize = 32;
 pub const DIGEST_SIZE: usize = 32;
 pub const IDENTIFIER_SIZE: usize = 10;
-pub const HEADER_SIZE: usize = IDENTIFIER_SIZE + 2;
+pub const CLUSTER_ID_SIZE: usize = 16;
+pub const ENVELOPE_SIZE: usize = 1 + CLUSTER_ID_SIZE + 8;
+pub const HEADER_SIZE: usize = ENVELOPE_SIZE + IDENTIFIER_SIZE + 2;
 pub const MIN_MESSAGE_SIZE: usize = HEADER_SIZE + SIGNATURE_SIZE;
+pub const WIRE_VERSION: u8 = 1;
+
+
+// Fragmentation
+pub const FRAGMENT_HEADER_SIZE: usize = 8 + 2 + 2;
+pub const FRAGMENT_PAYLOAD_SIZE: usize = MESSAGE_BUFFER_SIZE - FRAGMENT_HEADER_SIZE;
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+pub const MAX_FRAGMENTS: usize = MAX_MESSAGE_SIZE.div_ceil(FRAGMENT_PAYLOAD_SIZE);
+pub const MAX_REASSEMBLY_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+pub const REASSEMBLY_TIMEOUT_MS: u64 = 5000;
+// Retransmissions resend whole messages, so a burst of fragments must fit into the socket buffer
+pub const SOCKET_RECEIVE_BUFFER_SIZE: usize = 4 * MAX_MESSAGE_SIZE;
+
+
+// Reliable links
+pub const LINK_HEADER_SIZE: usize = 1 + 2 + 8 + 8;
+pub const RETRANSMIT_TICK_MS: u64 = 50;
+pub const INITIAL_RETRANSMIT_TIMEOUT_MS: u64 = 200;
+pub const MAX_RETRANSMIT_TIMEOUT_MS: u64 = 10_000;
+pub const MAX_RETRANSMIT_ATTEMPTS: u32 = 30;
+pub const RECEIVE_WINDOW_SIZE: u64 = 65536;
+// Every broadcast protocol sends the payload whole in one message, behind two type bytes
+pub const MAX_BROADCAST_PAYLOAD_SIZE: usize = MAX_MESSAGE_SIZE - LINK_HEADER_SIZE - MAC_SIZE - MIN_MESSAGE_SIZE - 2;
+
+
+// Equivocation evidence
+// Votes larger than this cannot both fit into one evidence message with its link frame
+pub const MAX_EVIDENCE_VOTE_SIZE: usize = (MAX_MESSAGE_SIZE - LINK_HEADER_SIZE - MAC_SIZE - MIN_MESSAGE_SIZE - 5) / 2;
+pub const MAX_OBSERVED_VOTES: usize = 1 << 16;  // first votes a node remembers
+pub const MAX_OBSERVED_VOTE_BYTES: usize = 64 * 1024 * 1024;  // of which it keeps the messages
+
+
+// Session-key authentication
+pub const X25519_KEY_SIZE: usize = 32;
+pub const MAC_KEY_SIZE: usize = 32;
+pub const MAC_SIZE: usize = 32;
+pub const HANDSHAKE_RETRY_MS: u64 = 1000;
+
+
+// Threshold signatures
+pub const MAX_KEPT_SIGNATURES: usize = 4096;  // the most recent ones, handed to approvers that missed shares
+
+
+// Randomness beacon
+pub const BEACON_HISTORY: usize = 1024;  // rounds a node keeps for HTTP clients
+pub const MAX_HTTP_REQUEST_SIZE: usize = 8192;
+pub const HTTP_REQUEST_TIMEOUT_MS: u64 = 5000;  // to read a request and answer it
+pub const MAX_HTTP_CONNECTIONS: usize = 256;  // served at once, further ones wait to be accepted
+
+
+// Key generation
+pub const MAX_PENDING_SESSIONS_PER_PEER: usize = 4;  // sessions a peer may open here before the application starts them
+pub const SESSION_LINGER_MS: u64 = 30_000;  // a finished session keeps serving slower nodes this long
+
+
+// Reconfiguration
+pub const MAX_FUTURE_EPOCH_MESSAGES: usize = 4096;  // buffered until this node installs the next epoch
+pub const DEPARTED_PEER_GRACE_MS: u64 = 60_000;  // removed nodes stay reachable for instances of the old epoch
+
+
+// Catch-up
+pub const MAX_CATCH_UP_CERTIFICATES: usize = 256;  // per response; the requester asks again for more
+pub const CATCH_UP_INTERVAL_MS: u64 = 1000;  // between requests triggered by instances far ahead
+pub const MAX_KEPT_CERTIFICATE_BYTES: usize = 64 * 1024 * 1024;  // of the most recent deliveries, served to lagging nodes
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::{transport::ReliableLink, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::CrashBroadcastMessage;
+
+
+/// Start instance `id` as its sender by sending the payload to every node (including this one)
+pub async fn broadcast(id: Identifier, message: Vec<u8>, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
+
+    let message = Message::new_authenticated(
+        id,
+        my_node.id,
+        MessageType::CrashBroadcast(CrashBroadcastMessage::Relay(message)),
+        &my_node.privkey,
+        config,
+    );
+    link.send_to_all(&message.to_bytes()).await
+}
+
+
+/// Relay-on-first-receipt reliable broadcast for crash faults only.
+///
+/// The first copy of the payload is relayed to every other node before it is delivered,
+/// so once any correct node delivers, the reliable links carry it to all correct nodes.
+pub async fn receive(id: Identifier, mut rx: mpsc::Receiver<Message>, config: &Config, link: Arc<ReliableLink>) -> Result<Vec<u8>> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+
+    while let Some(message) = rx.recv().await {
+        let MessageType::CrashBroadcast(CrashBroadcastMessage::Relay(m)) = message.payload else { continue };
+
+        let relay = Message::new_authenticated(
+            id,
+            my_node.id,
+            MessageType::CrashBroadcast(CrashBroadcastMessage::Relay(m.clone())),
+            &my_node.privkey,
+            config,
+        );
+        let relay_bytes = relay.to_bytes();
+        // The node we got it from already has it
+        for node in config.nodes.iter().filter(|n| n.id != my_node.id && n.id != message.sender) {
+            link.send(node.id, &relay_bytes).await?;
+        }
+        return Ok(m);
+    }
+    Err(Error::InstanceClosed(id))
+}
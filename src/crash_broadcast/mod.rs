@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod crash_broadcast;
+
+// re-export all public items from crash_broadcast module
+pub use crash_broadcast::*;
+pub use types::*;
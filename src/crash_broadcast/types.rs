@@ -0,0 +1,47 @@
+use crate::{Error, Result};
+
+
+// Protocol Identifier
+pub const CRASH_IDENTIFIER: u8 = 2;
+const PROTOCOL: &str = "crash broadcast";
+
+// Message Types
+const MSG_RELAY: u8 = 0;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CrashBroadcastMessage {
+    /// The broadcast payload, sent by the broadcaster and relayed once by every node
+    Relay(Vec<u8>),
+}
+
+
+impl CrashBroadcastMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(CRASH_IDENTIFIER);
+
+        match self {
+            Self::Relay(msg) => {
+                result.push(MSG_RELAY);
+                result.extend_from_slice(msg);
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != CRASH_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a Crash Broadcast message"));
+        }
+
+        match bytes[1] {
+            MSG_RELAY => Ok(Self::Relay(bytes[2..].to_vec())),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::{Bound, RangeBounds},
+    sync::Mutex,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{constants::DIGEST_SIZE, Error, Identifier, Result};
+
+use super::types::{Entry, ENTRY_HEADER_SIZE};
+
+
+// Every entry is framed as length (4 bytes) || entry header || payload
+const FRAME_LENGTH_SIZE: usize = 4;
+
+
+/// Append-only, hash-chained log of everything a node delivered, indexed by
+/// identifier and by sender. Nodes that delivered the same instances in the same
+/// order end up with the same head hash.
+pub struct DeliveryLog {
+    path: String,
+    read_only: bool,
+    state: Mutex<State>,
+}
+
+
+struct State {
+    file: File,
+    entries: Vec<Entry>,
+    payloads: Vec<(u64, usize)>,  // offset and length of every entry's payload in the file
+    by_id: HashMap<Identifier, u64>,
+    by_sender: BTreeMap<u16, BTreeMap<u64, u64>>,  // sender -> sequence -> index
+}
+
+
+impl State {
+    fn push(&mut self, entry: Entry, payload: (u64, usize)) {
+        self.by_id.insert(entry.id, entry.index);
+        self.by_sender.entry(entry.id.sender).or_default().insert(entry.id.sequence, entry.index);
+        self.entries.push(entry);
+        self.payloads.push(payload);
+    }
+}
+
+
+impl DeliveryLog {
+    /// Open the log at `path` and check its hash chain. An entry torn by a crash in the
+    /// middle of an append is cut off; an entry that breaks the chain is an error.
+    pub fn open(path: &str) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::DeliveryLog { path: path.to_string(), reason: e.to_string() })?;
+        Self::load(path, file, false)
+    }
+
+    /// Open the log at `path` for auditing, e.g. while its node is running: the hash chain is
+    /// checked as by `open`, but the file is never written, and a torn or half-written entry at
+    /// the end is left alone instead of being cut off. `append` fails on such a log.
+    pub fn open_read_only(path: &str) -> Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| Error::DeliveryLog { path: path.to_string(), reason: e.to_string() })?;
+        Self::load(path, file, true)
+    }
+
+    fn load(path: &str, mut file: File, read_only: bool) -> Result<Self> {
+        let error = |reason: String| Error::DeliveryLog { path: path.to_string(), reason };
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(|e| error(e.to_string()))?;
+
+        let mut state = State {
+            file,
+            entries: Vec::new(),
+            payloads: Vec::new(),
+            by_id: HashMap::new(),
+            by_sender: BTreeMap::new(),
+        };
+        let mut offset = 0;
+        while bytes.len() - offset >= FRAME_LENGTH_SIZE {
+            let len = u32::from_be_bytes(bytes[offset..offset + FRAME_LENGTH_SIZE].try_into().unwrap()) as usize;
+            let start = offset + FRAME_LENGTH_SIZE;
+            let Some(frame) = bytes.get(start..start + len) else { break };
+            if len < ENTRY_HEADER_SIZE {
+                return Err(error(format!("entry {} is too short", state.entries.len())));
+            }
+            let (header, payload) = frame.split_at(ENTRY_HEADER_SIZE);
+            let entry = Entry::from_bytes(header.try_into().unwrap());
+            entry.verify(state.entries.last()).map_err(|e| error(e.to_string()))?;
+            if entry.digest != <[u8; DIGEST_SIZE]>::from(Sha256::digest(payload)) {
+                return Err(error(format!("payload of entry {} does not match its digest", entry.index)));
+            }
+            if state.by_id.contains_key(&entry.id) {
+                return Err(error(format!("{:?} is delivered twice", entry.id)));
+            }
+            state.push(entry, ((start + ENTRY_HEADER_SIZE) as u64, payload.len()));
+            offset = start + len;
+        }
+        if offset < bytes.len() && !read_only {
+            eprintln!("Cutting {} bytes of a torn entry off {}", bytes.len() - offset, path);
+            state.file.set_len(offset as u64).map_err(|e| error(e.to_string()))?;
+            state.file.sync_all().map_err(|e| error(e.to_string()))?;
+        }
+        Ok(Self { path: path.to_string(), read_only, state: Mutex::new(state) })
+    }
+
+    /// Record `payload` as delivered in instance `id` and return its entry, synced to disk.
+    /// Logging an instance again returns its entry, unless the payload differs.
+    pub fn append(&self, id: Identifier, payload: &[u8]) -> Result<Entry> {
+        if self.read_only {
+            return Err(self.error("opened read-only".to_string()));
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(&index) = state.by_id.get(&id) {
+            let entry = state.entries[index as usize];
+            if entry.digest != <[u8; DIGEST_SIZE]>::from(Sha256::digest(payload)) {
+                return Err(self.error(format!("{:?} was delivered with another payload", id)));
+            }
+            return Ok(entry);
+        }
+
+        let entry = Entry::new(state.entries.last(), id, payload);
+        let mut frame = Vec::with_capacity(FRAME_LENGTH_SIZE + ENTRY_HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&((ENTRY_HEADER_SIZE + payload.len()) as u32).to_be_bytes());
+        frame.extend_from_slice(&entry.to_bytes());
+        frame.extend_from_slice(payload);
+
+        let end = state.file.seek(SeekFrom::End(0)).map_err(|e| self.error(e.to_string()))?;
+        state.file.write_all(&frame).map_err(|e| self.error(e.to_string()))?;
+        state.file.sync_data().map_err(|e| self.error(e.to_string()))?;
+        state.push(entry, (end + (FRAME_LENGTH_SIZE + ENTRY_HEADER_SIZE) as u64, payload.len()));
+        Ok(entry)
+    }
+
+    /// The entry of instance `id`, if it was delivered
+    pub fn entry(&self, id: Identifier) -> Option<Entry> {
+        let state = self.state.lock().unwrap();
+        state.by_id.get(&id).map(|&index| state.entries[index as usize])
+    }
+
+    /// The digest of the payload delivered in instance `id`, if it was delivered
+    pub fn digest(&self, id: Identifier) -> Option<[u8; DIGEST_SIZE]> {
+        self.entry(id).map(|entry| entry.digest)
+    }
+
+    /// Entries at the positions in `indexes`, in delivery order
+    pub fn range(&self, indexes: impl RangeBounds<u64>) -> Vec<Entry> {
+        let state = self.state.lock().unwrap();
+        let start = match indexes.start_bound() {
+            Bound::Included(&index) => index,
+            Bound::Excluded(&index) => index.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match indexes.end_bound() {
+            Bound::Included(&index) => index.saturating_add(1),
+            Bound::Excluded(&index) => index,
+            Bound::Unbounded => u64::MAX,
+        };
+        let end = end.min(state.entries.len() as u64);
+        if start >= end {
+            return Vec::new();
+        }
+        state.entries[start as usize..end as usize].to_vec()
+    }
+
+    /// Entries of `sender`'s instances with a sequence number in `sequences`, by sequence number
+    pub fn by_sender(&self, sender: u16, sequences: impl RangeBounds<u64>) -> Vec<Entry> {
+        let state = self.state.lock().unwrap();
+        let Some(indexes) = state.by_sender.get(&sender) else { return Vec::new() };
+        indexes.iter()
+            .filter(|(sequence, _)| sequences.contains(sequence))
+            .map(|(_, &index)| state.entries[index as usize])
+            .collect()
+    }
+
+    /// The payload logged with `entry`
+    pub fn payload(&self, entry: &Entry) -> Result<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        let Some(&(offset, len)) = state.payloads.get(entry.index as usize) else {
+            return Err(self.error(format!("no entry {}", entry.index)));
+        };
+        let mut payload = vec![0; len];
+        state.file.seek(SeekFrom::Start(offset)).map_err(|e| self.error(e.to_string()))?;
+        state.file.read_exact(&mut payload).map_err(|e| self.error(e.to_string()))?;
+        Ok(payload)
+    }
+
+    /// The last entry, whose hash commits to the whole log
+    pub fn head(&self) -> Option<Entry> {
+        self.state.lock().unwrap().entries.last().copied()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.state.lock().unwrap().entries.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn error(&self, reason: String) -> Error {
+        Error::DeliveryLog { path: self.path.clone(), reason }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod delivery_log;
+
+// re-export all public items from delivery_log module
+pub use delivery_log::*;
+pub use types::*;
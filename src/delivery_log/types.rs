@@ -0,0 +1,88 @@
+use sha2::{Digest, Sha256};
+
+use crate::{constants::{DIGEST_SIZE, IDENTIFIER_SIZE}, Error, Identifier, Result};
+
+
+const PROTOCOL: &str = "delivery log entry";
+const ENTRY_HASH_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/delivery-log";
+
+pub const ENTRY_HEADER_SIZE: usize = 8 + IDENTIFIER_SIZE + 3 * DIGEST_SIZE;
+
+
+/// One delivery in the log. Every entry commits to the one before it, so the hash of
+/// the last entry commits to everything the node delivered, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// Position in the log, from 0
+    pub index: u64,
+    pub id: Identifier,
+    /// SHA-256 of the delivered payload
+    pub digest: [u8; DIGEST_SIZE],
+    /// Hash of the previous entry, zero for the first
+    pub prev: [u8; DIGEST_SIZE],
+    pub hash: [u8; DIGEST_SIZE],
+}
+
+
+impl Entry {
+    /// The entry after `prev` (or the first one) for `payload` delivered in instance `id`
+    pub fn new(prev: Option<&Entry>, id: Identifier, payload: &[u8]) -> Self {
+        let (index, prev) = match prev {
+            Some(prev) => (prev.index + 1, prev.hash),
+            None => (0, [0; DIGEST_SIZE]),
+        };
+        let mut entry = Self { index, id, digest: Sha256::digest(payload).into(), prev, hash: [0; DIGEST_SIZE] };
+        entry.hash = entry.compute_hash();
+        entry
+    }
+
+    fn compute_hash(&self) -> [u8; DIGEST_SIZE] {
+        let mut hasher = Sha256::new();
+        hasher.update(ENTRY_HASH_CONTEXT);
+        hasher.update(self.prev);
+        hasher.update(self.index.to_be_bytes());
+        hasher.update(self.id.to_bytes());
+        hasher.update(self.digest);
+        hasher.finalize().into()
+    }
+
+    /// Check that the entry follows `prev` and that its hash covers its contents
+    pub fn verify(&self, prev: Option<&Entry>) -> Result<()> {
+        let (index, prev_hash) = match prev {
+            Some(prev) => (prev.index + 1, prev.hash),
+            None => (0, [0; DIGEST_SIZE]),
+        };
+        if self.index != index {
+            return Err(Error::decode(PROTOCOL, format!("entry {} where {} was expected", self.index, index)));
+        }
+        if self.prev != prev_hash {
+            return Err(Error::decode(PROTOCOL, format!("entry {} does not follow the previous entry", self.index)));
+        }
+        if self.hash != self.compute_hash() {
+            return Err(Error::decode(PROTOCOL, format!("hash of entry {} does not match its contents", self.index)));
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_HEADER_SIZE] {
+        let mut result = [0; ENTRY_HEADER_SIZE];
+        result[..8].copy_from_slice(&self.index.to_be_bytes());
+        result[8..8 + IDENTIFIER_SIZE].copy_from_slice(&self.id.to_bytes());
+        let digests = &mut result[8 + IDENTIFIER_SIZE..];
+        digests[..DIGEST_SIZE].copy_from_slice(&self.digest);
+        digests[DIGEST_SIZE..2 * DIGEST_SIZE].copy_from_slice(&self.prev);
+        digests[2 * DIGEST_SIZE..].copy_from_slice(&self.hash);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8; ENTRY_HEADER_SIZE]) -> Self {
+        let digests = &bytes[8 + IDENTIFIER_SIZE..];
+        Self {
+            index: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            id: Identifier::from_bytes(bytes[8..8 + IDENTIFIER_SIZE].try_into().unwrap()),
+            digest: digests[..DIGEST_SIZE].try_into().unwrap(),
+            prev: digests[DIGEST_SIZE..2 * DIGEST_SIZE].try_into().unwrap(),
+            hash: digests[2 * DIGEST_SIZE..].try_into().unwrap(),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::{BTreeSet, HashMap}, sync::Arc, time::Duration};
+
+use bls12_381::{G1Projective, Scalar};
+use ff::Field;
+use rand_core::OsRng;
+use tokio::{sync::{broadcast::error::RecvError, mpsc, watch}, time::Instant};
+
+use crate::{agreement::{Agreement, AgreementMessage, PROPOSAL_TAG}, constants::{IDENTIFIER_SIZE, SESSION_LINGER_MS}, node::Node, polynomial::Committee, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::KeyShare;
+
+
+/// Agreement sessions with this `sender` in their id generate keys, numbered by `sequence`
+pub const SESSION_KIND: u16 = 0;
+
+// Value slot announcing a node's own dealing
+const DEALING_TAG: u8 = 1;
+
+
+/// Id of the messages of key generation session `session`
+pub fn session_id(session: u64) -> Identifier {
+    Identifier::new(SESSION_KIND, session)
+}
+
+
+fn encode_dealings(dealings: &[Identifier]) -> Vec<u8> {
+    dealings.iter().flat_map(|id| id.to_bytes()).collect()
+}
+
+
+fn decode_dealings(bytes: &[u8]) -> Option<Vec<Identifier>> {
+    if !bytes.len().is_multiple_of(IDENTIFIER_SIZE) {
+        return None;
+    }
+    Some(bytes.chunks(IDENTIFIER_SIZE).map(|chunk| Identifier::from_bytes(chunk.try_into().unwrap())).collect())
+}
+
+
+async fn send_to_all(messages: Vec<AgreementMessage>, session: u64, config: &Config, node: &Node) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    for message in messages {
+        let message = Message::new_authenticated(
+            session_id(session),
+            my_node.id,
+            MessageType::Agreement(message),
+            &my_node.privkey,
+            config,
+        );
+        node.link().send_to_all(&message.to_bytes()).await?;
+    }
+    Ok(())
+}
+
+
+/// A proposal we can vote for: t+1 or more dealings of distinct dealers, each the one
+/// its dealer announced, and all completed here
+fn acceptable(proposal: &[u8], announced: &HashMap<u16, Identifier>, t: usize, node: &Node) -> bool {
+    let Some(dealings) = decode_dealings(proposal) else { return false };
+    let dealers: BTreeSet<u16> = dealings.iter().map(|id| id.sender).collect();
+    dealers.len() == dealings.len()
+        && dealings.len() > t
+        && dealings.iter().all(|id| announced.get(&id.sender) == Some(id) && node.sharing(*id).is_some())
+}
+
+
+/// Sum up the dealings of every included proposal, once all of them have arrived and completed
+fn derive(agreement: &Agreement, included: &[u16], config: &Config, node: &Node) -> Option<KeyShare> {
+    let mut dealings = BTreeSet::new();
+    for &proposer in included {
+        dealings.extend(decode_dealings(agreement.value(proposer, PROPOSAL_TAG)?)?);
+    }
+
+    let dimension = config.fault_tolerance() + 1;
+    let mut commitment = vec![G1Projective::identity(); dimension];
+    let mut share = Scalar::zero();
+    for id in dealings {
+        let sharing = node.sharing(id)?;
+        if sharing.commitment.len() != dimension {
+            return None;
+        }
+        for (sum, c) in commitment.iter_mut().zip(&sharing.commitment) {
+            *sum += c;
+        }
+        share += sharing.share;
+    }
+    Some(KeyShare { node: config.my_id, commitment, share })
+}
+
+
+/// Asynchronous distributed key generation (after Kate, Goldberg and others):
+/// every node deals a random secret with AVSS and announces the dealing, proposes the
+/// t+1 or more announced dealings that have completed locally, and the nodes agree on a
+/// common subset of proposals. The key is the sum of the dealings in the agreed proposals,
+/// each node's share the sum of its shares of them, and the public key the sum of their
+/// commitments' constant terms. At least one dealing is from a correct node, so nobody
+/// knows the key. There is no key yet to draw a common coin from, so the agreement flips
+/// local coins.
+///
+/// Installs the key share on the node and sends it through `result` once derived,
+/// then keeps serving the session for slower nodes for `SESSION_LINGER_MS`.
+pub async fn run(node: Arc<Node>, session: u64, mut rx: mpsc::Receiver<Message>, result: watch::Sender<Option<KeyShare>>) -> Result<()> {
+    let config = node.config();
+    let t = config.fault_tolerance();
+    let mut agreement = Agreement::new(config.my_id, &Committee::of(&config));
+    let mut sharings = node.subscribe_sharings();
+
+    let dealing = node.share(Scalar::random(OsRng)).await?;
+    let messages = agreement.broadcast(DEALING_TAG, encode_dealings(&[dealing]));
+    send_to_all(messages, session, &config, &node).await?;
+    let mut proposed = false;
+    let mut finished = None;
+
+    loop {
+        if result.borrow().is_none() {
+            let announced: HashMap<u16, Identifier> = agreement.values(DEALING_TAG)
+                .filter_map(|(dealer, value)| match decode_dealings(value)?.as_slice() {
+                    [id] if id.sender == dealer => Some((dealer, *id)),
+                    _ => None,
+                })
+                .collect();
+
+            if !proposed {
+                let completed: Vec<Identifier> = announced.values().copied()
+                    .filter(|id| node.sharing(*id).is_some())
+                    .collect::<BTreeSet<_>>().into_iter().collect();
+                if completed.len() > t {
+                    proposed = true;
+                    let messages = agreement.propose(encode_dealings(&completed));
+                    send_to_all(messages, session, &config, &node).await?;
+                }
+            }
+
+            let accepted: Vec<u16> = agreement.pending_proposals().into_iter()
+                .filter(|(_, proposal)| acceptable(proposal, &announced, t, &node))
+                .map(|(proposer, _)| proposer)
+                .collect();
+            for proposer in accepted {
+                let messages = agreement.accept(proposer);
+                send_to_all(messages, session, &config, &node).await?;
+            }
+
+            if let Some(key) = agreement.decided().and_then(|included| derive(&agreement, &included, &config, &node)) {
+                node.install_key(key.clone());
+                result.send_replace(Some(key));
+                finished = Some(Instant::now() + Duration::from_millis(SESSION_LINGER_MS));
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(finished.unwrap_or_else(Instant::now)), if finished.is_some() => return Ok(()),
+            message = rx.recv() => {
+                let Some(message) = message else { return Ok(()) };
+                let MessageType::Agreement(agreement_message) = message.payload else { continue };
+                let messages = agreement.handle(message.sender, agreement_message);
+                send_to_all(messages, session, &config, &node).await?;
+            }
+            sharing = sharings.recv() => {
+                if let Err(RecvError::Closed) = sharing {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod dkg;
+
+// re-export all public items from dkg module
+pub use dkg::*;
+pub use types::*;
@@ -0,0 +1,109 @@
+use std::{fs, path::Path};
+
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use group::Curve;
+use serde::{Deserialize, Serialize};
+
+use crate::{hex, polynomial::{self, Polynomial}, Config, Error, Result};
+
+
+/// One node's share of a threshold key: any t+1 shares determine the secret key,
+/// t of them reveal nothing about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyShare {
+    pub node: u16,
+    /// Feldman commitment to the polynomial the shares lie on. Its constant term is the public key.
+    pub commitment: Vec<G1Projective>,
+    pub share: Scalar,
+}
+
+
+/// On-disk form of a key share, as JSON with hex-encoded group elements
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    node: u16,
+    commitment: Vec<String>,
+    share: String,
+}
+
+
+impl KeyShare {
+    /// Shares of `secret` for every node of `config`, as a trusted dealer hands them out
+    pub fn deal(config: &Config, secret: Scalar) -> Vec<Self> {
+        let polynomial = Polynomial::random(secret, config.fault_tolerance());
+        let commitment = polynomial.commit();
+        config.node_ids().into_iter().enumerate()
+            .map(|(index, node)| Self {
+                node,
+                commitment: commitment.clone(),
+                share: polynomial.evaluate(Scalar::from(index as u64 + 1)),
+            })
+            .collect()
+    }
+
+    /// g^secret
+    pub fn public_key(&self) -> G1Projective {
+        self.commitment[0]
+    }
+
+    /// Number of faults the key tolerates: t+1 shares are needed to use it
+    pub fn threshold(&self) -> usize {
+        self.commitment.len() - 1
+    }
+
+    /// g^share of `node`, computed from the commitment alone
+    pub fn public_share(&self, config: &Config, node: u16) -> Result<G1Projective> {
+        Ok(polynomial::evaluate_commitment(&self.commitment, polynomial::node_point(config, node)?))
+    }
+
+    /// Check that the share lies on the committed polynomial, i.e. matches the public key
+    pub fn verify(&self, config: &Config) -> Result<()> {
+        if self.commitment.is_empty() {
+            return Err(Error::InvalidKeyShare("empty commitment"));
+        }
+        if G1Projective::generator() * self.share != self.public_share(config, self.node)? {
+            return Err(Error::InvalidKeyShare("share does not match the public key"));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = KeyFile {
+            node: self.node,
+            commitment: self.commitment.iter().map(|c| hex::encode(&c.to_affine().to_compressed())).collect(),
+            share: hex::encode(&self.share.to_bytes()),
+        };
+        let json = serde_json::to_string_pretty(&file).expect("key file serializes");
+        fs::write(path.as_ref(), json).map_err(|e| key_file_error(path.as_ref(), e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| key_file_error(path, e))?;
+        let file: KeyFile = serde_json::from_str(&json).map_err(|e| key_file_error(path, e))?;
+
+        let commitment = file.commitment.iter()
+            .map(|c| {
+                let bytes: [u8; 48] = hex::decode(c).and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| key_file_error(path, "commitment: expected 48 hex bytes"))?;
+                Option::<G1Affine>::from(G1Affine::from_compressed(&bytes))
+                    .map(G1Projective::from)
+                    .ok_or_else(|| key_file_error(path, "commitment: invalid group element"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if commitment.is_empty() {
+            return Err(key_file_error(path, "empty commitment"));
+        }
+        let bytes: [u8; 32] = hex::decode(&file.share).and_then(|b| b.try_into().ok())
+            .ok_or_else(|| key_file_error(path, "share: expected 32 hex bytes"))?;
+        let share = Option::from(Scalar::from_bytes(&bytes))
+            .ok_or_else(|| key_file_error(path, "share: non-canonical scalar"))?;
+
+        Ok(Self { node: file.node, commitment, share })
+    }
+}
+
+
+fn key_file_error(path: &Path, reason: impl ToString) -> Error {
+    Error::KeyFile { path: path.display().to_string(), reason: reason.to_string() }
+}
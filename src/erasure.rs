@@ -0,0 +1,147 @@
+use crate::{Error, Result};
+
+
+// GF(2^8) with the primitive polynomial x^8 + x^4 + x^3 + x^2 + 1
+const GF_POLY: u16 = 0x11d;
+
+const fn gf_tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= GF_POLY;
+        }
+        i += 1;
+    }
+    (exp, log)
+}
+
+const GF_EXP: [u8; 512] = gf_tables().0;
+const GF_LOG: [u8; 256] = gf_tables().1;
+
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    GF_EXP[GF_LOG[a as usize] as usize + GF_LOG[b as usize] as usize]
+}
+
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    GF_EXP[GF_LOG[a as usize] as usize + 255 - GF_LOG[b as usize] as usize]
+}
+
+
+/// Evaluation point of shard `index`
+fn point(index: usize) -> u8 {
+    (index + 1) as u8
+}
+
+
+/// Lagrange coefficients for evaluating, at `x`, the polynomial through `points`
+fn lagrange(points: &[u8], x: u8) -> Vec<u8> {
+    points.iter().enumerate().map(|(j, xj)| {
+        let mut coefficient = 1;
+        for (m, xm) in points.iter().enumerate() {
+            if m != j {
+                coefficient = gf_mul(coefficient, gf_div(x ^ xm, xj ^ xm));
+            }
+        }
+        coefficient
+    }).collect()
+}
+
+
+fn check_parameters(k: usize, n: usize) -> Result<()> {
+    if k == 0 || k > n || n > 255 {
+        return Err(Error::Erasure(format!("invalid code parameters k={}, n={}", k, n)));
+    }
+    Ok(())
+}
+
+
+/// Systematic Reed-Solomon encoding of `data` into `n` shards, any `k` of which recover it.
+///
+/// The data is prefixed with its length and padded to `k` equal data shards;
+/// shard `i` holds the evaluations at point `i+1` of one polynomial per byte position.
+pub fn encode(data: &[u8], k: usize, n: usize) -> Result<Vec<Vec<u8>>> {
+    check_parameters(k, n)?;
+
+    let mut padded = (data.len() as u64).to_be_bytes().to_vec();
+    padded.extend_from_slice(data);
+    let shard_len = padded.len().div_ceil(k);
+    padded.resize(shard_len * k, 0);
+
+    let mut shards: Vec<Vec<u8>> = padded.chunks(shard_len).map(|chunk| chunk.to_vec()).collect();
+    let data_points: Vec<u8> = (0..k).map(point).collect();
+    for index in k..n {
+        let coefficients = lagrange(&data_points, point(index));
+        let mut parity = vec![0u8; shard_len];
+        for (shard, coefficient) in shards[..k].iter().zip(&coefficients) {
+            for (p, b) in parity.iter_mut().zip(shard) {
+                *p ^= gf_mul(*coefficient, *b);
+            }
+        }
+        shards.push(parity);
+    }
+    Ok(shards)
+}
+
+
+/// Recover the data from at least `k` distinct (index, shard) pairs produced by `encode`
+pub fn decode(shards: &[(usize, Vec<u8>)], k: usize, n: usize) -> Result<Vec<u8>> {
+    check_parameters(k, n)?;
+
+    let mut chosen: Vec<&(usize, Vec<u8>)> = Vec::with_capacity(k);
+    for shard in shards {
+        if shard.0 < n && !chosen.iter().any(|c| c.0 == shard.0) {
+            chosen.push(shard);
+        }
+        if chosen.len() == k {
+            break;
+        }
+    }
+    if chosen.len() < k {
+        return Err(Error::Erasure(format!("{} distinct shards, {} needed", chosen.len(), k)));
+    }
+    let shard_len = chosen[0].1.len();
+    if chosen.iter().any(|c| c.1.len() != shard_len) {
+        return Err(Error::Erasure("shards of different lengths".to_string()));
+    }
+
+    let points: Vec<u8> = chosen.iter().map(|c| point(c.0)).collect();
+    let mut padded = Vec::with_capacity(shard_len * k);
+    for index in 0..k {
+        if let Some(c) = chosen.iter().find(|c| c.0 == index) {
+            padded.extend_from_slice(&c.1);
+            continue;
+        }
+        let coefficients = lagrange(&points, point(index));
+        let mut shard = vec![0u8; shard_len];
+        for (c, coefficient) in chosen.iter().zip(&coefficients) {
+            for (s, b) in shard.iter_mut().zip(&c.1) {
+                *s ^= gf_mul(*coefficient, *b);
+            }
+        }
+        padded.extend_from_slice(&shard);
+    }
+
+    if padded.len() < 8 {
+        return Err(Error::Erasure("shards too short".to_string()));
+    }
+    let len = u64::from_be_bytes(padded[..8].try_into().unwrap()) as usize;
+    if len > padded.len() - 8 {
+        return Err(Error::Erasure(format!("invalid length prefix: {}", len)));
+    }
+    Ok(padded[8..8 + len].to_vec())
+}
@@ -0,0 +1,126 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::{constants::CLUSTER_ID_SIZE, hex, BroadcastProtocol, Identifier};
+
+
+/// Errors returned by this crate
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Bytes received from the network could not be decoded
+    #[error("failed to decode {protocol}: {reason}")]
+    Decode { protocol: &'static str, reason: String },
+
+    #[error("unknown node: {0}")]
+    UnknownNode(u16),
+
+    #[error("bad signature from node {0}")]
+    BadSignature(u16),
+
+    #[error("payload too large: {size} bytes, limit is {limit}")]
+    PayloadTooLarge { size: usize, limit: usize },
+
+    #[error("transport error: {0}")]
+    Transport(#[from] io::Error),
+
+    #[error("instance {0:?} closed before delivery")]
+    InstanceClosed(Identifier),
+
+    #[error("no completed sharing {0:?}")]
+    NoSharing(Identifier),
+
+    #[error("erasure coding: {0}")]
+    Erasure(String),
+
+    #[error("cannot broadcast as node {0}")]
+    NotSender(u16),
+
+    #[error("wire version mismatch: got {got}, expected {expected}")]
+    VersionMismatch { got: u8, expected: u8 },
+
+    #[error("cluster mismatch: got {}, expected {}", hex::encode(got), hex::encode(expected))]
+    ClusterMismatch { got: [u8; CLUSTER_ID_SIZE], expected: [u8; CLUSTER_ID_SIZE] },
+
+    #[error("epoch mismatch: got {got}, expected {expected}")]
+    EpochMismatch { got: u64, expected: u64 },
+
+    #[error("invalid evidence: {0}")]
+    InvalidEvidence(&'static str),
+
+    #[error("invalid storage certificate: {0}")]
+    InvalidCertificate(&'static str),
+
+    #[error("invalid delivery certificate: {0}")]
+    InvalidDeliveryCertificate(&'static str),
+
+    #[error("invalid key share: {0}")]
+    InvalidKeyShare(&'static str),
+
+    #[error("key file {path}: {reason}")]
+    KeyFile { path: String, reason: String },
+
+    #[error("write-ahead log {path}: {reason}")]
+    Wal { path: String, reason: String },
+
+    #[error("delivery log {path}: {reason}")]
+    DeliveryLog { path: String, reason: String },
+
+    #[error("beacon head {path}: {reason}")]
+    BeaconHead { path: String, reason: String },
+
+    #[error("no key share installed")]
+    NoKey,
+
+    #[error("session {0} is already running")]
+    SessionInUse(u64),
+
+    #[error("epoch {0} was taken by another configuration change")]
+    EpochTaken(u64),
+
+    #[error("catch-up needs reliable broadcast, the cluster runs {0:?}")]
+    CatchUpUnsupported(BroadcastProtocol),
+
+    #[error("failed to read config {path}: {source}")]
+    ConfigRead { path: String, source: io::Error },
+
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error("invalid config: node id {0} appears more than once")]
+    DuplicateNodeId(u16),
+
+    #[error("invalid config: nodes {0} and {1} share a key")]
+    DuplicateKey(u16, u16),
+
+    #[error("invalid config: nodes {0} and {1} share an address")]
+    DuplicateAddress(u16, u16),
+
+    #[error("invalid config: address {address} of node {id} does not resolve: {reason}")]
+    UnresolvableAddress { id: u16, address: String, reason: String },
+
+    #[error("invalid config: {n} nodes cannot tolerate {t} faults, at least {} are needed", 3 * *t as u128 + 1)]
+    TooFewNodes { n: usize, t: usize },
+
+    #[error("invalid config: {n} nodes cannot tolerate {b} Byzantine and {c} crash faults, at least {} are needed", 3 * *b as u128 + 2 * *c as u128 + 1)]
+    TooFewNodesHybrid { n: usize, b: usize, c: usize },
+
+    #[error("invalid config: {0} nodes tolerate no Byzantine faults; set \"t\": 0 to run anyway")]
+    NoFaultTolerance(usize),
+
+    #[error("invalid config: two-step broadcast needs n ≥ 5t+1, {n} nodes cannot tolerate {t} faults")]
+    TwoStepTooFewNodes { n: usize, t: usize },
+
+    #[error("invalid config: fail-prone sets {0}, {1} and {2} cover every node (not Q³)")]
+    NotQ3(usize, usize, usize),
+}
+
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+
+impl Error {
+    pub fn decode(protocol: &'static str, reason: impl Into<String>) -> Self {
+        Self::Decode { protocol, reason: reason.into() }
+    }
+}
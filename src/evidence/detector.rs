@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sha2::{Digest, Sha256};
+
+use crate::{avss::AVSS_IDENTIFIER, constants::*, Identifier, Message};
+
+use super::types::Evidence;
+
+
+/// (instance, sender, (protocol, message type))
+type VoteKey = (Identifier, u16, (u8, u8));
+
+
+/// The first signed vote of a node: the digest of its payload, and the message itself
+/// if it is small enough to be shown in evidence
+struct FirstVote {
+    digest: [u8; DIGEST_SIZE],
+    message: Option<(Message, usize)>,  // with its encoded size
+}
+
+
+/// Remembers the first signed vote of every node per instance and message kind,
+/// and turns a conflicting second vote into `Evidence`.
+///
+/// Votes of finished instances are forgotten, and the oldest ones are evicted once
+/// `MAX_OBSERVED_VOTES` votes or `MAX_OBSERVED_VOTE_BYTES` of messages are kept.
+/// One piece of evidence per offender is enough to prove its fault, so further
+/// equivocations of known offenders are not recorded.
+#[derive(Default)]
+pub struct EquivocationDetector {
+    first_seen: BTreeMap<VoteKey, FirstVote>,
+    first_seen_bytes: usize,
+    evidence: HashMap<VoteKey, Evidence>,
+    offenders: HashSet<u16>,
+}
+
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(message: &Message) -> Option<VoteKey> {
+        message.payload.vote_kind().map(|kind| (message.id, message.sender, kind))
+    }
+
+    /// Record a message whose signature has been verified.
+    /// Returns new evidence if it conflicts with an earlier vote.
+    pub fn observe(&mut self, message: &Message) -> Option<Evidence> {
+        let key = Self::key(message)?;
+        let digest: [u8; DIGEST_SIZE] = Sha256::digest(message.payload.to_bytes()).into();
+        let size = message.to_bytes().len();
+        let provable = size <= MAX_EVIDENCE_VOTE_SIZE;
+
+        let Some(first) = self.first_seen.get(&key) else {
+            let message = provable.then(|| (message.clone(), size));
+            self.first_seen_bytes += message.as_ref().map_or(0, |(_, size)| *size);
+            self.first_seen.insert(key, FirstVote { digest, message });
+            self.evict();
+            return None;
+        };
+        if first.digest == digest || self.offenders.contains(&message.sender) {
+            return None;
+        }
+        let (Some((first, _)), true) = (&first.message, provable) else {
+            eprintln!("Node {} equivocated in {:?} with votes too large to prove it", message.sender, message.id);
+            return None;
+        };
+        let evidence = Evidence::new(first.clone(), message.clone());
+        self.offenders.insert(message.sender);
+        self.evidence.insert(key, evidence.clone());
+        Some(evidence)
+    }
+
+    /// Forget the votes of instance `id`, e.g. once it has finished. Broadcasts and
+    /// sharings number their instances apart, so `avss` tells which one is meant.
+    pub fn forget(&mut self, id: Identifier, avss: bool) {
+        let keys: Vec<VoteKey> = self.first_seen.range((id, 0, (0, 0))..=(id, u16::MAX, (u8::MAX, u8::MAX)))
+            .map(|(key, _)| *key)
+            .filter(|(_, _, (protocol, _))| (*protocol == AVSS_IDENTIFIER) == avss)
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &VoteKey) {
+        if let Some(FirstVote { message: Some((_, size)), .. }) = self.first_seen.remove(key) {
+            self.first_seen_bytes -= size;
+        }
+    }
+
+    /// Drop the votes of the lowest instances until the limits hold again
+    fn evict(&mut self) {
+        while self.first_seen.len() > MAX_OBSERVED_VOTES || self.first_seen_bytes > MAX_OBSERVED_VOTE_BYTES {
+            let Some(key) = self.first_seen.keys().next().copied() else { break };
+            self.remove(&key);
+        }
+    }
+
+    /// Record evidence learnt from a peer. It must already be verified.
+    /// Returns false if the offender is already known.
+    pub fn record(&mut self, evidence: Evidence) -> bool {
+        let Some(key) = Self::key(&evidence.first) else { return false };
+        if !self.offenders.insert(evidence.offender()) {
+            return false;
+        }
+        self.evidence.insert(key, evidence);
+        true
+    }
+
+    /// All evidence collected so far
+    pub fn evidence(&self) -> Vec<Evidence> {
+        self.evidence.values().cloned().collect()
+    }
+
+    /// Nodes that have been caught equivocating
+    pub fn offenders(&self) -> Vec<u16> {
+        let mut offenders: Vec<u16> = self.offenders.iter().copied().collect();
+        offenders.sort_unstable();
+        offenders
+    }
+}
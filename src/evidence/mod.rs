@@ -0,0 +1,6 @@
+pub mod types;
+pub mod detector;
+
+// re-export all public items from evidence module
+pub use types::*;
+pub use detector::*;
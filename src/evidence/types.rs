@@ -0,0 +1,100 @@
+use crate::{constants::HEADER_SIZE, Config, Error, Identifier, Message, Result};
+
+
+// Protocol Identifier
+pub const EVIDENCE_IDENTIFIER: u8 = 1;
+const PROTOCOL: &str = "evidence";
+
+
+/// Proof that a node signed two conflicting votes for the same instance.
+///
+/// Anyone holding the cluster `Config` can check it: both messages carry valid
+/// signatures of the same sender, for the same `Identifier` and message kind,
+/// but with different contents.
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    pub first: Message,
+    pub second: Message,
+}
+
+
+impl Evidence {
+    pub fn new(first: Message, second: Message) -> Self {
+        Self { first, second }
+    }
+
+    /// The node that equivocated
+    pub fn offender(&self) -> u16 {
+        self.first.sender
+    }
+
+    pub fn id(&self) -> Identifier {
+        self.first.id
+    }
+
+    pub fn verify(&self, config: &Config) -> Result<()> {
+        let invalid = |reason| Err(Error::InvalidEvidence(reason));
+
+        if self.first.id != self.second.id {
+            return invalid("identifiers differ");
+        }
+        if self.first.sender != self.second.sender {
+            return invalid("senders differ");
+        }
+        if self.first.domain != self.second.domain {
+            return invalid("envelopes differ");
+        }
+        if self.first.domain.cluster_id != config.domain().cluster_id {
+            return invalid("signed for another cluster");
+        }
+        let kind = self.first.payload.vote_kind();
+        if kind.is_none() || kind != self.second.payload.vote_kind() {
+            return invalid("not two votes of the same kind");
+        }
+        if self.first.payload.to_bytes() == self.second.payload.to_bytes() {
+            return invalid("votes do not conflict");
+        }
+
+        let sender_config = config.get_node(self.first.sender)
+            .ok_or(Error::UnknownNode(self.first.sender))?;
+        let pubkey = ed25519_dalek::SigningKey::from_bytes(&sender_config.privkey).verifying_key();
+        if !self.first.verify(pubkey) || !self.second.verify(pubkey) {
+            return Err(Error::BadSignature(self.first.sender));
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let first = self.first.to_bytes();
+        let second = self.second.to_bytes();
+        let mut result = Vec::with_capacity(1 + 4 + first.len() + second.len());
+        result.push(EVIDENCE_IDENTIFIER);
+        result.extend_from_slice(&(first.len() as u32).to_be_bytes());
+        result.extend_from_slice(&first);
+        result.extend_from_slice(&second);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 5 {
+            return Err(Error::decode(PROTOCOL, "length < 5"));
+        }
+
+        if bytes[0] != EVIDENCE_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not an Evidence message"));
+        }
+
+        let first_len = u32::from_be_bytes(bytes[1..5].try_into().unwrap()) as usize;
+        if bytes.len() < 5 + first_len {
+            return Err(Error::decode(PROTOCOL, format!("first message truncated ({} bytes)", first_len)));
+        }
+        let (first, second) = (&bytes[5..5 + first_len], &bytes[5 + first_len..]);
+        // Evidence is never a vote, so nested evidence is useless and only costs stack depth
+        if [first, second].iter().any(|m| m.get(HEADER_SIZE) == Some(&EVIDENCE_IDENTIFIER)) {
+            return Err(Error::decode(PROTOCOL, "nested evidence"));
+        }
+        let first = Message::from_bytes(first)?;
+        let second = Message::from_bytes(second)?;
+        Ok(Self { first, second })
+    }
+}
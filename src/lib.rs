@@ -1,18 +1,73 @@
-use std::{io, sync::Arc};
+use std::{collections::{HashMap, HashSet}, net::{SocketAddr, ToSocketAddrs}, sync::Arc};
 use ed25519::signature::SignerMut;
-use tokio::net::UdpSocket;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 
 pub mod reliable_broadcast;
+pub mod crash_broadcast;
+pub mod two_step_broadcast;
+pub mod merkle_broadcast;
+pub mod avid;
+pub mod avss;
+pub mod agreement;
+pub mod dkg;
+pub mod reshare;
+pub mod threshold;
+pub mod beacon;
+pub mod reconfig;
+pub mod wal;
+pub mod catch_up;
+pub mod delivery_log;
 pub mod constants;
+pub mod transport;
+pub mod evidence;
+pub mod limits;
+pub mod node;
+pub mod error;
+pub mod quorum;
+pub mod merkle;
+pub mod erasure;
+pub mod hex;
+pub mod polynomial;
 
 use constants::*;
+pub use error::{Error, Result};
+use transport::ReliableLink;
 
 
 #[derive(Deserialize, Clone)]
 pub struct Config {
     pub my_id: u16,
     pub nodes: Vec<NodeConfig>,
+    #[serde(default)]
+    pub authentication: Authentication,
+    #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default)]
+    pub limits: limits::Limits,
+    /// Deployment epoch, bound into every signature
+    #[serde(default)]
+    pub epoch: u64,
+    /// Number of Byzantine faults to tolerate. Defaults to (n-1)/3.
+    #[serde(default)]
+    pub t: Option<usize>,
+    /// Sets of nodes that may fail together (e.g. all nodes of one hosting provider).
+    /// If given, quorums are derived from these sets instead of `t` or weights.
+    #[serde(default)]
+    pub fail_prone_sets: Vec<Vec<u16>>,
+    /// Hybrid fault model with separate Byzantine and crash fault counts.
+    /// If given, replaces `t`.
+    #[serde(default)]
+    pub hybrid: Option<quorum::HybridFaults>,
+    /// Run a randomness beacon with a dealt threshold key
+    #[serde(default)]
+    pub beacon: Option<beacon::BeaconConfig>,
+    /// Log votes and deliveries to disk, so that the node can restart without equivocating
+    #[serde(default)]
+    pub wal: Option<wal::WalConfig>,
+    /// Record every delivery in a hash-chained log at this path
+    #[serde(default)]
+    pub delivery_log: Option<String>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -20,16 +75,201 @@ pub struct NodeConfig {
     pub id: u16,
     pub address: String,
     pub privkey: [u8; 32],
+    /// Voting power of the node
+    #[serde(default = "default_weight")]
+    pub weight: u64,
+}
+
+
+fn default_weight() -> u64 {
+    1
+}
+
+
+/// How messages are authenticated between nodes
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Authentication {
+    /// Every message carries an ed25519 signature
+    #[default]
+    Signatures,
+    /// Links are authenticated with pairwise session keys (X25519 + HMAC-SHA256).
+    /// Only messages that must be shown to third parties are still signed.
+    SessionMac,
+    /// Nothing but the source address of a datagram. Only for trusted networks,
+    /// and only with `Protocol::CrashBroadcast`.
+    #[serde(rename = "none")]
+    Unauthenticated,
+}
+
+
+/// Broadcast protocol run by the cluster
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// Two-step broadcast if the cluster allows it, otherwise reliable broadcast
+    #[default]
+    Auto,
+    /// Bracha's Byzantine reliable broadcast (Send/Echo/Ready)
+    ReliableBroadcast,
+    /// Relay-on-first-receipt, tolerates crash faults only
+    CrashBroadcast,
+    /// Imbs and Raynal's two-step Byzantine reliable broadcast (Init/Witness), needs n ≥ 5t+1
+    TwoStepBroadcast,
+    /// Reliable broadcast with erasure-coded fragments, Merkle proofs and root-only readies
+    MerkleBroadcast,
+}
+
+
+/// The broadcast protocol a cluster actually runs, with `Protocol::Auto` resolved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BroadcastProtocol {
+    ReliableBroadcast,
+    CrashBroadcast,
+    TwoStepBroadcast,
+    MerkleBroadcast,
 }
 
 
 impl Config {
-    pub async fn load(filename: &str) -> io::Result<Config> {
-        let config_data = tokio::fs::read_to_string(filename).await?;
-        let config: Config = serde_json::from_str(&config_data)?;
+    pub async fn load(filename: &str) -> Result<Config> {
+        let config_data = tokio::fs::read_to_string(filename).await
+            .map_err(|source| Error::ConfigRead { path: filename.to_string(), source })?;
+        let config: Config = serde_json::from_str(&config_data)
+            .map_err(|e| Error::Config(e.to_string()))?;
+        config.validate()?;
         Ok(config)
     }
 
+    /// 設定の整合性を検証
+    ///
+    /// Node ids, keys and addresses must be unique, every address must resolve,
+    /// `my_id` must be one of the nodes, and n ≥ 3t+1 must hold (weighted clusters
+    /// instead need a positive total weight, adversary structures must be Q³,
+    /// and hybrid fault mixes need n ≥ 3b+2c+1).
+    pub fn validate(&self) -> Result<()> {
+        let n = self.nodes.len();
+        let mut ids: HashSet<u16> = HashSet::new();
+        let mut keys: HashMap<[u8; 32], u16> = HashMap::new();
+        let mut addresses: HashMap<SocketAddr, u16> = HashMap::new();
+
+        for node in &self.nodes {
+            if !ids.insert(node.id) {
+                return Err(Error::DuplicateNodeId(node.id));
+            }
+            let pubkey = ed25519_dalek::SigningKey::from_bytes(&node.privkey).verifying_key().to_bytes();
+            if let Some(other) = keys.insert(pubkey, node.id) {
+                return Err(Error::DuplicateKey(other, node.id));
+            }
+            let address = node.address.to_socket_addrs()
+                .map_err(|e| Error::UnresolvableAddress { id: node.id, address: node.address.clone(), reason: e.to_string() })?
+                .next()
+                .ok_or_else(|| Error::UnresolvableAddress { id: node.id, address: node.address.clone(), reason: "no addresses".to_string() })?;
+            if let Some(other) = addresses.insert(address, node.id) {
+                return Err(Error::DuplicateAddress(other, node.id));
+            }
+        }
+
+        if self.get_my_node().is_none() {
+            return Err(Error::UnknownNode(self.my_id));
+        }
+
+        if self.protocol == Protocol::TwoStepBroadcast && !self.supports_two_step() {
+            return match self.quorums() {
+                quorum::QuorumSystem::Threshold { n, t } => Err(Error::TwoStepTooFewNodes { n, t }),
+                _ => Err(Error::Config("two-step broadcast needs a plain threshold \"t\"".to_string())),
+            };
+        }
+        if self.protocol == Protocol::MerkleBroadcast {
+            if !matches!(self.quorums(), quorum::QuorumSystem::Threshold { .. }) {
+                return Err(Error::Config("merkle broadcast needs a plain threshold \"t\"".to_string()));
+            }
+            if n > 255 {
+                return Err(Error::Config(format!("merkle broadcast supports at most 255 nodes, got {}", n)));
+            }
+        }
+        // Only the votes of reliable broadcast are logged, those of the others could be taken back after a restart
+        if self.wal.is_some() && self.broadcast_protocol() != BroadcastProtocol::ReliableBroadcast {
+            return Err(Error::Config(format!("\"wal\" requires reliable broadcast, got {:?}", self.broadcast_protocol())));
+        }
+        if self.authentication == Authentication::Unauthenticated && self.protocol != Protocol::CrashBroadcast {
+            return Err(Error::Config("\"authentication\": \"none\" requires \"protocol\": \"crash_broadcast\"".to_string()));
+        }
+
+        if let Some(faults) = self.hybrid {
+            if self.t.is_some() || !self.fail_prone_sets.is_empty() || self.nodes.iter().any(|node| node.weight != 1) {
+                return Err(Error::Config("\"hybrid\" cannot be combined with \"t\", \"fail_prone_sets\" or node weights".to_string()));
+            }
+            if (n as u128) < 3 * faults.byzantine as u128 + 2 * faults.crash as u128 + 1 {
+                return Err(Error::TooFewNodesHybrid { n, b: faults.byzantine, c: faults.crash });
+            }
+            return Ok(());
+        }
+
+        if !self.fail_prone_sets.is_empty() {
+            if self.t.is_some() || self.nodes.iter().any(|node| node.weight != 1) {
+                return Err(Error::Config("\"fail_prone_sets\" cannot be combined with \"t\" or node weights".to_string()));
+            }
+            if let Some(unknown) = self.fail_prone_sets.iter().flatten().find(|id| !ids.contains(id)) {
+                return Err(Error::UnknownNode(*unknown));
+            }
+            let fail_prone: Vec<HashSet<u16>> = self.fail_prone_sets.iter()
+                .map(|set| set.iter().copied().collect())
+                .collect();
+            if let Some((i, j, k)) = quorum::q3_violation(&ids, &fail_prone) {
+                return Err(Error::NotQ3(i, j, k));
+            }
+            return Ok(());
+        }
+
+        if self.nodes.iter().any(|node| node.weight != 1) {
+            if self.t.is_some() {
+                return Err(Error::Config("\"t\" cannot be combined with node weights".to_string()));
+            }
+            if self.nodes.iter().all(|node| node.weight == 0) {
+                return Err(Error::Config("total node weight is 0".to_string()));
+            }
+            return Ok(());
+        }
+
+        let t = self.fault_tolerance();
+        if (n as u128) < 3 * t as u128 + 1 {
+            return Err(Error::TooFewNodes { n, t });
+        }
+        if t == 0 && self.t.is_none() {
+            return Err(Error::NoFaultTolerance(n));
+        }
+        Ok(())
+    }
+
+    /// 実際に使うブロードキャストプロトコルを取得 (`Auto` を解決)
+    pub fn broadcast_protocol(&self) -> BroadcastProtocol {
+        match self.protocol {
+            Protocol::Auto if self.supports_two_step() => BroadcastProtocol::TwoStepBroadcast,
+            Protocol::Auto | Protocol::ReliableBroadcast => BroadcastProtocol::ReliableBroadcast,
+            Protocol::CrashBroadcast => BroadcastProtocol::CrashBroadcast,
+            Protocol::TwoStepBroadcast => BroadcastProtocol::TwoStepBroadcast,
+            Protocol::MerkleBroadcast => BroadcastProtocol::MerkleBroadcast,
+        }
+    }
+
+    /// Two-step broadcast needs equal-weight nodes and n ≥ 5t+1
+    fn supports_two_step(&self) -> bool {
+        matches!(self.quorums(), quorum::QuorumSystem::Threshold { n, t } if n as u128 > 5 * t as u128)
+    }
+
+    /// 許容するビザンチン故障数 t
+    pub fn fault_tolerance(&self) -> usize {
+        self.t.unwrap_or(self.nodes.len().saturating_sub(1) / 3)
+    }
+
+    /// ID順に並べた全ノードIDを取得 (erasure-coded fragments are assigned in this order)
+    pub fn node_ids(&self) -> Vec<u16> {
+        let mut ids: Vec<u16> = self.nodes.iter().map(|n| n.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
     /// 指定されたIDのノード設定を取得
     pub fn get_node(&self, id: u16) -> Option<&NodeConfig> {
         self.nodes.iter().find(|n| n.id == id)
@@ -44,10 +284,89 @@ impl Config {
     pub fn get_all_addresses(&self) -> Vec<String> {
         self.nodes.iter().map(|n| n.address.clone()).collect()
     }
+
+    /// クラスタIDを計算 (ノードのID・公開鍵・アドレスのハッシュ)
+    pub fn cluster_id(&self) -> [u8; CLUSTER_ID_SIZE] {
+        let mut nodes: Vec<&NodeConfig> = self.nodes.iter().collect();
+        nodes.sort_by_key(|n| n.id);
+
+        let mut hasher = Sha256::new();
+        hasher.update(CLUSTER_ID_CONTEXT);
+        for node in nodes {
+            let pubkey = ed25519_dalek::SigningKey::from_bytes(&node.privkey).verifying_key();
+            hasher.update(node.id.to_be_bytes());
+            hasher.update(pubkey.as_bytes());
+            hasher.update(node.weight.to_be_bytes());
+            hasher.update((node.address.len() as u32).to_be_bytes());
+            hasher.update(node.address.as_bytes());
+        }
+        hasher.finalize()[..CLUSTER_ID_SIZE].try_into().unwrap()
+    }
+
+    /// 署名ドメインを取得 (呼び出しごとに現在のフィールドから計算)
+    pub fn domain(&self) -> Domain {
+        Domain {
+            version: WIRE_VERSION,
+            cluster_id: self.cluster_id(),
+            epoch: self.epoch,
+        }
+    }
+
+    /// クォーラムシステムを取得 (呼び出しごとに現在のフィールドから計算)
+    pub fn quorums(&self) -> quorum::QuorumSystem {
+        quorum::QuorumSystem::from_config(self)
+    }
+}
+
+
+const CLUSTER_ID_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/cluster";
+const SIGNING_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/message";
+
+
+/// Envelope binding a message to a wire version, a cluster and an epoch.
+/// It is covered by the signature, so messages cannot be replayed across clusters or epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Domain {
+    pub version: u8,
+    pub cluster_id: [u8; CLUSTER_ID_SIZE],
+    pub epoch: u64,
+}
+
+
+impl Domain {
+    fn to_bytes(self) -> [u8; ENVELOPE_SIZE] {
+        let mut result: [u8; ENVELOPE_SIZE] = [0; ENVELOPE_SIZE];
+        result[0] = self.version;
+        result[1..1 + CLUSTER_ID_SIZE].copy_from_slice(&self.cluster_id);
+        result[1 + CLUSTER_ID_SIZE..ENVELOPE_SIZE].copy_from_slice(&self.epoch.to_be_bytes());
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8; ENVELOPE_SIZE]) -> Self {
+        Self {
+            version: bytes[0],
+            cluster_id: bytes[1..1 + CLUSTER_ID_SIZE].try_into().unwrap(),
+            epoch: u64::from_be_bytes(bytes[1 + CLUSTER_ID_SIZE..ENVELOPE_SIZE].try_into().unwrap()),
+        }
+    }
+
+    /// Check a received envelope against the local one
+    pub fn check(&self, expected: &Domain) -> Result<()> {
+        if self.version != expected.version {
+            return Err(Error::VersionMismatch { got: self.version, expected: expected.version });
+        }
+        if self.cluster_id != expected.cluster_id {
+            return Err(Error::ClusterMismatch { got: self.cluster_id, expected: expected.cluster_id });
+        }
+        if self.epoch != expected.epoch {
+            return Err(Error::EpochMismatch { got: self.epoch, expected: expected.epoch });
+        }
+        Ok(())
+    }
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, PartialOrd, Ord)]
 pub struct Identifier {
     sender: u16,  // j
     sequence: u64  // s
@@ -59,7 +378,17 @@ impl Identifier {
         Self { sender, sequence }
     }
 
-    fn to_bytes(&self) -> [u8; IDENTIFIER_SIZE] {
+    /// The broadcaster j
+    pub fn sender(&self) -> u16 {
+        self.sender
+    }
+
+    /// The broadcaster's sequence number s
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn to_bytes(self) -> [u8; IDENTIFIER_SIZE] {
         let mut result: [u8; IDENTIFIER_SIZE] = [0; IDENTIFIER_SIZE];
         result[0..2].copy_from_slice(&self.sender.to_be_bytes());
         result[2..IDENTIFIER_SIZE].copy_from_slice(&self.sequence.to_be_bytes());
@@ -93,34 +422,145 @@ impl Instance {
 #[derive(Debug, Clone)]
 pub enum MessageType {
     ReliableBroadcast(reliable_broadcast::types::ReliableBroadcastMessage),
+    Evidence(Box<evidence::Evidence>),
+    CrashBroadcast(crash_broadcast::types::CrashBroadcastMessage),
+    TwoStepBroadcast(two_step_broadcast::types::TwoStepBroadcastMessage),
+    MerkleBroadcast(merkle_broadcast::types::MerkleBroadcastMessage),
+    Avid(avid::types::AvidMessage),
+    Avss(avss::types::AvssMessage),
+    Agreement(agreement::types::AgreementMessage),
+    Reshare(reshare::types::ReshareMessage),
+    Threshold(Box<threshold::types::ThresholdMessage>),
+    CatchUp(catch_up::types::CatchUpMessage),
 }
 
 impl MessageType {
+    /// Whether this message must carry a signature, i.e. a transferable proof
+    /// of its origin, even if the link it arrives on is authenticated
+    pub fn requires_signature(&self) -> bool {
+        match self {
+            MessageType::ReliableBroadcast(msg) => msg.requires_signature(),
+            MessageType::Evidence(_) => false,  // Carries its own signatures
+            MessageType::CrashBroadcast(_) => false,
+            MessageType::TwoStepBroadcast(_) => false,
+            MessageType::MerkleBroadcast(_) => false,
+            MessageType::Avid(msg) => msg.requires_signature(),
+            MessageType::Avss(_) => false,
+            MessageType::Agreement(_) => false,
+            MessageType::Reshare(_) => false,
+            MessageType::Threshold(_) => false,  // Shares are checked against the key
+            MessageType::CatchUp(_) => false,  // Certificates carry their own signatures
+        }
+    }
+
+    /// The broadcast protocol this message belongs to, if any
+    pub fn protocol(&self) -> Option<BroadcastProtocol> {
+        match self {
+            MessageType::ReliableBroadcast(_) => Some(BroadcastProtocol::ReliableBroadcast),
+            MessageType::Evidence(_) => None,
+            MessageType::CrashBroadcast(_) => Some(BroadcastProtocol::CrashBroadcast),
+            MessageType::TwoStepBroadcast(_) => Some(BroadcastProtocol::TwoStepBroadcast),
+            MessageType::MerkleBroadcast(_) => Some(BroadcastProtocol::MerkleBroadcast),
+            MessageType::Avid(_) => None,  // Dispersal runs next to any broadcast protocol
+            MessageType::Avss(_) => None,  // So does secret sharing
+            MessageType::Agreement(_) => None,  // And agreement sessions
+            MessageType::Reshare(_) => None,
+            MessageType::Threshold(_) => None,
+            MessageType::CatchUp(_) => None,
+        }
+    }
+
+    /// (protocol, message type) for messages a node may send at most once per instance.
+    /// Two different signed messages of the same vote kind are an equivocation.
+    pub fn vote_kind(&self) -> Option<(u8, u8)> {
+        match self {
+            MessageType::ReliableBroadcast(msg) => msg.vote_kind().map(|kind| (reliable_broadcast::RBC_IDENTIFIER, kind)),
+            MessageType::Evidence(_) => None,
+            MessageType::CrashBroadcast(_) => None,  // Relays of one payload are all alike
+            MessageType::TwoStepBroadcast(msg) => msg.vote_kind().map(|kind| (two_step_broadcast::TWO_STEP_IDENTIFIER, kind)),
+            MessageType::MerkleBroadcast(msg) => msg.vote_kind().map(|kind| (merkle_broadcast::MERKLE_IDENTIFIER, kind)),
+            MessageType::Avid(msg) => msg.vote_kind().map(|kind| (avid::AVID_IDENTIFIER, kind)),
+            MessageType::Avss(_) => None,  // Every node gets its own points
+            MessageType::Agreement(_) => None,  // The votes of all slots share the session id
+            MessageType::Reshare(_) => None,  // Same for the dealings and agreement inside
+            MessageType::Threshold(_) => None,  // Shares are deterministic, a second one is merely invalid
+            MessageType::CatchUp(_) => None,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             MessageType::ReliableBroadcast(msg) => msg.to_bytes(),
+            MessageType::Evidence(evidence) => evidence.to_bytes(),
+            MessageType::CrashBroadcast(msg) => msg.to_bytes(),
+            MessageType::TwoStepBroadcast(msg) => msg.to_bytes(),
+            MessageType::MerkleBroadcast(msg) => msg.to_bytes(),
+            MessageType::Avid(msg) => msg.to_bytes(),
+            MessageType::Avss(msg) => msg.to_bytes(),
+            MessageType::Agreement(msg) => msg.to_bytes(),
+            MessageType::Reshare(msg) => msg.to_bytes(),
+            MessageType::Threshold(msg) => msg.to_bytes(),
+            MessageType::CatchUp(msg) => msg.to_bytes(),
         }
     }
     
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, io::Error> {
-        if bytes.len() < 1 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid message: length < 1"))
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() {
+            return Err(Error::decode("message", "empty payload"))
         }
         match bytes[0] {
             reliable_broadcast::RBC_IDENTIFIER => Ok(MessageType::ReliableBroadcast(
                 reliable_broadcast::types::ReliableBroadcastMessage::from_bytes(bytes)?
             )),
-            _ => Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                format!("Unknown protocol ID: {}", bytes[0])
+            evidence::EVIDENCE_IDENTIFIER => Ok(MessageType::Evidence(Box::new(
+                evidence::Evidence::from_bytes(bytes)?
+            ))),
+            crash_broadcast::CRASH_IDENTIFIER => Ok(MessageType::CrashBroadcast(
+                crash_broadcast::types::CrashBroadcastMessage::from_bytes(bytes)?
+            )),
+            two_step_broadcast::TWO_STEP_IDENTIFIER => Ok(MessageType::TwoStepBroadcast(
+                two_step_broadcast::types::TwoStepBroadcastMessage::from_bytes(bytes)?
+            )),
+            merkle_broadcast::MERKLE_IDENTIFIER => Ok(MessageType::MerkleBroadcast(
+                merkle_broadcast::types::MerkleBroadcastMessage::from_bytes(bytes)?
+            )),
+            avid::AVID_IDENTIFIER => Ok(MessageType::Avid(
+                avid::types::AvidMessage::from_bytes(bytes)?
+            )),
+            avss::AVSS_IDENTIFIER => Ok(MessageType::Avss(
+                avss::types::AvssMessage::from_bytes(bytes)?
             )),
+            agreement::AGREEMENT_IDENTIFIER => Ok(MessageType::Agreement(
+                agreement::types::AgreementMessage::from_bytes(bytes)?
+            )),
+            reshare::RESHARE_IDENTIFIER => Ok(MessageType::Reshare(
+                reshare::types::ReshareMessage::from_bytes(bytes)?
+            )),
+            threshold::THRESHOLD_IDENTIFIER => Ok(MessageType::Threshold(Box::new(
+                threshold::types::ThresholdMessage::from_bytes(bytes)?
+            ))),
+            catch_up::CATCH_UP_IDENTIFIER => Ok(MessageType::CatchUp(
+                catch_up::types::CatchUpMessage::from_bytes(bytes)?
+            )),
+            _ => Err(Error::decode("message", format!("unknown protocol ID: {}", bytes[0]))),
         }
     }
 }
 
 
+/// How the origin of a received message was established
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Authenticity {
+    /// Valid signature: transferable, can be shown to third parties
+    Signed,
+    /// Vouched for by the authenticated link only
+    Channel,
+}
+
+
 #[derive(Debug, Clone)]
 pub struct Message {
+    pub domain: Domain,
     pub id: Identifier,
     pub sender: u16,
     pub payload: MessageType,
@@ -128,57 +568,102 @@ pub struct Message {
 }
 
 impl Message {
-    pub fn new(id: Identifier, sender: u16, payload: MessageType, privkey: &[u8; 32]) -> Self {
+    pub fn new(domain: Domain, id: Identifier, sender: u16, payload: MessageType, privkey: &[u8; 32]) -> Self {
         let mut signing_key = ed25519_dalek::SigningKey::from_bytes(privkey);
-        let mut message =  Self { id, sender, payload, signature: [0; 64] };
-        let sig = signing_key.sign(&message.to_header_and_payload_bytes());
+        let mut message =  Self { domain, id, sender, payload, signature: [0; 64] };
+        let sig = signing_key.sign(&message.signing_bytes());
         message.signature = sig.to_bytes();
-        return message;
+        message
+    }
+    
+    /// Create a message in `config`'s domain, authenticated as configured: signed, unless
+    /// session MACs are in use and the payload needs no transferable proof, or authentication is off.
+    pub fn new_authenticated(id: Identifier, sender: u16, payload: MessageType, privkey: &[u8; 32], config: &Config) -> Self {
+        let unsigned = match config.authentication {
+            Authentication::Signatures => false,
+            Authentication::SessionMac => !payload.requires_signature(),
+            Authentication::Unauthenticated => true,
+        };
+        if unsigned {
+            Self { domain: config.domain(), id, sender, payload, signature: [0; 64] }
+        } else {
+            Self::new(config.domain(), id, sender, payload, privkey)
+        }
     }
     
     fn to_header_and_payload_bytes(&self) -> Vec<u8> {
         let mut result = Vec::new();
+        result.extend_from_slice(&self.domain.to_bytes());
         result.extend_from_slice(&self.id.to_bytes());
         result.extend_from_slice(&self.sender.to_be_bytes());
         result.extend_from_slice(&self.payload.to_bytes());
         result
     }
 
+    /// The signature covers a context string, the envelope, the header and the payload
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut result = SIGNING_CONTEXT.to_vec();
+        result.extend_from_slice(&self.to_header_and_payload_bytes());
+        result
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut result = self.to_header_and_payload_bytes();
         result.extend_from_slice(&self.signature);
         result
     }
     
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, io::Error> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         if bytes.len() < MIN_MESSAGE_SIZE {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                format!("Message too short: {} bytes, expected at least {}", bytes.len(), MIN_MESSAGE_SIZE)
+            return Err(Error::decode(
+                "message",
+                format!("too short: {} bytes, expected at least {}", bytes.len(), MIN_MESSAGE_SIZE)
             ));
         }
 
-        let id = Identifier::from_bytes(bytes[0..IDENTIFIER_SIZE].try_into().unwrap());
-        let sender: u16 = u16::from_be_bytes(bytes[IDENTIFIER_SIZE..IDENTIFIER_SIZE+2].try_into().unwrap());
+        if bytes[0] != WIRE_VERSION {
+            return Err(Error::VersionMismatch { got: bytes[0], expected: WIRE_VERSION });
+        }
+
+        let domain = Domain::from_bytes(bytes[0..ENVELOPE_SIZE].try_into().unwrap());
+        let id = Identifier::from_bytes(bytes[ENVELOPE_SIZE..ENVELOPE_SIZE+IDENTIFIER_SIZE].try_into().unwrap());
+        let sender: u16 = u16::from_be_bytes(bytes[ENVELOPE_SIZE+IDENTIFIER_SIZE..HEADER_SIZE].try_into().unwrap());
         let payload = MessageType::from_bytes(&bytes[HEADER_SIZE..bytes.len()-SIGNATURE_SIZE])?;
         let signature: [u8; SIGNATURE_SIZE] = bytes[bytes.len()-SIGNATURE_SIZE..].try_into().unwrap();
         
-        Ok(Self {id, sender, payload, signature})
+        Ok(Self {domain, id, sender, payload, signature})
     }
 
     pub fn verify(&self, pubkey: ed25519_dalek::VerifyingKey) -> bool {
-        let data = self.to_header_and_payload_bytes();
+        let data = self.signing_bytes();
         let signature = ed25519::Signature::from_bytes(&self.signature);
         pubkey.verify_strict(&data, &signature).is_ok()
     }
+
+    /// Authenticate a message that arrived from node `from` over `link`.
+    /// The signature check is skipped when the link itself vouches for the sender.
+    pub fn authenticate(&self, from: u16, link: &ReliableLink, config: &Config) -> Result<Authenticity> {
+        if link.is_authenticated() && from == self.sender && !self.payload.requires_signature() {
+            return Ok(Authenticity::Channel);
+        }
+        // The link has already matched the source address against the configured one
+        if config.authentication == Authentication::Unauthenticated && from == self.sender {
+            return Ok(Authenticity::Channel);
+        }
+        let sender_config = config.get_node(self.sender).ok_or(Error::UnknownNode(self.sender))?;
+        let sender_verify_key = ed25519_dalek::SigningKey::from_bytes(&sender_config.privkey).verifying_key();
+        if !self.verify(sender_verify_key) {
+            return Err(Error::BadSignature(self.sender));
+        }
+        Ok(Authenticity::Signed)
+    }
 }
 
 
-pub async fn broadcast(destinations: &[String], message: &Message, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
+pub async fn broadcast(destinations: &[u16], message: &Message, link: Arc<ReliableLink>) -> Result<()> {
     let message_bytes = message.to_bytes();
     for dest in destinations {
-        socket.send_to(&message_bytes, dest).await
-            .map_err(|e| io::Error::new(io::ErrorKind::NetworkDown, format!("Failed to send to {}: {}", dest, e)))?;
+        link.send(*dest, &message_bytes).await?;
     }
     Ok(())
 }
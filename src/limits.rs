@@ -0,0 +1,238 @@
+use std::{collections::{BTreeSet, HashMap}, time::{Duration, Instant}};
+
+use serde::Deserialize;
+
+use crate::Identifier;
+
+
+/// Per-peer limits on abusive traffic
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Limits {
+    /// Invalid messages (undecodable, bad signature, bad evidence) tolerated per window
+    pub max_invalid_messages: u32,
+    /// Instances a peer may cause this node to create per window
+    pub max_new_instances: u32,
+    /// Length of the accounting window in milliseconds
+    pub window_ms: u64,
+    /// How long a peer that exceeds a limit is ignored, in milliseconds
+    pub ban_duration_ms: u64,
+    /// Instances of one broadcaster that one peer may cause to be open at the same time
+    pub max_open_instances_per_sender: usize,
+    /// How far ahead of the lowest unfinished sequence number of a broadcaster
+    /// new instances are accepted
+    pub sequence_window: u64,
+    /// Bytes of AVID fragments this node stores for one client
+    pub max_fragment_bytes_per_client: usize,
+    /// Catch-up requests answered per peer and window, each with up to a full message of certificates
+    pub max_catch_up_requests: u32,
+}
+
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_invalid_messages: 100,
+            max_new_instances: 10_000,
+            window_ms: 10_000,
+            ban_duration_ms: 60_000,
+            max_open_instances_per_sender: 256,
+            sequence_window: 1024,
+            max_fragment_bytes_per_client: 64 * 1024 * 1024,
+            max_catch_up_requests: 64,
+        }
+    }
+}
+
+
+struct PeerStats {
+    window_start: Instant,
+    invalid: u32,
+    created: u32,
+    catch_ups: u32,
+    banned_until: Option<Instant>,
+}
+
+
+/// Counts invalid messages and instance creations per peer, and temporarily bans
+/// peers that exceed the configured limits.
+pub struct PeerGuard {
+    limits: Limits,
+    peers: HashMap<u16, PeerStats>,
+}
+
+
+impl PeerGuard {
+    pub fn new(limits: Limits) -> Self {
+        Self { limits, peers: HashMap::new() }
+    }
+
+    fn stats(&mut self, peer: u16, now: Instant) -> &mut PeerStats {
+        let window = Duration::from_millis(self.limits.window_ms);
+        let stats = self.peers.entry(peer).or_insert(PeerStats {
+            window_start: now,
+            invalid: 0,
+            created: 0,
+            catch_ups: 0,
+            banned_until: None,
+        });
+        if now.duration_since(stats.window_start) >= window {
+            stats.window_start = now;
+            stats.invalid = 0;
+            stats.created = 0;
+            stats.catch_ups = 0;
+        }
+        if stats.banned_until.is_some_and(|until| until <= now) {
+            stats.banned_until = None;
+        }
+        stats
+    }
+
+    fn ban(stats: &mut PeerStats, peer: u16, now: Instant, duration: Duration, reason: &str) {
+        if stats.banned_until.is_none() {
+            eprintln!("Banning node {} for {:?}: {}", peer, duration, reason);
+        }
+        stats.banned_until = Some(now + duration);
+    }
+
+    pub fn is_banned(&mut self, peer: u16) -> bool {
+        self.stats(peer, Instant::now()).banned_until.is_some()
+    }
+
+    /// Count an invalid message. Returns true if the peer is banned as a result.
+    pub fn record_invalid(&mut self, peer: u16) -> bool {
+        let now = Instant::now();
+        let (limit, duration) = (self.limits.max_invalid_messages, Duration::from_millis(self.limits.ban_duration_ms));
+        let stats = self.stats(peer, now);
+        stats.invalid += 1;
+        if stats.invalid > limit {
+            Self::ban(stats, peer, now, duration, "too many invalid messages");
+        }
+        stats.banned_until.is_some()
+    }
+
+    /// Count an instance created on behalf of a peer. Returns false if the peer
+    /// exceeded its quota (and is banned now).
+    pub fn record_instance_creation(&mut self, peer: u16) -> bool {
+        let now = Instant::now();
+        let (limit, duration) = (self.limits.max_new_instances, Duration::from_millis(self.limits.ban_duration_ms));
+        let stats = self.stats(peer, now);
+        stats.created += 1;
+        if stats.created > limit {
+            Self::ban(stats, peer, now, duration, "too many new instances");
+        }
+        stats.banned_until.is_none()
+    }
+
+    /// Count a catch-up request of a peer. Returns false if the peer used up its quota of this
+    /// window; the request is then left unanswered, but the peer is not banned, as a node far
+    /// behind legitimately asks for one page after the other.
+    pub fn record_catch_up_request(&mut self, peer: u16) -> bool {
+        let limit = self.limits.max_catch_up_requests;
+        let stats = self.stats(peer, Instant::now());
+        stats.catch_ups += 1;
+        stats.banned_until.is_none() && stats.catch_ups <= limit
+    }
+
+    /// Currently banned peers
+    pub fn banned(&self) -> Vec<u16> {
+        let now = Instant::now();
+        let mut banned: Vec<u16> = self.peers.iter()
+            .filter(|(_, stats)| stats.banned_until.is_some_and(|until| until > now))
+            .map(|(peer, _)| *peer)
+            .collect();
+        banned.sort_unstable();
+        banned
+    }
+}
+
+
+/// Why a new instance was (not) admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Admitted,
+    /// Already finished
+    Stale,
+    /// Too far ahead of the broadcaster's lowest unfinished sequence number
+    OutOfWindow,
+    /// The peer already opened the maximum number of the broadcaster's instances
+    TooManyOpen,
+}
+
+
+#[derive(Default)]
+struct SenderWindow {
+    watermark: u64,  // every sequence below this has finished
+    finished: BTreeSet<u64>,
+    openers: HashMap<u64, u16>,  // open instances by sequence, with the peer whose message opened them
+    open: HashMap<u16, usize>,  // open instances by the peer that opened them
+}
+
+
+/// Tracks open and finished instances per broadcaster, bounding how many
+/// instances of any single broadcaster are open.
+///
+/// Open instances are counted against the peer whose message opened them, not against
+/// the broadcaster: otherwise a faulty peer could open a correct broadcaster's future
+/// instances with Echoes alone, and its real instances would be turned away.
+pub struct SequenceWindows {
+    limits: Limits,
+    senders: HashMap<u16, SenderWindow>,
+}
+
+
+impl SequenceWindows {
+    pub fn new(limits: Limits) -> Self {
+        Self { limits, senders: HashMap::new() }
+    }
+
+    /// Admit a new instance `id` opened by a message from peer `opener`
+    pub fn admit(&mut self, id: Identifier, opener: u16) -> Admission {
+        let window = self.senders.entry(id.sender).or_default();
+        if id.sequence < window.watermark || window.finished.contains(&id.sequence) {
+            return Admission::Stale;
+        }
+        if id.sequence >= window.watermark.saturating_add(self.limits.sequence_window) {
+            return Admission::OutOfWindow;
+        }
+        if window.openers.contains_key(&id.sequence) {
+            return Admission::Admitted;
+        }
+        let open = window.open.entry(opener).or_default();
+        if *open >= self.limits.max_open_instances_per_sender {
+            return Admission::TooManyOpen;
+        }
+        *open += 1;
+        window.openers.insert(id.sequence, opener);
+        Admission::Admitted
+    }
+
+    /// Undo `admit` for an instance that was not opened after all
+    pub fn release(&mut self, id: Identifier) {
+        let Some(window) = self.senders.get_mut(&id.sender) else { return };
+        let Some(opener) = window.openers.remove(&id.sequence) else { return };
+        if let Some(open) = window.open.get_mut(&opener) {
+            *open -= 1;
+            if *open == 0 {
+                window.open.remove(&opener);
+            }
+        }
+    }
+
+    /// Mark an admitted instance as finished
+    pub fn finish(&mut self, id: Identifier) {
+        self.release(id);
+        self.skip(id);
+    }
+
+    /// Mark an instance as finished that was never admitted, e.g. one delivered before a restart
+    pub fn skip(&mut self, id: Identifier) {
+        let window = self.senders.entry(id.sender).or_default();
+        if id.sequence < window.watermark || !window.finished.insert(id.sequence) {
+            return;
+        }
+        while window.finished.remove(&window.watermark) {
+            window.watermark += 1;
+        }
+    }
+}
@@ -1,110 +1,83 @@
-use asynchronous_broadcast_protocols::{reliable_broadcast::{self}, Config, Identifier, Message, constants::*};
-use futures::io;
-use tokio::{net::UdpSocket, sync::mpsc, task::JoinHandle};
-use std::{collections::HashMap, env::args, sync::Arc, time::Duration};
+use asynchronous_broadcast_protocols::{beacon, dkg::KeyShare, node::Node, Config, Error};
+use std::{env::args, time::Duration};
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = args().collect();
     if args.len() < 2 {
-        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Please provide a config file"));
+        return Err("Please provide a config file".into());
     }
     
     let config = Config::load(&args[1]).await?;
     let my_node = config.get_my_node()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "My node not found in config"))?
+        .ok_or(Error::UnknownNode(config.my_id))?
         .clone();
     
-    let socket: Arc<UdpSocket> = Arc::new(
-        UdpSocket::bind(&my_node.address).await
-            .map_err(|e| io::Error::new(io::ErrorKind::AddrInUse, format!("Failed to bind to {}: {}", my_node.address, e)))?
-    );
+    let node = Node::start(config.clone()).await?;
 
-    // recv
-    let cloned_config = config.clone();
-    let handle = tokio::spawn(receiver(socket.clone(), cloned_config));
-
-    tokio::time::sleep(Duration::from_secs(5)).await;
-    // RBC (send)
-    for sequence in 0..10 {
-        let identifier = Identifier::new(my_node.id, sequence);
-        let message = format!("Message {} from {}", sequence, my_node.id);
-        let cloned_config = config.clone();
-        let _ = tokio::spawn(reliable_broadcast::broadcast(identifier, message.into_bytes(), cloned_config, socket.clone()));
-    }
-    handle.await.map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Join error: {}", e)))?;
-    Ok(())
-}
-
-
-
-
-async fn receiver(socket: Arc<UdpSocket>, config: Config) {
-    let mut buffer: [u8; MESSAGE_BUFFER_SIZE] = [0; MESSAGE_BUFFER_SIZE];
-    let mut instances: HashMap<Identifier, (mpsc::Sender<Message>, JoinHandle<()>)> = HashMap::new();
-    
-    loop {
-        let result = socket.recv_from(&mut buffer).await;
-        if result.is_err() { 
-            eprintln!("Failed to receive message: {:?}", result.err());
-            continue; 
-        }
-        let (len, _) = result.unwrap();
-        
-        let message = Message::from_bytes(&buffer[..len]);
-        if message.is_err() { 
-            eprintln!("Failed to parse message: {:?}", message.err());
-            continue; 
+    // evidence
+    let mut evidence_rx = node.subscribe_evidence();
+    tokio::spawn(async move {
+        while let Ok(evidence) = evidence_rx.recv().await {
+            println!("[Evidence]: node {} equivocated in {:?}", evidence.offender(), evidence.id());
         }
-        let message = message.unwrap();
+    });
 
-        let sender_config = config.get_node(message.sender);
-        if sender_config.is_none() {
-            eprintln!("Unknown sender: {}", message.sender);
-            continue;
-        }
-        let sender_config = sender_config.unwrap();
-        let sender_verify_key = ed25519_dalek::SigningKey::from_bytes(&sender_config.privkey).verifying_key();
-        if !message.verify(sender_verify_key) {
-            eprintln!("Failed to verify message signature from node {}", message.sender);
-            continue;
+    // deliveries: recorded in the delivery log if there is one, otherwise only printed
+    let mut delivery_rx = node.subscribe_deliveries();
+    let delivery_log = node.delivery_log();
+    tokio::spawn(async move {
+        while let Ok(delivery) = delivery_rx.recv().await {
+            if let Some(entry) = delivery_log.as_ref().and_then(|log| log.entry(delivery.id)) {
+                println!("[Delivered]: {:?} as entry {}", delivery.id, entry.index);
+                continue;
+            }
+            match String::from_utf8(delivery.payload) {
+                Ok(str_msg) => println!("[RBC Received]: {}", str_msg),
+                Err(e) => eprintln!("Failed to decode received message: {}", e),
+            }
         }
+    });
 
-        if let Some((tx, handle)) = instances.get_mut(&message.id) {
-            if !handle.is_finished() { 
-                if let Err(e) = tx.send(message).await {
-                    eprintln!("Failed to send message to instance: {}", e);
-                }
+    // randomness beacon
+    if let Some(beacon_config) = config.beacon.clone() {
+        let key = KeyShare::load(&beacon_config.key_file)?;
+        key.verify(&config)?;
+        node.install_key(key);
+
+        let mut beacon_rx = node.subscribe_beacon();
+        tokio::spawn(async move {
+            while let Ok(value) = beacon_rx.recv().await {
+                println!("[Beacon]: round {}: {}", value.round, value.to_json()["randomness"]);
+            }
+        });
+        let cloned_node = node.clone();
+        tokio::spawn(async move {
+            if let Err(e) = beacon::run(cloned_node, Duration::from_millis(beacon_config.interval_ms), beacon_config.head_file.map(Into::into)).await {
+                eprintln!("Beacon stopped: {}", e);
             }
-        } else {
-            // New messages
-            let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
-            let cloned_socket = socket.clone();
-            let cloned_config = config.clone();
-            let message_id = message.id;
-            
-            let handle = tokio::spawn(async move {
-                match reliable_broadcast::receive(
-                    reliable_broadcast::Instance::new(message_id, cloned_config.my_id),
-                    rx,
-                    &cloned_config,
-                    cloned_socket
-                ).await {
-                    Ok(msg) => {
-                        match String::from_utf8(msg) {
-                            Ok(str_msg) => println!("[RBC Received]: {}", str_msg),
-                            Err(e) => eprintln!("Failed to decode received message: {}", e),
-                        }
-                    }
-                    Err(e) => eprintln!("Error in reliable broadcast receive: {}", e),
+        });
+        if let Some(address) = beacon_config.http_address {
+            let node = node.clone();
+            tokio::spawn(async move {
+                if let Err(e) = beacon::serve(node, &address).await {
+                    eprintln!("Failed to serve beacon values on {}: {}", address, e);
                 }
             });
-            
-            if let Err(e) = tx.send(message).await {
-                eprintln!("Failed to send initial message to new instance: {}", e);
-            } else {
-                instances.insert(message_id, (tx, handle));
-            }
         }
     }
-}
\ No newline at end of file
+
+    // recv
+    let handle = tokio::spawn(node.clone().run());
+
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    // RBC (send)
+    for sequence in 0..10 {
+        let message = format!("Message {} from {}", sequence, my_node.id);
+        if let Err(e) = node.broadcast(message.into_bytes()).await {
+            eprintln!("Failed to broadcast: {}", e);
+        }
+    }
+    handle.await?;
+    Ok(())
+}
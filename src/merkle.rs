@@ -0,0 +1,141 @@
+use sha2::{Digest, Sha256};
+
+use crate::{constants::DIGEST_SIZE, Error, Result};
+
+
+const PROTOCOL: &str = "merkle proof";
+
+// A proof has one hash per tree level, and there are at most 255 leaves
+const MAX_PROOF_LEN: usize = 8;
+
+
+pub type Hash = [u8; DIGEST_SIZE];
+
+
+fn hash_leaf(leaf: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+
+/// Binary Merkle tree over a fixed number of leaves.
+///
+/// Leaves and inner nodes are hashed with different prefixes. A node without
+/// a sibling at the end of a level moves up unchanged.
+pub struct MerkleTree {
+    levels: Vec<Vec<Hash>>,  // levels[0] are the leaf hashes, the last level is the root
+}
+
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Self {
+        let mut levels = vec![leaves.iter().map(|leaf| hash_leaf(leaf.as_ref())).collect::<Vec<Hash>>()];
+        if levels[0].is_empty() {
+            levels[0].push(hash_leaf(&[]));
+        }
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level.chunks(2).map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            }).collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Sibling hashes from the leaf `index` up to the root
+    pub fn proof(&self, index: usize) -> Vec<Hash> {
+        let mut proof = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
+
+
+/// Check that `leaf` is leaf `index` of a tree with `leaf_count` leaves and root `root`
+pub fn verify(root: &Hash, index: usize, leaf: &[u8], proof: &[Hash], leaf_count: usize) -> bool {
+    if index >= leaf_count {
+        return false;
+    }
+    let mut hash = hash_leaf(leaf);
+    let mut siblings = proof.iter();
+    let (mut index, mut len) = (index, leaf_count);
+    while len > 1 {
+        if index ^ 1 < len {
+            let Some(sibling) = siblings.next() else { return false };
+            hash = if index % 2 == 0 { hash_node(&hash, sibling) } else { hash_node(sibling, &hash) };
+        }
+        index /= 2;
+        len = len.div_ceil(2);
+    }
+    siblings.next().is_none() && hash == *root
+}
+
+
+/// Erasure-coded fragment of a message with its Merkle proof
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProvenFragment {
+    pub root: Hash,
+    pub proof: Vec<Hash>,
+    pub data: Vec<u8>,
+}
+
+
+impl ProvenFragment {
+    /// Check that this is fragment `index` of `leaf_count`
+    pub fn verify(&self, index: usize, leaf_count: usize) -> bool {
+        verify(&self.root, index, &self.data, &self.proof, leaf_count)
+    }
+
+    pub fn to_bytes(&self, result: &mut Vec<u8>) {
+        result.extend_from_slice(&self.root);
+        result.push(self.proof.len() as u8);
+        for hash in &self.proof {
+            result.extend_from_slice(hash);
+        }
+        result.extend_from_slice(&self.data);
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < DIGEST_SIZE + 1 {
+            return Err(Error::decode(PROTOCOL, "fragment truncated"));
+        }
+        let root = bytes[..DIGEST_SIZE].try_into().unwrap();
+        let proof_len = bytes[DIGEST_SIZE] as usize;
+        if proof_len > MAX_PROOF_LEN {
+            return Err(Error::decode(PROTOCOL, format!("proof of {} hashes", proof_len)));
+        }
+        let data_start = DIGEST_SIZE + 1 + proof_len * DIGEST_SIZE;
+        if bytes.len() < data_start {
+            return Err(Error::decode(PROTOCOL, "proof truncated"));
+        }
+        let proof = bytes[DIGEST_SIZE + 1..data_start]
+            .chunks(DIGEST_SIZE)
+            .map(|hash| hash.try_into().unwrap())
+            .collect();
+        Ok(Self { root, proof, data: bytes[data_start..].to_vec() })
+    }
+}
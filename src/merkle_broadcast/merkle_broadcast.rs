@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::{erasure, merkle::{MerkleTree, ProvenFragment}, transport::ReliableLink, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{Instance, MerkleBroadcastMessage};
+
+
+async fn send_message_to_all(message: Message, link: &ReliableLink) -> Result<()> {
+    link.send_to_all(&message.to_bytes()).await
+}
+
+
+/// (k, n): any k of the n fragments recover the message.
+/// Proofs make every accepted fragment correct, so the n-t correct nodes' fragments are enough.
+pub fn code_parameters(config: &Config) -> (usize, usize) {
+    let n = config.nodes.len();
+    (n - config.fault_tolerance(), n)
+}
+
+
+/// Start instance `id` as its sender by sending `Propose(message)` to every node (including this one)
+pub async fn broadcast(id: Identifier, message: Vec<u8>, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
+
+    let message = Message::new_authenticated(
+        id,
+        my_node.id,
+        MessageType::MerkleBroadcast(MerkleBroadcastMessage::Propose(message)),
+        &my_node.privkey,
+        config,
+    );
+    send_message_to_all(message, link).await
+}
+
+
+/// Four-round reliable broadcast with erasure-coded fragments (after Das, Xiang and Ren).
+///
+/// On `Propose(M)` a node encodes M, commits to the fragments with a Merkle root and sends
+/// every node its own fragment with a proof (`Echo`). `Ready(root)` carries only the root.
+/// Once the readies for a root form a delivery quorum, each node sends its fragment to all
+/// (`Fragment`), and nodes that missed the `Propose` decode M from k valid fragments.
+pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, config: &Config, link: Arc<ReliableLink>) -> Result<Vec<u8>> {
+    let quorums = config.quorums();
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let (k, n) = code_parameters(config);
+    let order = config.node_ids();  // node order[i] holds fragment i
+    let my_index = order.binary_search(&instance.my_id)
+        .map_err(|_| Error::UnknownNode(instance.my_id))?;
+
+    while let Some(message) = rx.recv().await {
+        let MessageType::MerkleBroadcast(merkle_message) = message.payload else { continue };
+
+        match merkle_message {
+            MerkleBroadcastMessage::Propose(m) => {
+                if instance.id.sender != message.sender || instance.message.is_some() {
+                    continue;
+                }
+                let fragments = erasure::encode(&m, k, n)?;
+                let tree = MerkleTree::new(&fragments);
+                let root = tree.root();
+                instance.own_fragments.insert(root, (fragments[my_index].clone(), tree.proof(my_index)));
+                instance.message = Some((m, root));
+
+                for (index, (target, data)) in order.iter().zip(fragments).enumerate() {
+                    let message = Message::new_authenticated(
+                        instance.id,
+                        instance.my_id,
+                        MessageType::MerkleBroadcast(MerkleBroadcastMessage::Echo(ProvenFragment { root, proof: tree.proof(index), data })),
+                        &my_node.privkey,
+                        config,
+                    );
+                    link.send(*target, &message.to_bytes()).await?;
+                }
+            }
+
+            MerkleBroadcastMessage::Echo(fragment) => {
+                if !fragment.verify(my_index, n) {
+                    continue;
+                }
+                if !instance.echoed.insert(message.sender) {  // Not first time
+                    continue;
+                }
+                let root = fragment.root;
+                instance.own_fragments.entry(root).or_insert((fragment.data, fragment.proof));
+                let voters = instance.echo_messages.entry(root).or_default();
+                voters.insert(message.sender);
+                if !instance.ready_sent && quorums.is_echo_quorum(voters) {
+                    instance.ready_sent = true;
+                    let message = Message::new_authenticated(
+                        instance.id,
+                        instance.my_id,
+                        MessageType::MerkleBroadcast(MerkleBroadcastMessage::Ready(root)),
+                        &my_node.privkey,
+                        config,
+                    );
+                    send_message_to_all(message, &link).await?;
+                }
+            }
+
+            MerkleBroadcastMessage::Ready(root) => {
+                if !instance.readied.insert(message.sender) {  // Not first time
+                    continue;
+                }
+                let voters = instance.ready_messages.entry(root).or_default();
+                voters.insert(message.sender);
+                let deliverable = quorums.is_delivery_quorum(voters);
+                if !instance.ready_sent && quorums.is_ready_amplification(voters) {
+                    instance.ready_sent = true;
+                    let message = Message::new_authenticated(
+                        instance.id,
+                        instance.my_id,
+                        MessageType::MerkleBroadcast(MerkleBroadcastMessage::Ready(root)),
+                        &my_node.privkey,
+                        config,
+                    );
+                    send_message_to_all(message, &link).await?;
+                }
+                if deliverable && instance.root.is_none() {
+                    instance.root = Some(root);
+                }
+            }
+
+            MerkleBroadcastMessage::Fragment(fragment) => {
+                let Ok(index) = order.binary_search(&message.sender) else { continue };
+                if !fragment.verify(index, n) {
+                    continue;
+                }
+                if !instance.fragmented.insert(message.sender) {  // Not first time
+                    continue;
+                }
+                instance.fragments.entry(fragment.root).or_default().push((index, fragment.data));
+            }
+        }
+
+        let Some(root) = instance.root else { continue };
+
+        // Other nodes may need this node's fragment to decode, so it goes out before delivering.
+        // Some correct node echoed the root to everyone, so the fragment arrives eventually.
+        if !instance.fragment_sent {
+            let Some((data, proof)) = instance.own_fragments.get(&root) else { continue };
+            instance.fragment_sent = true;
+            let message = Message::new_authenticated(
+                instance.id,
+                instance.my_id,
+                MessageType::MerkleBroadcast(MerkleBroadcastMessage::Fragment(ProvenFragment { root, proof: proof.clone(), data: data.clone() })),
+                &my_node.privkey,
+                config,
+            );
+            send_message_to_all(message, &link).await?;
+        }
+
+        match &instance.message {
+            Some((m, message_root)) if *message_root == root => return Ok(m.clone()),
+            _ => {}
+        }
+        let Some(fragments) = instance.fragments.get(&root) else { continue };
+        if fragments.len() < k {
+            continue;
+        }
+        let m = erasure::decode(fragments, k, n)?;
+        // A correct node computed the root from a whole message, so this only fails on a bug
+        if MerkleTree::new(&erasure::encode(&m, k, n)?).root() != root {
+            return Err(Error::Erasure("decoded message does not match the root".to_string()));
+        }
+        return Ok(m);
+    }
+    Err(Error::InstanceClosed(instance.id))
+}
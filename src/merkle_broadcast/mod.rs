@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod merkle_broadcast;
+
+// re-export all public items from merkle_broadcast module
+pub use merkle_broadcast::*;
+pub use types::*;
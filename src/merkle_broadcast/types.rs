@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{constants::DIGEST_SIZE, merkle::{Hash, ProvenFragment}, Error, Identifier, Result};
+
+
+pub struct Instance {
+    pub id: Identifier,
+    pub my_id: u16,
+    pub message: Option<(Vec<u8>, Hash)>,  // Propose of the sender and its root
+    pub root: Option<Hash>,  // root with a delivery quorum
+    pub own_fragments: HashMap<Hash, (Vec<u8>, Vec<Hash>)>,  // root -> (this node's fragment, proof)
+    pub echo_messages: HashMap<Hash, HashSet<u16>>,
+    pub ready_messages: HashMap<Hash, HashSet<u16>>,
+    pub fragments: HashMap<Hash, Vec<(usize, Vec<u8>)>>,  // root -> (index, fragment)
+    pub echoed: HashSet<u16>,  // senders of any Echo
+    pub readied: HashSet<u16>,  // senders of any Ready
+    pub fragmented: HashSet<u16>,  // senders of any Fragment
+    pub ready_sent: bool,
+    pub fragment_sent: bool,
+}
+
+
+impl Instance {
+    pub fn new(id: Identifier, my_id: u16) -> Self {
+        Self {
+            id, my_id, message: None,
+            root: None,
+            own_fragments: HashMap::new(),
+            echo_messages: HashMap::new(),
+            ready_messages: HashMap::new(),
+            fragments: HashMap::new(),
+            echoed: HashSet::new(),
+            readied: HashSet::new(),
+            fragmented: HashSet::new(),
+            ready_sent: false,
+            fragment_sent: false,
+        }
+    }
+}
+
+
+// Protocol Identifier
+pub const MERKLE_IDENTIFIER: u8 = 4;
+const PROTOCOL: &str = "merkle broadcast";
+
+// Message Types
+const MSG_PROPOSE: u8 = 0;
+const MSG_ECHO: u8 = 1;
+const MSG_READY: u8 = 2;
+const MSG_FRAGMENT: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MerkleBroadcastMessage {
+    Propose(Vec<u8>),
+    Echo(ProvenFragment),  // the recipient's fragment
+    Ready(Hash),
+    Fragment(ProvenFragment),  // the sender's fragment
+}
+
+
+impl MerkleBroadcastMessage {
+    /// Message types an honest node sends at most once per instance.
+    /// Each node gets a different Echo, so those are not votes.
+    pub fn vote_kind(&self) -> Option<u8> {
+        match self {
+            Self::Propose(_) => Some(MSG_PROPOSE),
+            Self::Echo(_) => None,
+            Self::Ready(_) => Some(MSG_READY),
+            Self::Fragment(_) => Some(MSG_FRAGMENT),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(MERKLE_IDENTIFIER);
+
+        match self {
+            Self::Propose(msg) => {
+                result.push(MSG_PROPOSE);
+                result.extend_from_slice(msg);
+            }
+            Self::Echo(fragment) => {
+                result.push(MSG_ECHO);
+                fragment.to_bytes(&mut result);
+            }
+            Self::Ready(root) => {
+                result.push(MSG_READY);
+                result.extend_from_slice(root);
+            }
+            Self::Fragment(fragment) => {
+                result.push(MSG_FRAGMENT);
+                fragment.to_bytes(&mut result);
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != MERKLE_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a Merkle Broadcast message"));
+        }
+
+        match bytes[1] {
+            MSG_PROPOSE => Ok(Self::Propose(bytes[2..].to_vec())),
+            MSG_ECHO => Ok(Self::Echo(ProvenFragment::from_bytes(&bytes[2..])?)),
+            MSG_READY => {
+                let root = bytes[2..]
+                    .try_into()
+                    .map_err(|_| Error::decode(PROTOCOL, format!("READY: expected {} bytes", 2 + DIGEST_SIZE)))?;
+                Ok(Self::Ready(root))
+            }
+            MSG_FRAGMENT => Ok(Self::Fragment(ProvenFragment::from_bytes(&bytes[2..])?)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
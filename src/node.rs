@@ -0,0 +1,1151 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use bls12_381::Scalar;
+use tokio::{sync::{broadcast, mpsc, watch}, task::JoinHandle};
+
+use crate::{beacon::BeaconValue, catch_up::{self, CatchUpMessage, Certificate, RecentCertificates, Watermarks}, delivery_log::DeliveryLog, reconfig::{self, ConfigChange, SignedConfigChange}, wal::{Counter, Record, Recovery, Wal}, avid::{self, AvidMessage, Commitment, FragmentStore}, avss::{self, AvssMessage, Sharing}, constants::*, crash_broadcast, dkg::{self, KeyShare}, reshare::{self, ReshareProgress}, threshold::{self, RecentSignatures, Signature, SignatureShare, ThresholdMessage}, two_step_broadcast, merkle_broadcast, evidence::{EquivocationDetector, Evidence}, limits::{Admission, PeerGuard, SequenceWindows}, polynomial::Committee, reliable_broadcast, transport::ReliableLink, Authenticity, BroadcastProtocol, Config, Domain, Identifier, Message, MessageType, Error, Result};
+
+
+/// A payload delivered by the broadcast protocol
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delivery {
+    pub id: Identifier,
+    pub payload: Vec<u8>,
+}
+
+
+/// A running protocol node: receives messages from the link, authenticates them,
+/// and dispatches them to per-instance protocol tasks.
+pub struct Node {
+    configs: Mutex<BTreeMap<u64, (Arc<Config>, Domain)>>,  // by epoch, from the one this node started in
+    staged: Mutex<HashMap<ConfigChange, Config>>,  // configs given for the next epoch, installed once agreed
+    agreed: Mutex<BTreeMap<u64, ConfigChange>>,  // agreed changes by the epoch they start
+    reconfigurations: Mutex<HashMap<u64, Reconfiguration>>,
+    future: Mutex<Vec<Received>>,  // messages of the next epoch, replayed once it is installed
+    inbox: mpsc::Sender<Received>,
+    inbox_rx: Mutex<Option<mpsc::Receiver<Received>>>,  // taken by `run`
+    wal: Option<Arc<Wal>>,
+    recovery: Mutex<Recovery>,  // what the previous run left in the log, used up by `run` and new instances
+    delivery_log: Option<Arc<DeliveryLog>>,
+    delivered: Mutex<Watermarks>,  // every instance this node delivered, whichever way
+    delivering: tokio::sync::Mutex<()>,  // held while a delivery is written
+    certificates: Mutex<RecentCertificates>,  // proofs of recent reliable broadcast deliveries, for lagging nodes
+    link: Arc<ReliableLink>,
+    detector: Mutex<EquivocationDetector>,
+    evidence_tx: broadcast::Sender<Evidence>,
+    delivery_tx: broadcast::Sender<Delivery>,
+    guard: Mutex<PeerGuard>,
+    next_sequence: tokio::sync::Mutex<u64>,  // broadcast ids, contiguous so that receivers' sequence windows move on
+    next_dispersal: AtomicU64,  // AVID ids, a space of their own
+    next_sharing: AtomicU64,  // AVSS ids, another one
+    fragments: Mutex<FragmentStore>,  // AVID fragments stored for clients
+    waiters: Mutex<HashMap<Identifier, Vec<mpsc::Sender<Message>>>>,  // pending disperse/retrieve/reconstruct calls
+    sharing_tx: broadcast::Sender<Sharing>,
+    sharings: Mutex<HashMap<Identifier, Sharing>>,  // completed AVSS instances
+    revealed: Mutex<HashSet<Identifier>>,  // sharings whose share this node made public
+    key_generations: Mutex<HashMap<u64, KeyGeneration>>,
+    key: Mutex<Option<KeyShare>>,  // this node's share of the cluster's threshold key
+    resharings: Mutex<HashMap<u64, Resharing>>,
+    signatures: Mutex<RecentSignatures>,  // threshold signatures this node helped make, the most recent ones
+    beacon_tx: broadcast::Sender<BeaconValue>,
+    beacon_values: Mutex<BTreeMap<u64, BeaconValue>>,  // the last BEACON_HISTORY rounds
+}
+
+
+/// Sender and bytes of a message from the link
+type Received = (u16, Vec<u8>);
+
+
+/// A key generation session. Messages are queued until this node starts the session,
+/// and dropped once it has finished.
+struct KeyGeneration {
+    tx: Option<mpsc::Sender<Message>>,  // None once finished
+    start: Option<(mpsc::Receiver<Message>, watch::Sender<Option<KeyShare>>)>,  // taken when this node starts the session
+    opener: u16,  // the peer whose message created the session
+    result: watch::Receiver<Option<KeyShare>>,
+}
+
+
+/// A resharing session. Messages are queued in `rx` until this node starts the session.
+struct Resharing {
+    tx: mpsc::Sender<Message>,
+    rx: Option<mpsc::Receiver<Message>>,
+}
+
+
+/// A configuration change session, keyed by the epoch it ends
+struct Reconfiguration {
+    tx: mpsc::Sender<Message>,
+    commands: mpsc::Sender<SignedConfigChange>,
+    decision: watch::Receiver<Option<ConfigChange>>,
+}
+
+
+impl KeyGeneration {
+    fn new(opener: u16) -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        let (result_tx, result) = watch::channel(None);
+        Self { tx: Some(tx), start: Some((rx, result_tx)), opener, result }
+    }
+}
+
+
+impl Resharing {
+    fn new() -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        Self { tx, rx: Some(rx) }
+    }
+}
+
+
+impl Node {
+    pub async fn start(config: Config) -> Result<Arc<Self>> {
+        let (wal, mut recovery) = match &config.wal {
+            Some(wal_config) => {
+                let (wal, records) = Wal::open(wal_config)?;
+                let recovery = Recovery::new(records);
+                wal.compact(&recovery.compacted())?;
+                (Some(Arc::new(wal)), recovery)
+            }
+            None => (None, Recovery::default()),
+        };
+        let delivery_log = match &config.delivery_log {
+            Some(path) => Some(Arc::new(DeliveryLog::open(path)?)),
+            None => None,
+        };
+        let link = ReliableLink::bind(&config).await?;
+        let domain = config.domain();
+        let mut fragments = FragmentStore::new(config.limits.max_fragment_bytes_per_client);
+        for (id, fragment) in std::mem::take(&mut recovery.fragments) {
+            fragments.insert(id, fragment);
+        }
+        let (evidence_tx, _) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+        let (delivery_tx, _) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+        let (sharing_tx, _) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+        let (beacon_tx, _) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+        let (inbox, inbox_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        Ok(Arc::new(Self {
+            link,
+            detector: Mutex::new(EquivocationDetector::new()),
+            evidence_tx,
+            delivery_tx,
+            guard: Mutex::new(PeerGuard::new(config.limits.clone())),
+            next_sequence: tokio::sync::Mutex::new(recovery.next(Counter::Broadcast)),
+            next_dispersal: AtomicU64::new(recovery.next(Counter::Dispersal)),
+            next_sharing: AtomicU64::new(recovery.next(Counter::Sharing)),
+            fragments: Mutex::new(fragments),
+            waiters: Mutex::new(HashMap::new()),
+            sharing_tx,
+            sharings: Mutex::new(HashMap::new()),
+            revealed: Mutex::new(HashSet::new()),
+            key_generations: Mutex::new(HashMap::new()),
+            key: Mutex::new(None),
+            resharings: Mutex::new(HashMap::new()),
+            signatures: Mutex::new(RecentSignatures::new(MAX_KEPT_SIGNATURES)),
+            beacon_tx,
+            beacon_values: Mutex::new(BTreeMap::new()),
+            configs: Mutex::new(BTreeMap::from([(config.epoch, (Arc::new(config), domain))])),
+            staged: Mutex::new(HashMap::new()),
+            agreed: Mutex::new(BTreeMap::new()),
+            reconfigurations: Mutex::new(HashMap::new()),
+            future: Mutex::new(Vec::new()),
+            inbox,
+            inbox_rx: Mutex::new(Some(inbox_rx)),
+            wal,
+            recovery: Mutex::new(recovery),
+            delivery_log,
+            delivered: Mutex::new(Watermarks::default()),
+            delivering: tokio::sync::Mutex::new(()),
+            certificates: Mutex::new(RecentCertificates::new(MAX_KEPT_CERTIFICATE_BYTES)),
+        }))
+    }
+
+    /// The configuration of the current epoch
+    pub fn config(&self) -> Arc<Config> {
+        self.configs.lock().unwrap().values().next_back().map(|(config, _)| config.clone()).expect("started with a config")
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.config().epoch
+    }
+
+    /// The configuration of `epoch`, if this node has been a member then
+    pub fn config_of(&self, epoch: u64) -> Option<Arc<Config>> {
+        self.configs.lock().unwrap().get(&epoch).map(|(config, _)| config.clone())
+    }
+
+    /// The signing domain of `epoch`, computed once when the epoch was installed
+    fn domain_of(&self, epoch: u64) -> Option<Domain> {
+        self.configs.lock().unwrap().get(&epoch).map(|(_, domain)| *domain)
+    }
+
+    pub fn link(&self) -> Arc<ReliableLink> {
+        self.link.clone()
+    }
+
+    /// All equivocation evidence this node has detected or received
+    pub fn evidence(&self) -> Vec<Evidence> {
+        self.detector.lock().unwrap().evidence()
+    }
+
+    /// Nodes proven to have equivocated
+    pub fn offenders(&self) -> Vec<u16> {
+        self.detector.lock().unwrap().offenders()
+    }
+
+    /// Stream of newly learnt evidence
+    pub fn subscribe_evidence(&self) -> broadcast::Receiver<Evidence> {
+        self.evidence_tx.subscribe()
+    }
+
+    /// Stream of delivered payloads, whichever protocol is configured
+    pub fn subscribe_deliveries(&self) -> broadcast::Receiver<Delivery> {
+        self.delivery_tx.subscribe()
+    }
+
+    /// The record of everything this node delivered, if it keeps one
+    pub fn delivery_log(&self) -> Option<Arc<DeliveryLog>> {
+        self.delivery_log.clone()
+    }
+
+    /// Peers currently banned for abusive traffic
+    pub fn banned_peers(&self) -> Vec<u16> {
+        self.guard.lock().unwrap().banned()
+    }
+
+    /// Reliably broadcast `payload` from this node under the next free sequence number
+    ///
+    /// A sequence number is only taken once its broadcast is logged: receivers wait for every
+    /// number in turn, so one skipped would stall this node's broadcasts for good.
+    pub async fn broadcast(&self, payload: Vec<u8>) -> Result<Identifier> {
+        if payload.len() > MAX_BROADCAST_PAYLOAD_SIZE {
+            return Err(Error::PayloadTooLarge { size: payload.len(), limit: MAX_BROADCAST_PAYLOAD_SIZE });
+        }
+        let config = self.config();
+        let id = {
+            let mut next_sequence = self.next_sequence.lock().await;
+            let id = Identifier::new(config.my_id, *next_sequence);
+            if let Some(wal) = &self.wal {
+                wal.write(&Record::Broadcast { id, payload: payload.clone() })?;
+            }
+            // Written records are replayed on restart, so the number is taken even if the sync fails
+            *next_sequence += 1;
+            id
+        };
+        if let Some(wal) = &self.wal {
+            wal.sync().await?;
+        }
+        self.send_broadcast(id, payload, &config).await?;
+        Ok(id)
+    }
+
+    async fn send_broadcast(&self, id: Identifier, payload: Vec<u8>, config: &Config) -> Result<()> {
+        match config.broadcast_protocol() {
+            BroadcastProtocol::ReliableBroadcast => reliable_broadcast::broadcast(id, payload, config, &self.link).await,
+            BroadcastProtocol::CrashBroadcast => crash_broadcast::broadcast(id, payload, config, &self.link).await,
+            BroadcastProtocol::TwoStepBroadcast => two_step_broadcast::broadcast(id, payload, config, &self.link).await,
+            BroadcastProtocol::MerkleBroadcast => merkle_broadcast::broadcast(id, payload, config, &self.link).await,
+        }
+    }
+
+    /// Append `record` to the write-ahead log, if the node keeps one
+    async fn log(&self, record: &Record) -> Result<()> {
+        match &self.wal {
+            Some(wal) => wal.append(record).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Log and publish `payload` as the delivery of instance `id`, unless the instance was delivered
+    /// already: an instance can finish by itself after this node caught up on it, or the other way round
+    async fn deliver(&self, id: Identifier, payload: Vec<u8>, certificate: Option<Certificate>) -> bool {
+        {
+            // One delivery is written at a time, so that none is written twice
+            let _writing = self.delivering.lock().await;
+            if self.delivered.lock().unwrap().contains(id) {
+                return false;
+            }
+            // Not marked as delivered yet, so the instance or a catch-up retries it. The delivery log
+            // takes a repeated instance, should the write-ahead log not have recorded it.
+            if let Err(e) = self.write_delivery(id, &payload).await {
+                eprintln!("Not delivering {:?}: {}", id, e);
+                return false;
+            }
+            self.delivered.lock().unwrap().insert(id);
+        }
+        // The record is written, and replayed as a delivery on restart, so it is published either way
+        if let Some(wal) = &self.wal {
+            if let Err(e) = wal.sync().await {
+                eprintln!("Delivering {:?} before its record is synced: {}", id, e);
+            }
+        }
+        self.detector.lock().unwrap().forget(id, false);
+        if let Some(certificate) = certificate {
+            self.certificates.lock().unwrap().insert(certificate);
+        }
+        let _ = self.delivery_tx.send(Delivery { id, payload });
+        true
+    }
+
+    /// Record the delivery of instance `id` in the delivery log and the write-ahead log. The writes
+    /// run on a blocking thread, so that they do not hold up the tasks receiving messages.
+    async fn write_delivery(&self, id: Identifier, payload: &[u8]) -> Result<()> {
+        if self.delivery_log.is_none() && self.wal.is_none() {
+            return Ok(());
+        }
+        let (delivery_log, wal, payload) = (self.delivery_log.clone(), self.wal.clone(), payload.to_vec());
+        tokio::task::spawn_blocking(move || {
+            if let Some(delivery_log) = delivery_log {
+                delivery_log.append(id, &payload)?;
+            }
+            match wal {
+                Some(wal) => wal.write(&Record::Delivered { id, payload }),
+                None => Ok(()),
+            }
+        }).await.expect("writing a delivery panicked")
+    }
+
+    /// Ask the other nodes for the reliable broadcast deliveries this node missed, e.g. while it was
+    /// offline. Each comes with the signed Ready messages of a delivery quorum, and is delivered
+    /// through `subscribe_deliveries` once they are verified. Only reliable broadcast leaves such
+    /// proofs, so other protocols fail with `CatchUpUnsupported`.
+    pub async fn catch_up(&self) -> Result<()> {
+        let config = self.config();
+        if config.broadcast_protocol() != BroadcastProtocol::ReliableBroadcast {
+            return Err(Error::CatchUpUnsupported(config.broadcast_protocol()));
+        }
+        let targets: Vec<u16> = config.node_ids().into_iter().filter(|id| *id != config.my_id).collect();
+        self.request_catch_up(&targets, None, &config).await
+    }
+
+    async fn request_catch_up(&self, targets: &[u16], after: Option<Identifier>, config: &Config) -> Result<()> {
+        let watermarks = self.delivered.lock().unwrap().to_vec();
+        catch_up::request(watermarks, after, targets, config, &self.link).await
+    }
+
+    /// Pick up where the previous run stopped: finished instances stay finished, and whatever
+    /// this node logged as sent is sent again, as peers may have missed it in the crash
+    async fn recover(&self, windows: &mut SequenceWindows) {
+        let (broadcasts, delivered, votes) = {
+            let mut recovery = self.recovery.lock().unwrap();
+            let delivered = std::mem::take(&mut recovery.delivered);
+            recovery.votes.retain(|id, _| !delivered.contains_key(id));
+            let votes: Vec<Message> = recovery.votes.values().flatten().cloned().collect();
+            (std::mem::take(&mut recovery.broadcasts), delivered, votes)
+        };
+        {
+            let mut watermarks = self.delivered.lock().unwrap();
+            for id in delivered.keys() {
+                watermarks.insert(*id);
+                windows.skip(*id);
+            }
+        }
+
+        let config = self.config();
+        for (id, payload) in broadcasts.into_iter().filter(|(id, _)| !delivered.contains_key(id)) {
+            if let Err(e) = self.send_broadcast(id, payload, &config).await {
+                eprintln!("Failed to resume broadcast {:?}: {}", id, e);
+            }
+        }
+        for vote in votes {
+            if let Err(e) = self.link.send_to_all(&vote.to_bytes()).await {
+                eprintln!("Failed to resend vote in {:?}: {}", vote.id, e);
+            }
+        }
+
+        // A node with a log may have been down, and instances finished without it
+        if self.wal.is_some() {
+            if let Err(e) = self.catch_up().await {
+                eprintln!("Failed to ask for missed deliveries: {}", e);
+            }
+        }
+    }
+
+    /// Disperse `blob` across the cluster and return its commitment once 2t+1 nodes stored their fragments
+    pub async fn disperse(&self, blob: &[u8]) -> Result<Commitment> {
+        let config = self.config();
+        let id = Identifier::new(config.my_id, self.next_dispersal.fetch_add(1, Ordering::Relaxed));
+        self.log(&Record::Taken { counter: Counter::Dispersal, id }).await?;
+        let rx = self.wait_for(id);
+        let result = avid::disperse(id, blob, rx, &config, &self.link).await;
+        self.prune_waiters();
+        result
+    }
+
+    /// Retrieve a dispersed blob from the nodes holding its fragments
+    pub async fn retrieve(&self, commitment: &Commitment) -> Result<Vec<u8>> {
+        let rx = self.wait_for(commitment.id);
+        let result = avid::retrieve(commitment, rx, &self.config(), &self.link).await;
+        self.prune_waiters();
+        result
+    }
+
+    /// Fetch the fragment this node never got of a dispersed blob: retrieve the blob from the
+    /// other nodes and encode it again. At least t+1 correct nodes hold a fragment, as 2t+1 acknowledged.
+    async fn recover_fragment(&self, commitment: Commitment) {
+        let id = commitment.id;
+        let recovered = match self.retrieve(&commitment).await {
+            Ok(blob) => avid::fragment_of(&blob, commitment.root, &self.config()),
+            Err(e) => Err(e),
+        };
+        let stored = match recovered {
+            Ok(fragment) => avid::store(id, fragment, &self.fragments, self.wal.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            eprintln!("Failed to recover fragment of {:?}: {}", id, e);
+        }
+    }
+
+    /// Deal `secret` to the cluster with AVSS. Every node, this one included, reports
+    /// its share through `subscribe_sharings` once the sharing completes.
+    pub async fn share(&self, secret: Scalar) -> Result<Identifier> {
+        let config = self.config();
+        let id = Identifier::new(config.my_id, self.next_sharing.fetch_add(1, Ordering::Relaxed));
+        self.log(&Record::Taken { counter: Counter::Sharing, id }).await?;
+        avss::share(id, secret, &config, &self.link).await?;
+        Ok(id)
+    }
+
+    /// Stream of completed sharings, dealt by any node
+    pub fn subscribe_sharings(&self) -> broadcast::Receiver<Sharing> {
+        self.sharing_tx.subscribe()
+    }
+
+    /// This node's view of a completed sharing
+    pub fn sharing(&self, id: Identifier) -> Option<Sharing> {
+        self.sharings.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Reveal this node's share of sharing `id` and recover the secret from t+1 valid shares.
+    ///
+    /// Nodes only reveal a share once their own application asked for it;
+    /// afterwards they answer later reveals so that slower nodes can catch up.
+    pub async fn reconstruct(&self, id: Identifier) -> Result<Scalar> {
+        let sharing = self.sharing(id).ok_or(Error::NoSharing(id))?;
+        let config = self.config();
+        let my_node = config.get_my_node()
+            .ok_or(Error::UnknownNode(config.my_id))?;
+
+        let mut rx = self.wait_for(id);
+        self.revealed.lock().unwrap().insert(id);
+        let message = Message::new_authenticated(
+            id,
+            my_node.id,
+            MessageType::Avss(AvssMessage::Reveal(sharing.share)),
+            &my_node.privkey,
+            &config,
+        );
+        self.link.send_to_all(&message.to_bytes()).await?;
+
+        let mut shares = Vec::new();
+        let result = loop {
+            let Some(message) = rx.recv().await else { break Err(Error::InstanceClosed(id)) };
+            let (MessageType::Avss(AvssMessage::Reveal(share)) | MessageType::Avss(AvssMessage::Answer(share))) = message.payload else { continue };
+            shares.push((message.sender, share));
+            if let Some(secret) = avss::reconstruct(&sharing, &shares, &config) {
+                break Ok(secret);
+            }
+        };
+        drop(rx);
+        self.prune_waiters();
+        result
+    }
+
+    /// Run key generation session `session` together with the other nodes and return this node's share.
+    ///
+    /// Every node must call this: a node only queues the messages of sessions it has not started,
+    /// so that peers cannot make it deal for sessions nobody asked for. Calling it again for a
+    /// finished session returns the same share.
+    pub async fn generate_key(self: &Arc<Self>, session: u64) -> Result<KeyShare> {
+        let my_id = self.config().my_id;
+        let (start, mut result) = {
+            let mut key_generations = self.key_generations.lock().unwrap();
+            let generation = key_generations.entry(session).or_insert_with(|| KeyGeneration::new(my_id));
+            (generation.start.take(), generation.result.clone())
+        };
+        if let Some((rx, result_tx)) = start {
+            let node = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = dkg::run(node.clone(), session, rx, result_tx).await {
+                    eprintln!("Error in key generation {}: {}", session, e);
+                }
+                // Keep the result, drop the channel and with it further messages
+                if let Some(generation) = node.key_generations.lock().unwrap().get_mut(&session) {
+                    generation.tx = None;
+                }
+            });
+        }
+        let key = result.wait_for(Option::is_some).await
+            .map_err(|_| Error::InstanceClosed(dkg::session_id(session)))?;
+        Ok(key.clone().expect("waited for a key"))
+    }
+
+    /// The channel into key generation session `session` for a message from peer `from`, and whether
+    /// this node has started the session. None if the session has finished, or if `from` may not
+    /// open it: every peer can only keep a few sessions pending that this node has not started.
+    fn key_generation(&self, session: u64, from: u16) -> Option<(mpsc::Sender<Message>, bool)> {
+        let mut key_generations = self.key_generations.lock().unwrap();
+        if !key_generations.contains_key(&session) {
+            let pending = key_generations.values()
+                .filter(|generation| generation.start.is_some() && generation.opener == from)
+                .count();
+            if pending >= MAX_PENDING_SESSIONS_PER_PEER || !self.guard.lock().unwrap().record_instance_creation(from) {
+                return None;
+            }
+            key_generations.insert(session, KeyGeneration::new(from));
+        }
+        let generation = &key_generations[&session];
+        Some((generation.tx.clone()?, generation.start.is_none()))
+    }
+
+    /// This node's current share of the threshold key, from key generation, a dealer or the last resharing
+    pub fn key(&self) -> Option<KeyShare> {
+        self.key.lock().unwrap().clone()
+    }
+
+    /// Use `key` from now on, e.g. a share handed out by a trusted dealer or loaded from a key file
+    pub fn install_key(&self, key: KeyShare) {
+        *self.key.lock().unwrap() = Some(key);
+    }
+
+    /// Forget the installed key share, e.g. once it was handed over to a node set without this node
+    pub fn erase_key(&self) {
+        *self.key.lock().unwrap() = None;
+    }
+
+    /// Hand the threshold key from the nodes of `old_config` over to those of `new_config` in
+    /// resharing session `session`, keeping the public key. Both must be subsets of the running
+    /// cluster, with their own fault thresholds, and every node of either must call this with the
+    /// same arguments. Old members need their key share installed; new members install their new
+    /// share once `Done` is reported, and leaving members erase theirs once they have dealt it.
+    pub fn reshare(self: &Arc<Self>, session: u64, old_config: &Config, new_config: &Config) -> Result<watch::Receiver<ReshareProgress>> {
+        let old = Committee::of(old_config);
+        let new = Committee::of(new_config);
+        let config = self.config();
+        if let Some(&unknown) = old.nodes.iter().chain(&new.nodes).find(|id| config.get_node(**id).is_none()) {
+            return Err(Error::UnknownNode(unknown));
+        }
+        let rx = {
+            let mut resharings = self.resharings.lock().unwrap();
+            let resharing = resharings.entry(session).or_insert_with(Resharing::new);
+            resharing.rx.take().ok_or(Error::SessionInUse(session))?
+        };
+
+        let (progress_tx, progress) = watch::channel(ReshareProgress::Started);
+        let node = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = reshare::run(node, session, old, new, rx, progress_tx.clone()).await {
+                eprintln!("Error in resharing {}: {}", session, e);
+                progress_tx.send_replace(ReshareProgress::Failed(e.to_string()));
+            }
+        });
+        Ok(progress)
+    }
+
+    /// Refresh the shares of the threshold key among the cluster's nodes: new shares of the same key,
+    /// which cannot be combined with the old ones
+    pub fn refresh(self: &Arc<Self>, session: u64) -> Result<watch::Receiver<ReshareProgress>> {
+        let config = self.config();
+        self.reshare(session, &config, &config)
+    }
+
+    /// The channel into resharing session `session`, and whether this node has started it
+    fn resharing(&self, session: u64) -> (mpsc::Sender<Message>, bool) {
+        let mut resharings = self.resharings.lock().unwrap();
+        let resharing = resharings.entry(session).or_insert_with(Resharing::new);
+        (resharing.tx.clone(), resharing.rx.is_none())
+    }
+
+    /// Approve `payload` under `id` and return the cluster's threshold signature on it.
+    ///
+    /// Sends this node's signature share to every node and combines the first t+1 valid shares
+    /// approving the same payload, so the signature exists only once t+1 nodes, one of them correct,
+    /// approved it. Approvers that miss shares get the signature from those that already have it.
+    pub async fn sign(&self, id: Identifier, payload: &[u8]) -> Result<Signature> {
+        self.approve(id, payload).await.map(|(signature, _)| signature)
+    }
+
+    /// `sign`, also telling whether the signature came finished from a node that already had it,
+    /// i.e. whether this node approved late
+    pub(crate) async fn approve(&self, id: Identifier, payload: &[u8]) -> Result<(Signature, bool)> {
+        let key = self.key().ok_or(Error::NoKey)?;
+        let config = self.config();
+        let my_node = config.get_my_node()
+            .ok_or(Error::UnknownNode(config.my_id))?;
+
+        let mut rx = self.wait_for(id);
+        let share = threshold::sign_share(&key, payload);
+        let message = Message::new_authenticated(
+            id,
+            my_node.id,
+            MessageType::Threshold(Box::new(ThresholdMessage::Share(share.signature))),
+            &my_node.privkey,
+            &config,
+        );
+        self.link.send_to_all(&message.to_bytes()).await?;
+
+        let mut shares: Vec<SignatureShare> = Vec::new();
+        let result = loop {
+            let Some(message) = rx.recv().await else { break Err(Error::InstanceClosed(id)) };
+            let MessageType::Threshold(threshold_message) = message.payload else { continue };
+            match *threshold_message {
+                ThresholdMessage::Share(signature) => {
+                    let share = SignatureShare { node: message.sender, signature };
+                    if !threshold::verify_share(&key, &share, payload, &config) {
+                        continue;
+                    }
+                    shares.push(share);
+                    if let Some(signature) = threshold::combine(&shares, key.threshold(), &config) {
+                        break Ok((signature, false));
+                    }
+                }
+                ThresholdMessage::Signature(signature) => {
+                    if threshold::verify(&signature, &key.public_key(), payload) {
+                        break Ok((signature, true));
+                    }
+                }
+            }
+        };
+        drop(rx);
+        self.prune_waiters();
+        let (signature, late) = result?;
+        self.signatures.lock().unwrap().insert(id, signature);
+
+        // Approvers whose share reached us may have missed ours
+        let answer = Message::new_authenticated(
+            id,
+            my_node.id,
+            MessageType::Threshold(Box::new(ThresholdMessage::Signature(signature))),
+            &my_node.privkey,
+            &config,
+        );
+        for share in shares.iter().filter(|share| share.node != my_node.id) {
+            self.link.send(share.node, &answer.to_bytes()).await?;
+        }
+        Ok((signature, late))
+    }
+
+    /// Stream of beacon values as this node's beacon produces them, see `beacon::run`
+    pub fn subscribe_beacon(&self) -> broadcast::Receiver<BeaconValue> {
+        self.beacon_tx.subscribe()
+    }
+
+    /// The beacon value of `round`, if this node produced it and still keeps it
+    pub fn beacon(&self, round: u64) -> Option<BeaconValue> {
+        self.beacon_values.lock().unwrap().get(&round).cloned()
+    }
+
+    pub fn latest_beacon(&self) -> Option<BeaconValue> {
+        self.beacon_values.lock().unwrap().values().next_back().cloned()
+    }
+
+    pub(crate) fn publish_beacon(&self, value: BeaconValue) {
+        self.restore_beacon(value.clone());
+        let _ = self.beacon_tx.send(value);
+    }
+
+    /// Keep `value` without announcing it, e.g. the head the beacon left before a restart
+    pub(crate) fn restore_beacon(&self, value: BeaconValue) {
+        let mut values = self.beacon_values.lock().unwrap();
+        values.insert(value.round, value);
+        while values.len() > BEACON_HISTORY {
+            values.pop_first();
+        }
+    }
+
+    /// Move this node to `new_config`, the configuration of the next epoch, once the members of
+    /// the current epoch agree on it, and return the new epoch.
+    ///
+    /// The operator gives the new configuration to every member that stays. Each member that calls
+    /// this issues a signed change command. Members only vote for changes their operator staged or
+    /// that t+1 members issued, so a single faulty member cannot move the cluster (see `reconfig::run`). Of concurrent changes to different configurations only the agreed one is
+    /// installed; the others fail with `EpochTaken` and can be issued again for the epoch after.
+    /// Instances of the old epoch finish under its membership and thresholds, new ones use the
+    /// new configuration. Joining nodes start with `new_config` directly, removed nodes keep
+    /// running until their old instances are done.
+    pub async fn reconfigure(self: &Arc<Self>, new_config: Config) -> Result<u64> {
+        let current = self.config();
+        if new_config.epoch != current.epoch + 1 {
+            return Err(Error::EpochMismatch { got: new_config.epoch, expected: current.epoch + 1 });
+        }
+        if new_config.my_id != current.my_id {
+            return Err(Error::Config(format!("my_id cannot change from {} to {}", current.my_id, new_config.my_id)));
+        }
+        new_config.validate()?;
+
+        let change = ConfigChange::of(&new_config);
+        self.staged.lock().unwrap().insert(change, new_config);
+        let (_, commands, mut decision) = self.reconfiguration(current.clone());
+        let _ = commands.send(SignedConfigChange::sign(change, &current)?).await;
+        let agreed = decision.wait_for(Option::is_some).await
+            .map_err(|_| Error::InstanceClosed(reconfig::session_id(current.epoch)))?
+            .expect("waited for a decision");
+        // The change may have been agreed before this node was given its configuration
+        self.install(agreed.epoch).await;
+        if agreed != change {
+            return Err(Error::EpochTaken(change.epoch));
+        }
+        Ok(change.epoch)
+    }
+
+    /// The channels into the configuration change session ending the epoch of `config`, started if it is not running yet
+    fn reconfiguration(self: &Arc<Self>, config: Arc<Config>) -> (mpsc::Sender<Message>, mpsc::Sender<SignedConfigChange>, watch::Receiver<Option<ConfigChange>>) {
+        let epoch = config.epoch;
+        let mut reconfigurations = self.reconfigurations.lock().unwrap();
+        let reconfiguration = reconfigurations.entry(epoch).or_insert_with(|| {
+            let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+            let (commands, commands_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+            let (decision_tx, decision) = watch::channel(None);
+            let node = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = reconfig::run(node, config, rx, commands_rx, decision_tx).await {
+                    eprintln!("Error in reconfiguration of epoch {}: {}", epoch, e);
+                }
+            });
+            Reconfiguration { tx, commands, decision }
+        });
+        (reconfiguration.tx.clone(), reconfiguration.commands.clone(), reconfiguration.decision.clone())
+    }
+
+    /// Whether this node's operator gave it the configuration of `change`
+    pub(crate) fn is_staged(&self, change: &ConfigChange) -> bool {
+        self.staged.lock().unwrap().contains_key(change)
+    }
+
+    /// Record the change the members of the ending epoch agreed on, and install it if this node was given its configuration
+    pub(crate) async fn agree_epoch(&self, change: ConfigChange) {
+        self.agreed.lock().unwrap().insert(change.epoch, change);
+        self.install(change.epoch).await;
+    }
+
+    /// Enter `epoch` with its agreed configuration, if this node has it and is in the epoch before
+    async fn install(&self, epoch: u64) {
+        let Some(change) = self.agreed.lock().unwrap().get(&epoch).copied() else { return };
+        let Some(config) = self.staged.lock().unwrap().remove(&change) else { return };
+        let config = Arc::new(config);
+        {
+            let mut configs = self.configs.lock().unwrap();
+            if configs.keys().next_back() != Some(&(epoch - 1)) {
+                return;
+            }
+            configs.insert(epoch, (config.clone(), config.domain()));
+        }
+        self.staged.lock().unwrap().retain(|staged, _| staged.epoch > epoch);
+        self.link.update_peers(&config).await;
+
+        // Replay what the new epoch's members sent before we got here
+        let future = std::mem::take(&mut *self.future.lock().unwrap());
+        let inbox = self.inbox.clone();
+        tokio::spawn(async move {
+            for received in future {
+                let _ = inbox.send(received).await;
+            }
+        });
+    }
+
+    /// Reply to a signature share with the signature if we already have it
+    async fn answer_share(&self, message: &Message, config: &Config) {
+        let Some(signature) = self.signatures.lock().unwrap().get(&message.id) else { return };
+        let Some(my_node) = config.get_my_node() else { return };
+        let answer = Message::new_authenticated(
+            message.id,
+            my_node.id,
+            MessageType::Threshold(Box::new(ThresholdMessage::Signature(signature))),
+            &my_node.privkey,
+            config,
+        );
+        if let Err(e) = self.link.send(message.sender, &answer.to_bytes()).await {
+            eprintln!("Failed to answer signature share of node {}: {}", message.sender, e);
+        }
+    }
+
+    /// Reply to a reveal with our own share if we already made it public
+    async fn answer_reveal(&self, message: &Message, config: &Config) {
+        if !self.revealed.lock().unwrap().contains(&message.id) {
+            return;
+        }
+        let (Some(sharing), Some(my_node)) = (self.sharing(message.id), config.get_my_node()) else { return };
+        let answer = Message::new_authenticated(
+            message.id,
+            my_node.id,
+            MessageType::Avss(AvssMessage::Answer(sharing.share)),
+            &my_node.privkey,
+            config,
+        );
+        if let Err(e) = self.link.send(message.sender, &answer.to_bytes()).await {
+            eprintln!("Failed to answer reveal of node {}: {}", message.sender, e);
+        }
+    }
+
+    fn wait_for(&self, id: Identifier) -> mpsc::Receiver<Message> {
+        let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        self.waiters.lock().unwrap().entry(id).or_default().push(tx);
+        rx
+    }
+
+    fn prune_waiters(&self) {
+        self.waiters.lock().unwrap().retain(|_, waiters| {
+            waiters.retain(|tx| !tx.is_closed());
+            !waiters.is_empty()
+        });
+    }
+
+    /// Hand an acknowledgement, answer or revealed share to the calls waiting for it.
+    /// Never blocks the receive loop: a waiter with a full queue misses the message.
+    fn forward_to_waiters(&self, message: Message) {
+        if let Some(waiters) = self.waiters.lock().unwrap().get(&message.id) {
+            for tx in waiters {
+                let _ = tx.try_send(message.clone());
+            }
+        }
+    }
+
+    /// Receive loop. Runs until the link fails for good.
+    pub async fn run(self: Arc<Self>) {
+        // Keyed by (id, is AVSS), so that a faulty node cannot claim a broadcast's id for a sharing or vice versa
+        let mut instances: HashMap<(Identifier, bool), (mpsc::Sender<Message>, JoinHandle<()>)> = HashMap::new();
+        let mut windows = SequenceWindows::new(self.config().limits.clone());
+        let mut sharing_windows = SequenceWindows::new(self.config().limits.clone());  // sharings are numbered apart from broadcasts
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel::<(Identifier, bool)>();
+        let mut caught_up_at: Option<Instant> = None;  // last catch-up asked for by instances far ahead
+        self.recover(&mut windows).await;
+
+        // Messages held back for the next epoch are fed in next to the link's
+        let mut inbox = self.inbox_rx.lock().unwrap().take().expect("node already running");
+        let link = self.link.clone();
+        let link_inbox = self.inbox.clone();
+        tokio::spawn(async move {
+            loop {
+                match link.recv().await {
+                    Ok(received) => {
+                        if link_inbox.send(received).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to receive message: {}", e),
+                }
+            }
+        });
+
+        loop {
+            let Some((from, bytes)) = inbox.recv().await else { return };
+
+            // Forget instances that have finished since the last message
+            while let Ok((id, is_avss)) = done_rx.try_recv() {
+                instances.remove(&(id, is_avss));
+                if is_avss { &mut sharing_windows } else { &mut windows }.finish(id);
+                self.detector.lock().unwrap().forget(id, is_avss);
+            }
+
+            if self.guard.lock().unwrap().is_banned(from) {
+                continue;
+            }
+
+            let message = match Message::from_bytes(&bytes) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("Failed to parse message from node {}: {}", from, e);
+                    self.guard.lock().unwrap().record_invalid(from);
+                    continue;
+                }
+            };
+
+            // Each message is handled under the configuration of its epoch
+            let config = match self.config_of(message.domain.epoch) {
+                Some(config) => config,
+                None if message.domain.epoch == self.epoch() + 1 => {
+                    let mut future = self.future.lock().unwrap();
+                    if future.len() < MAX_FUTURE_EPOCH_MESSAGES {
+                        future.push((from, bytes));
+                    }
+                    continue;
+                }
+                None => continue,  // An epoch before this node joined, or too far ahead
+            };
+
+            let Some(domain) = self.domain_of(config.epoch) else { continue };
+            if let Err(e) = message.domain.check(&domain) {
+                eprintln!("Rejected message from node {}: {}", message.sender, e);
+                self.guard.lock().unwrap().record_invalid(from);
+                continue;
+            }
+
+            let authenticity = match message.authenticate(from, &self.link, &config) {
+                Ok(authenticity) => authenticity,
+                Err(e) => {
+                    eprintln!("Rejected message from node {}: {}", from, e);
+                    self.guard.lock().unwrap().record_invalid(from);
+                    continue;
+                }
+            };
+
+            // Votes that come in after this node delivered are not kept around for good
+            if authenticity == Authenticity::Signed && !self.delivered.lock().unwrap().contains(message.id) {
+                let evidence = self.detector.lock().unwrap().observe(&message);
+                if let Some(evidence) = evidence {
+                    self.publish_evidence(evidence).await;
+                }
+            }
+
+            if let MessageType::Evidence(evidence) = message.payload {
+                let verified = match self.config_of(evidence.first.domain.epoch) {
+                    Some(evidence_config) => evidence.verify(&evidence_config),
+                    None => Err(Error::EpochMismatch { got: evidence.first.domain.epoch, expected: config.epoch }),
+                };
+                if let Err(e) = verified {
+                    eprintln!("Rejected evidence from node {}: {}", message.sender, e);
+                    self.guard.lock().unwrap().record_invalid(from);
+                } else if self.detector.lock().unwrap().record((*evidence).clone()) {
+                    self.publish_evidence(*evidence).await;
+                }
+                continue;
+            }
+
+            if let MessageType::Avid(avid_message) = &message.payload {
+                match avid_message {
+                    AvidMessage::Stored(_) | AvidMessage::Answer(_) => self.forward_to_waiters(message),
+                    AvidMessage::Store(_) if !self.guard.lock().unwrap().record_instance_creation(from) => {}
+                    AvidMessage::Commit(_) if message.sender != message.id.sender || self.fragments.lock().unwrap().contains(&message.id) => {}
+                    AvidMessage::Commit(_) if !self.guard.lock().unwrap().record_instance_creation(from) => {}
+                    AvidMessage::Commit(commitment) => {
+                        let node = self.clone();
+                        let commitment = commitment.clone();
+                        tokio::spawn(async move { node.recover_fragment(commitment).await });
+                    }
+                    AvidMessage::Store(_) | AvidMessage::Request => {
+                        if let Err(e) = avid::serve(message, &self.fragments, self.wal.as_deref(), &config, &self.link).await {
+                            eprintln!("Failed to serve AVID message from node {}: {}", from, e);
+                        }
+                    }
+                }
+                continue;
+            }
+            if let MessageType::Agreement(_) = &message.payload {
+                if message.id.sender == reconfig::SESSION_KIND {
+                    // A session is run by the members of the epoch it ends
+                    let epoch = message.id.sequence;
+                    if epoch != config.epoch {
+                        continue;
+                    }
+                    let running = self.reconfigurations.lock().unwrap().contains_key(&epoch);
+                    if !running && !self.guard.lock().unwrap().record_instance_creation(from) {
+                        continue;
+                    }
+                    let (tx, _, _) = self.reconfiguration(config);
+                    if let Err(e) = tx.send(message).await {
+                        eprintln!("Failed to send message to reconfiguration of epoch {}: {}", epoch, e);
+                    }
+                    continue;
+                }
+                if message.id.sender != dkg::SESSION_KIND {
+                    continue;
+                }
+                let Some((tx, started)) = self.key_generation(message.id.sequence, from) else { continue };
+                if started {
+                    let _ = tx.send(message).await;  // Closed once the session has finished
+                } else {
+                    // Queue until the application starts the session, without blocking the receive loop
+                    let _ = tx.try_send(message);
+                }
+                continue;
+            }
+            if let MessageType::Reshare(_) = &message.payload {
+                let session = message.id.sequence;
+                let known = self.resharings.lock().unwrap().contains_key(&session);
+                if !known && !self.guard.lock().unwrap().record_instance_creation(from) {
+                    continue;
+                }
+                let (tx, started) = self.resharing(session);
+                if started {
+                    let _ = tx.send(message).await;  // Closed once this node's part is done
+                } else {
+                    // Queue until the application starts the session, without blocking the receive loop
+                    let _ = tx.try_send(message);
+                }
+                continue;
+            }
+            if let MessageType::CatchUp(catch_up_message) = message.payload {
+                match catch_up_message {
+                    CatchUpMessage::Request { watermarks, after } => {
+                        if !self.guard.lock().unwrap().record_catch_up_request(from) {
+                            continue;
+                        }
+                        let certificates = catch_up::missing(&watermarks, after, &self.certificates.lock().unwrap());
+                        if let Err(e) = catch_up::answer(from, certificates, &config, &self.link).await {
+                            eprintln!("Failed to answer catch-up request of node {}: {}", from, e);
+                        }
+                    }
+                    CatchUpMessage::Response(certificates) => {
+                        let mut next_page = certificates.last().map(|certificate| certificate.id);
+                        for certificate in certificates {
+                            let id = certificate.id;
+                            if self.delivered.lock().unwrap().contains(id) {
+                                continue;
+                            }
+                            // Instances of epochs before this node joined cannot be checked
+                            let Some(certificate_config) = certificate.epoch().and_then(|epoch| self.config_of(epoch)) else { continue };
+                            if let Err(e) = certificate.verify(&certificate_config) {
+                                eprintln!("Rejected delivery certificate for {:?} from node {}: {}", id, from, e);
+                                self.guard.lock().unwrap().record_invalid(from);
+                                next_page = None;
+                                break;
+                            }
+                            if !self.deliver(id, certificate.payload.clone(), Some(certificate)).await {
+                                continue;
+                            }
+                            match instances.remove(&(id, false)) {
+                                Some((_, handle)) => {
+                                    handle.abort();
+                                    windows.finish(id);
+                                }
+                                None => windows.skip(id),
+                            }
+                        }
+                        // Ask for the next page until one comes back empty
+                        if let Some(after) = next_page {
+                            if let Err(e) = self.request_catch_up(&[from], Some(after), &config).await {
+                                eprintln!("Failed to ask node {} for missed deliveries: {}", from, e);
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            if let MessageType::Threshold(threshold_message) = &message.payload {
+                if let ThresholdMessage::Share(_) = **threshold_message {
+                    self.answer_share(&message, &config).await;
+                }
+                self.forward_to_waiters(message);
+                continue;
+            }
+            if let MessageType::Avss(AvssMessage::Reveal(_) | AvssMessage::Answer(_)) = &message.payload {
+                if let MessageType::Avss(AvssMessage::Reveal(_)) = &message.payload {
+                    self.answer_reveal(&message, &config).await;
+                }
+                self.forward_to_waiters(message);
+                continue;
+            }
+
+            let is_avss = matches!(message.payload, MessageType::Avss(_));
+            if !is_avss && message.payload.protocol() != Some(config.broadcast_protocol()) {
+                eprintln!("Rejected {:?} message from node {}: cluster runs {:?}", message.payload.protocol(), from, config.broadcast_protocol());
+                self.guard.lock().unwrap().record_invalid(from);
+                continue;
+            }
+
+            if let Some((tx, handle)) = instances.get_mut(&(message.id, is_avss)) {
+                if !handle.is_finished() {
+                    if let Err(e) = tx.send(message).await {
+                        eprintln!("Failed to send message to instance: {}", e);
+                    }
+                }
+            } else {
+                // New messages
+                let windows = if is_avss { &mut sharing_windows } else { &mut windows };
+                match windows.admit(message.id, from) {
+                    Admission::Admitted => {}
+                    Admission::Stale => continue,
+                    Admission::OutOfWindow if !is_avss && config.broadcast_protocol() == BroadcastProtocol::ReliableBroadcast => {
+                        // Its sender is far ahead of what this node delivered: catch up on the instances in between
+                        if caught_up_at.is_none_or(|at| at.elapsed() >= Duration::from_millis(CATCH_UP_INTERVAL_MS)) {
+                            caught_up_at = Some(Instant::now());
+                            if let Err(e) = self.catch_up().await {
+                                eprintln!("Failed to ask for missed deliveries: {}", e);
+                            }
+                        }
+                        eprintln!("Not opening instance {:?} from node {}: {:?}", message.id, from, Admission::OutOfWindow);
+                        continue;
+                    }
+                    admission => {
+                        eprintln!("Not opening instance {:?} from node {}: {:?}", message.id, from, admission);
+                        continue;
+                    }
+                }
+                if !self.guard.lock().unwrap().record_instance_creation(from) {
+                    windows.release(message.id);
+                    continue;
+                }
+
+                let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+                let cloned_link = self.link.clone();
+                let cloned_config = config.clone();
+                let cloned_done_tx = done_tx.clone();
+                let message_id = message.id;
+
+                let handle = if is_avss {
+                    let node = self.clone();
+                    tokio::spawn(async move {
+                        match avss::receive(avss::Instance::new(message_id, config.my_id, Committee::of(&config)), rx, &config, node.link.clone()).await {
+                            Ok(sharing) => {
+                                node.sharings.lock().unwrap().insert(message_id, sharing.clone());
+                                let _ = node.sharing_tx.send(sharing);
+                            }
+                            Err(e) => eprintln!("Error in AVSS receive: {}", e),
+                        }
+                        let _ = cloned_done_tx.send((message_id, is_avss));
+                    })
+                } else {
+                    let node = self.clone();
+                    let votes = self.recovery.lock().unwrap().votes.remove(&message_id).unwrap_or_default();
+                    tokio::spawn(async move {
+                        let result = match cloned_config.broadcast_protocol() {
+                            BroadcastProtocol::ReliableBroadcast => {
+                                let mut instance = reliable_broadcast::Instance::new(message_id, cloned_config.my_id);
+                                instance.wal = node.wal.clone();
+                                instance.restore(&votes);
+                                reliable_broadcast::receive(instance, rx, &cloned_config, cloned_link).await
+                                    .map(|(payload, readies)| (payload.clone(), Some(Certificate { id: message_id, payload, readies })))
+                            }
+                            BroadcastProtocol::CrashBroadcast => crash_broadcast::receive(message_id, rx, &cloned_config, cloned_link).await
+                                .map(|payload| (payload, None)),
+                            BroadcastProtocol::TwoStepBroadcast => two_step_broadcast::receive(
+                                two_step_broadcast::Instance::new(message_id, cloned_config.my_id),
+                                rx,
+                                &cloned_config,
+                                cloned_link
+                            ).await.map(|payload| (payload, None)),
+                            BroadcastProtocol::MerkleBroadcast => merkle_broadcast::receive(
+                                merkle_broadcast::Instance::new(message_id, cloned_config.my_id),
+                                rx,
+                                &cloned_config,
+                                cloned_link
+                            ).await.map(|payload| (payload, None)),
+                        };
+                        match result {
+                            Ok((payload, certificate)) => {
+                                node.deliver(message_id, payload, certificate).await;
+                            }
+                            Err(e) => eprintln!("Error in {:?} receive: {}", cloned_config.broadcast_protocol(), e),
+                        }
+                        let _ = cloned_done_tx.send((message_id, is_avss));
+                    })
+                };
+
+                if let Err(e) = tx.send(message).await {
+                    eprintln!("Failed to send initial message to new instance: {}", e);
+                } else {
+                    instances.insert((message_id, is_avss), (tx, handle));
+                }
+            }
+        }
+    }
+
+    /// Hand new evidence to local subscribers and gossip it to every peer
+    async fn publish_evidence(&self, evidence: Evidence) {
+        let _ = self.evidence_tx.send(evidence.clone());
+
+        let config = self.config();
+        let Some(my_node) = config.get_my_node() else { return };
+        let message = Message::new(
+            config.domain(),
+            evidence.id(),
+            config.my_id,
+            MessageType::Evidence(Box::new(evidence)),
+            &my_node.privkey,
+        );
+        if let Err(e) = self.link.send_to_all(&message.to_bytes()).await {
+            eprintln!("Failed to gossip evidence: {}", e);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use bls12_381::{G1Projective, Scalar};
+use ff::Field;
+use rand_core::OsRng;
+
+use crate::{Config, Error, Result};
+
+
+/// Polynomial over the BLS12-381 scalar field, lowest coefficient first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    pub coefficients: Vec<Scalar>,
+}
+
+
+impl Polynomial {
+    /// Random polynomial of degree `degree` with `constant` at 0
+    pub fn random(constant: Scalar, degree: usize) -> Self {
+        let mut coefficients = vec![constant];
+        coefficients.extend((0..degree).map(|_| Scalar::random(OsRng)));
+        Self { coefficients }
+    }
+
+    pub fn evaluate(&self, x: Scalar) -> Scalar {
+        self.coefficients.iter().rev().fold(Scalar::zero(), |acc, c| acc * x + c)
+    }
+
+    /// Feldman commitment: g^c for every coefficient c
+    pub fn commit(&self) -> Vec<G1Projective> {
+        self.coefficients.iter().map(|c| G1Projective::generator() * c).collect()
+    }
+}
+
+
+/// Evaluate a committed polynomial in the exponent: g^f(x)
+pub fn evaluate_commitment(commitment: &[G1Projective], x: Scalar) -> G1Projective {
+    commitment.iter().rev().fold(G1Projective::identity(), |acc, c| acc * x + c)
+}
+
+
+/// Evaluation point of node `id`: its position among the sorted node ids, plus one
+pub fn node_point(config: &Config, id: u16) -> Result<Scalar> {
+    Committee::of(config).point(id)
+}
+
+
+/// Nodes holding shares of a polynomial of degree `t`, which need not be the whole cluster
+/// (e.g. the next node set while shares are handed over)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Committee {
+    pub nodes: Vec<u16>,  // sorted
+    pub t: usize,
+}
+
+
+impl Committee {
+    pub fn new(nodes: impl IntoIterator<Item = u16>, t: usize) -> Self {
+        let mut nodes: Vec<u16> = nodes.into_iter().collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        Self { nodes, t }
+    }
+
+    /// All nodes of `config`, tolerating its fault threshold
+    pub fn of(config: &Config) -> Self {
+        Self { nodes: config.node_ids(), t: config.fault_tolerance() }
+    }
+
+    pub fn n(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn contains(&self, id: u16) -> bool {
+        self.nodes.binary_search(&id).is_ok()
+    }
+
+    /// Evaluation point of member `id`: its position among the sorted ids, plus one
+    pub fn point(&self, id: u16) -> Result<Scalar> {
+        let index = self.nodes.binary_search(&id)
+            .map_err(|_| Error::UnknownNode(id))?;
+        Ok(Scalar::from(index as u64 + 1))
+    }
+}
+
+
+/// Lagrange coefficients for evaluating, at `x`, the polynomial through the points `xs`
+pub fn lagrange_coefficients(xs: &[Scalar], x: Scalar) -> Vec<Scalar> {
+    xs.iter().enumerate().map(|(j, xj)| {
+        let mut numerator = Scalar::one();
+        let mut denominator = Scalar::one();
+        for (m, xm) in xs.iter().enumerate() {
+            if m != j {
+                numerator *= x - xm;
+                denominator *= xj - xm;
+            }
+        }
+        numerator * denominator.invert().unwrap_or(Scalar::zero())
+    }).collect()
+}
+
+
+/// Evaluate, at `x`, the polynomial through `points` (with distinct x coordinates)
+pub fn interpolate(points: &[(Scalar, Scalar)], x: Scalar) -> Scalar {
+    let xs: Vec<Scalar> = points.iter().map(|(xi, _)| *xi).collect();
+    lagrange_coefficients(&xs, x).iter().zip(points).map(|(l, (_, y))| l * y).sum()
+}
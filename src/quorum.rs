@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::Config;
+
+
+/// Fault mix of a hybrid cluster: up to `byzantine` arbitrary faults plus up to `crash`
+/// nodes that can only stop (e.g. trusted hardware). Needs n ≥ 3b+2c+1.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HybridFaults {
+    pub byzantine: usize,
+    pub crash: usize,
+}
+
+
+/// Decides which sets of nodes are large enough for each step of reliable broadcast.
+///
+/// Every predicate takes the set of nodes that voted for one value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuorumSystem {
+    /// Equal-weight nodes, at most `t` of them Byzantine
+    Threshold { n: usize, t: usize },
+    /// Nodes with voting power, Byzantine nodes holding less than 1/3 of `total`
+    Weighted { weights: HashMap<u16, u64>, total: u128 },
+    /// The Byzantine nodes are a subset of one of the fail-prone sets (Q³: no three of them cover `nodes`)
+    AdversaryStructure { nodes: HashSet<u16>, fail_prone: Vec<HashSet<u16>> },
+    /// Equal-weight nodes, at most `b` of them Byzantine and `c` crashed
+    Hybrid { n: usize, b: usize, c: usize },
+}
+
+
+impl QuorumSystem {
+    pub fn threshold(n: usize, t: usize) -> Self {
+        Self::Threshold { n, t }
+    }
+
+    pub fn weighted(weights: impl IntoIterator<Item = (u16, u64)>) -> Self {
+        let weights: HashMap<u16, u64> = weights.into_iter().collect();
+        let total = weights.values().map(|&weight| weight as u128).sum();
+        Self::Weighted { weights, total }
+    }
+
+    pub fn adversary_structure(nodes: impl IntoIterator<Item = u16>, fail_prone: impl IntoIterator<Item = HashSet<u16>>) -> Self {
+        Self::AdversaryStructure { nodes: nodes.into_iter().collect(), fail_prone: fail_prone.into_iter().collect() }
+    }
+
+    pub fn hybrid(n: usize, faults: HybridFaults) -> Self {
+        Self::Hybrid { n, b: faults.byzantine, c: faults.crash }
+    }
+
+    /// A hybrid fault mix or fail-prone sets take precedence, then weights;
+    /// unit weights use the (possibly explicit) threshold `t`
+    pub fn from_config(config: &Config) -> Self {
+        if let Some(faults) = config.hybrid {
+            Self::hybrid(config.nodes.len(), faults)
+        } else if !config.fail_prone_sets.is_empty() {
+            Self::adversary_structure(
+                config.nodes.iter().map(|node| node.id),
+                config.fail_prone_sets.iter().map(|set| set.iter().copied().collect()),
+            )
+        } else if config.nodes.iter().all(|node| node.weight == 1) {
+            Self::threshold(config.nodes.len(), config.fault_tolerance())
+        } else {
+            Self::weighted(config.nodes.iter().map(|node| (node.id, node.weight)))
+        }
+    }
+
+    /// Summed in u128, so neither the sum nor three times it can overflow
+    fn weight_of(weights: &HashMap<u16, u64>, voters: &HashSet<u16>) -> u128 {
+        voters.iter().filter_map(|id| weights.get(id)).map(|&weight| weight as u128).sum()
+    }
+
+    /// Every node outside `voters` belongs to one fail-prone set
+    fn covers_all_but_fail_prone(nodes: &HashSet<u16>, fail_prone: &[HashSet<u16>], voters: &HashSet<u16>) -> bool {
+        fail_prone.iter().any(|f| nodes.iter().all(|id| voters.contains(id) || f.contains(id)))
+    }
+
+    /// The known nodes among `voters` fit into no fail-prone set
+    fn escapes_every_fail_prone(nodes: &HashSet<u16>, fail_prone: &[HashSet<u16>], voters: &HashSet<u16>) -> bool {
+        !fail_prone.iter().any(|f| voters.iter().filter(|id| nodes.contains(id)).all(|id| f.contains(id)))
+    }
+
+    /// Enough Echoes to send Ready: n-t, more than 2/3 of the total weight,
+    /// all nodes but a fail-prone set, or n-b-c
+    pub fn is_echo_quorum(&self, voters: &HashSet<u16>) -> bool {
+        match self {
+            Self::Threshold { n, t } => voters.len() >= n - t,
+            Self::Hybrid { n, b, c } => voters.len() >= n - b - c,
+            Self::Weighted { weights, total } => 3 * Self::weight_of(weights, voters) > 2 * total,
+            Self::AdversaryStructure { nodes, fail_prone } => Self::covers_all_but_fail_prone(nodes, fail_prone, voters),
+        }
+    }
+
+    /// Enough Readies to include an honest node, so this node joins in:
+    /// t+1, more than 1/3 of the total weight, a set contained in no fail-prone set, or b+1
+    pub fn is_ready_amplification(&self, voters: &HashSet<u16>) -> bool {
+        match self {
+            Self::Threshold { t, .. } => voters.len() > *t,
+            Self::Hybrid { b, .. } => voters.len() > *b,
+            Self::Weighted { weights, total } => 3 * Self::weight_of(weights, voters) > *total,
+            Self::AdversaryStructure { nodes, fail_prone } => Self::escapes_every_fail_prone(nodes, fail_prone, voters),
+        }
+    }
+
+    /// Enough Readies to deliver: 2t+1, more than 2/3 of the total weight,
+    /// all nodes but a fail-prone set, or 2b+c+1
+    ///
+    /// In the hybrid model b+1 of those Readies come from correct nodes even if b senders are
+    /// Byzantine and c crash right after sending, which is enough for every correct node to join in.
+    pub fn is_delivery_quorum(&self, voters: &HashSet<u16>) -> bool {
+        match self {
+            Self::Threshold { t, .. } => voters.len() > 2 * t,
+            Self::Hybrid { b, c, .. } => voters.len() > 2 * b + c,
+            Self::Weighted { weights, total } => 3 * Self::weight_of(weights, voters) > 2 * total,
+            Self::AdversaryStructure { nodes, fail_prone } => Self::covers_all_but_fail_prone(nodes, fail_prone, voters),
+        }
+    }
+}
+
+
+/// Indices of three fail-prone sets (not necessarily distinct) that together cover every node,
+/// i.e. a witness that the structure is not Q³
+pub fn q3_violation(nodes: &HashSet<u16>, fail_prone: &[HashSet<u16>]) -> Option<(usize, usize, usize)> {
+    let m = fail_prone.len();
+    for i in 0..m {
+        for j in i..m {
+            for k in j..m {
+                let covered = nodes.iter()
+                    .all(|id| fail_prone[i].contains(id) || fail_prone[j].contains(id) || fail_prone[k].contains(id));
+                if covered {
+                    return Some((i, j, k));
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod reconfig;
+
+// re-export all public items from reconfig module
+pub use reconfig::*;
+pub use types::*;
@@ -0,0 +1,128 @@
+use std::{collections::HashSet, sync::Arc};
+
+use tokio::sync::{mpsc, watch};
+
+use crate::{agreement::{Agreement, AgreementMessage, Coin, PROPOSAL_TAG}, node::Node, polynomial::Committee, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{ConfigChange, SignedConfigChange};
+
+
+/// Agreement sessions with this `sender` in their id order configuration changes, numbered by the epoch they end
+pub const SESSION_KIND: u16 = 1;
+
+
+/// Id of the messages of the session ending `epoch`
+pub fn session_id(epoch: u64) -> Identifier {
+    Identifier::new(SESSION_KIND, epoch)
+}
+
+
+async fn send_to_all(messages: Vec<AgreementMessage>, config: &Config, node: &Node) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    for message in messages {
+        let message = Message::new_authenticated(
+            session_id(config.epoch),
+            my_node.id,
+            MessageType::Agreement(message),
+            &my_node.privkey,
+            config,
+        );
+        node.link().send_to_all(&message.to_bytes()).await?;
+    }
+    Ok(())
+}
+
+
+/// A command a member of `config` issued for the next epoch
+fn valid_command(proposal: &[u8], config: &Config) -> Option<SignedConfigChange> {
+    SignedConfigChange::from_bytes(proposal).ok().filter(|command| command.verify(config))
+}
+
+
+/// Members that issued a valid command for `change` among the delivered `proposals`
+fn issuers<'a>(change: &ConfigChange, proposals: impl Iterator<Item = (u16, &'a [u8])>, config: &Config) -> usize {
+    proposals
+        .filter_map(|(_, proposal)| valid_command(proposal, config))
+        .filter(|command| command.change == *change)
+        .map(|command| command.issuer)
+        .collect::<HashSet<u16>>()
+        .len()
+}
+
+
+/// Agree on the configuration that follows the epoch of `config`, among its members.
+///
+/// Members propose the signed command their operator issued through `commands`. A node
+/// accepts a proposed change only if its own operator staged it, or if t+1 members issued it,
+/// so every agreed change was given by the operator of a correct member: a faulty member
+/// cannot move the cluster to a configuration nobody installs. Members without a command
+/// propose a copy of a change issued by t+1 members, so t+1 commands are enough to move the
+/// cluster. The nodes agree on a common subset of proposals and take the one of the lowest
+/// proposer, so every correct member picks the same change even when several were issued
+/// concurrently.
+///
+/// Members holding a share of the cluster's threshold key flip its common coin in the
+/// agreement, others a local coin: either every member should hold a share or none.
+///
+/// Hands the change to the node for installation and sends it through `decision`,
+/// then keeps serving the session for slower nodes until `rx` closes.
+pub async fn run(node: Arc<Node>, config: Arc<Config>, mut rx: mpsc::Receiver<Message>, mut commands: mpsc::Receiver<SignedConfigChange>, decision: watch::Sender<Option<ConfigChange>>) -> Result<()> {
+    let committee = Committee::of(&config);
+    let mut agreement = match node.key().filter(|key| key.verify(&config).is_ok()) {
+        Some(key) => Agreement::with_coin(config.my_id, &committee, Coin::new(key, config.clone(), &[b"reconfig".as_slice(), &config.epoch.to_be_bytes()].concat())),
+        None => Agreement::new(config.my_id, &committee),
+    };
+    let mut proposed = false;
+
+    loop {
+        if decision.borrow().is_none() {
+            let t = config.fault_tolerance();
+            if !proposed {
+                let seen = agreement.values(PROPOSAL_TAG)
+                    .filter_map(|(_, proposal)| valid_command(proposal, &config))
+                    .find(|command| issuers(&command.change, agreement.values(PROPOSAL_TAG), &config) > t);
+                if let Some(command) = seen {
+                    proposed = true;
+                    let messages = agreement.propose(command.to_bytes());
+                    send_to_all(messages, &config, &node).await?;
+                }
+            }
+
+            let endorsed = |command: &SignedConfigChange| {
+                node.is_staged(&command.change) || issuers(&command.change, agreement.values(PROPOSAL_TAG), &config) > t
+            };
+            let accepted: Vec<u16> = agreement.pending_proposals().into_iter()
+                .filter(|(_, proposal)| valid_command(proposal, &config).is_some_and(|command| endorsed(&command)))
+                .map(|(proposer, _)| proposer)
+                .collect();
+            for proposer in accepted {
+                let messages = agreement.accept(proposer);
+                send_to_all(messages, &config, &node).await?;
+            }
+
+            // Included proposals were accepted by a correct node, so they decode
+            let change = agreement.decided()
+                .and_then(|included| agreement.value(*included.first()?, PROPOSAL_TAG).map(<[u8]>::to_vec))
+                .and_then(|proposal| SignedConfigChange::from_bytes(&proposal).ok());
+            if let Some(command) = change {
+                node.agree_epoch(command.change).await;
+                decision.send_replace(Some(command.change));
+            }
+        }
+
+        tokio::select! {
+            message = rx.recv() => {
+                let Some(message) = message else { return Ok(()) };
+                let MessageType::Agreement(agreement_message) = message.payload else { continue };
+                let messages = agreement.handle(message.sender, agreement_message);
+                send_to_all(messages, &config, &node).await?;
+            }
+            Some(command) = commands.recv(), if !proposed => {
+                proposed = true;
+                let messages = agreement.propose(command.to_bytes());
+                send_to_all(messages, &config, &node).await?;
+            }
+        }
+    }
+}
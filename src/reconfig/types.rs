@@ -0,0 +1,92 @@
+use ed25519::signature::Signer;
+use ed25519_dalek::SigningKey;
+
+use crate::{constants::{CLUSTER_ID_SIZE, SIGNATURE_SIZE}, Config, Error, Result};
+
+
+const PROTOCOL: &str = "reconfig";
+
+// Domain separation of configuration change commands from protocol messages
+const RECONFIG_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/reconfig";
+
+pub const CONFIG_CHANGE_SIZE: usize = 8 + CLUSTER_ID_SIZE + 2 + SIGNATURE_SIZE;
+
+
+/// A membership change: from `epoch` on, the cluster is the one of the config with `cluster_id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConfigChange {
+    pub epoch: u64,
+    pub cluster_id: [u8; CLUSTER_ID_SIZE],
+}
+
+
+impl ConfigChange {
+    /// The change installing `config`
+    pub fn of(config: &Config) -> Self {
+        Self { epoch: config.epoch, cluster_id: config.cluster_id() }
+    }
+}
+
+
+/// A configuration change command, signed by the member of the current epoch that issued it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedConfigChange {
+    pub change: ConfigChange,
+    pub issuer: u16,
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+
+impl SignedConfigChange {
+    /// The command to move the cluster of `current` to `change`, bound to the current cluster and epoch
+    fn signing_bytes(change: &ConfigChange, current: &Config) -> Vec<u8> {
+        let mut result = RECONFIG_CONTEXT.to_vec();
+        result.extend_from_slice(&current.domain().to_bytes());
+        result.extend_from_slice(&change.epoch.to_be_bytes());
+        result.extend_from_slice(&change.cluster_id);
+        result
+    }
+
+    /// Issue `change` as this node of `current`
+    pub fn sign(change: ConfigChange, current: &Config) -> Result<Self> {
+        let my_node = current.get_my_node()
+            .ok_or(Error::UnknownNode(current.my_id))?;
+        let signature = SigningKey::from_bytes(&my_node.privkey).sign(&Self::signing_bytes(&change, current));
+        Ok(Self { change, issuer: my_node.id, signature: signature.to_bytes() })
+    }
+
+    /// Whether a member of `current` issued this command, and it moves to the next epoch
+    pub fn verify(&self, current: &Config) -> bool {
+        let Some(issuer) = current.get_node(self.issuer) else { return false };
+        let pubkey = SigningKey::from_bytes(&issuer.privkey).verifying_key();
+        let signature = ed25519_dalek::Signature::from_bytes(&self.signature);
+        self.change.epoch == current.epoch + 1
+            && pubkey.verify_strict(&Self::signing_bytes(&self.change, current), &signature).is_ok()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(CONFIG_CHANGE_SIZE);
+        result.extend_from_slice(&self.change.epoch.to_be_bytes());
+        result.extend_from_slice(&self.change.cluster_id);
+        result.extend_from_slice(&self.issuer.to_be_bytes());
+        result.extend_from_slice(&self.signature);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != CONFIG_CHANGE_SIZE {
+            return Err(Error::decode(PROTOCOL, format!("expected {} bytes", CONFIG_CHANGE_SIZE)));
+        }
+        let (epoch, rest) = bytes.split_at(8);
+        let (cluster_id, rest) = rest.split_at(CLUSTER_ID_SIZE);
+        let (issuer, signature) = rest.split_at(2);
+        Ok(Self {
+            change: ConfigChange {
+                epoch: u64::from_be_bytes(epoch.try_into().unwrap()),
+                cluster_id: cluster_id.try_into().unwrap(),
+            },
+            issuer: u16::from_be_bytes(issuer.try_into().unwrap()),
+            signature: signature.try_into().unwrap(),
+        })
+    }
+}
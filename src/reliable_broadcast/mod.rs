@@ -1,4 +1,5 @@
 pub mod types;
+#[allow(clippy::module_inception)]
 pub mod reliable_broadcast;
 
 // re-export all public items from reliable_broadcast module
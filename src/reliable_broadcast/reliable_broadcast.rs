@@ -1,149 +1,172 @@
-use std::{io, sync::Arc};
+use std::sync::Arc;
 
 use sha2::{Digest, Sha256};
-use tokio::{net::UdpSocket, sync::mpsc};
+use tokio::sync::mpsc;
 
-use crate::{Config, Identifier, Message, MessageType};
+use crate::{transport::ReliableLink, wal::Record, Config, Error, Identifier, Message, MessageType, Result};
 
 use super::types::{Instance, ReliableBroadcastMessage};
 
 
-async fn send_message_to_all(message: Message, config: &Config, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
-    let destinations = config.get_all_addresses();
-    let message_bytes = message.to_bytes();
-    for dest in &destinations {
-        socket.send_to(&message_bytes, dest).await
-            .map_err(|e| io::Error::new(io::ErrorKind::NetworkDown, format!("Failed to send to {}: {}", dest, e)))?;
-    }
-    Ok(())
+async fn send_message_to_all(message: Message, link: &ReliableLink) -> Result<()> {
+    link.send_to_all(&message.to_bytes()).await
 }
 
 
-async fn send_message_to_node(message: Message, target_id: u16, config: &Config, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
-    if let Some(node) = config.get_node(target_id) {
-        let message_bytes = message.to_bytes();
-        socket.send_to(&message_bytes, &node.address).await
-            .map_err(|e| io::Error::new(io::ErrorKind::NetworkDown, format!("Failed to send to {}: {}", node.address, e)))?;
-        Ok(())
-    } else {
-        Err(io::Error::new(io::ErrorKind::NotFound, format!("Node {} not found", target_id)))
+/// Log a vote before it is sent, so that after a restart the node knows it voted
+async fn log_vote(instance: &Instance, message: &Message) -> Result<()> {
+    match &instance.wal {
+        Some(wal) => wal.append(&Record::Vote(message.clone())).await,
+        None => Ok(()),
     }
 }
 
 
-pub async fn broadcast(id: Identifier, message: Vec<u8>, config: Config, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
+async fn send_message_to_node(message: Message, target_id: u16, link: &ReliableLink) -> Result<()> {
+    link.send(target_id, &message.to_bytes()).await
+}
+
+
+/// Start instance `id` as its sender by sending `Send(message)` to every node (including this one).
+/// Only the local node may call this; there is no wire message that starts a broadcast.
+pub async fn broadcast(id: Identifier, message: Vec<u8>, config: &Config, link: &ReliableLink) -> Result<()> {
     let my_node = config.get_my_node()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "My node not found in config"))?;
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
     
-    let message = Message::new(
+    let message = Message::new_authenticated(
         id,
-        id.sender,
-        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Broadcast(message)),
-        &my_node.privkey
+        my_node.id,
+        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Send(message)),
+        &my_node.privkey,
+        config,
     );
     
-    socket.send_to(&message.to_bytes(), &my_node.address).await
-        .map_err(|e| io::Error::new(io::ErrorKind::NetworkDown, format!("Failed to send broadcast: {}", e)))?;
-    
-    Ok(())
+    send_message_to_all(message, link).await
 }
 
 
-pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, config: &Config, socket: Arc<UdpSocket>) -> Result<Vec<u8>, io::Error> {
-    let n = config.nodes.len();
-    let t = calc_t(n);
+/// Run instance `instance.id` until it delivers, and return the payload with the Ready messages that justify it
+pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, config: &Config, link: Arc<ReliableLink>) -> Result<(Vec<u8>, Vec<Message>)> {
+    let quorums = config.quorums();
     let my_node = config.get_my_node()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "My node not found in config"))?;
+        .ok_or(Error::UnknownNode(config.my_id))?;
 
     while let Some(message) = rx.recv().await {
-        let rbc_message = match message.payload {
-            MessageType::ReliableBroadcast(m) => m,
-        };
+        let MessageType::ReliableBroadcast(rbc_message) = message.payload else { continue };
 
         match rbc_message {
-            ReliableBroadcastMessage::Broadcast(m) => {
-                let message = Message::new(
-                    instance.id,
-                    instance.my_id,
-                    MessageType::ReliableBroadcast(ReliableBroadcastMessage::Send(m.clone())),
-                    &my_node.privkey
-                );
-                send_message_to_all(message, config, socket.clone()).await?;
-            }
-
             ReliableBroadcastMessage::Send(m) => {
                 if instance.id.sender == message.sender && instance.message.is_none() {
-                    instance.message = Some(m.clone());
                     let digest: [u8; 32] = Sha256::digest(&m).into();
-                    let message = Message::new(
-                        instance.id,
-                        instance.my_id,
-                        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Echo(digest)),
-                        &my_node.privkey,
-                    );
-                    send_message_to_all(message, config, socket.clone()).await?;
+                    match instance.echo_sent {
+                        None => {
+                            instance.echo_sent = Some(digest);
+                            let message = Message::new_authenticated(
+                                instance.id,
+                                instance.my_id,
+                                MessageType::ReliableBroadcast(ReliableBroadcastMessage::Echo(digest)),
+                                &my_node.privkey,
+                                config,
+                            );
+                            log_vote(&instance, &message).await?;
+                            send_message_to_all(message, &link).await?;
+                        }
+                        // Echoed another payload before restarting
+                        Some(echoed) if echoed != digest => continue,
+                        Some(_) => {}
+                    }
+                    instance.message = Some(m);
                 }
             }
 
             ReliableBroadcastMessage::Echo(d) => {
-                if !instance.echo_messages.insert(message.sender) {  // Not first time
+                if !instance.echoed.insert(message.sender) {  // Not first time
                     continue;
                 }
-                if instance.echo_messages.len() == n-t && instance.ready_messages.len() <= t {
-                    let message = Message::new(
+                let voters = instance.echo_messages.entry(d).or_default();
+                voters.insert(message.sender);
+                if !instance.ready_sent && quorums.is_echo_quorum(voters) {
+                    instance.ready_sent = true;
+                    let message = Message::new_authenticated(
                         instance.id,
                         instance.my_id,
-                        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d.clone())),
+                        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d)),
                         &my_node.privkey,
+                        config,
                     );
-                    send_message_to_all(message, config, socket.clone()).await?;
+                    log_vote(&instance, &message).await?;
+                    send_message_to_all(message, &link).await?;
                 }
             }
 
             ReliableBroadcastMessage::Ready(d) => {
-                if !instance.ready_messages.insert(message.sender) {  // Not first time
+                if !instance.readied.insert(message.sender) {  // Not first time
                     continue;
                 }
-                if instance.ready_messages.len() == t+1 && instance.echo_messages.len() < n-t {
-                    let message = Message::new(
+                instance.readies.push(Message {
+                    domain: message.domain,
+                    id: message.id,
+                    sender: message.sender,
+                    payload: MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d)),
+                    signature: message.signature,
+                });
+                let voters = instance.ready_messages.entry(d).or_default();
+                voters.insert(message.sender);
+                let deliverable = quorums.is_delivery_quorum(voters);
+                if !instance.ready_sent && quorums.is_ready_amplification(voters) {
+                    instance.ready_sent = true;
+                    let message = Message::new_authenticated(
                         instance.id,
                         instance.my_id,
-                        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d.clone())),
+                        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d)),
                         &my_node.privkey,
+                        config,
                     );
-                    send_message_to_all(message, config, socket.clone()).await?;
-                } else if instance.ready_messages.len() == 2*t+1 {
-                    instance.digest = Some(d.clone());
-                    let m_digest: [u8; 32] = Sha256::digest(instance.message.as_ref().unwrap()).into();
-                    if m_digest != instance.digest.unwrap() {
-                        let message = Message::new(
+                    log_vote(&instance, &message).await?;
+                    send_message_to_all(message, &link).await?;
+                }
+                if deliverable {
+                    instance.digest = Some(d);
+                    // The Send may still be in flight (or never come) when the readies complete
+                    let m_digest: Option<[u8; 32]> = instance.message.as_ref().map(|m| Sha256::digest(m).into());
+                    if m_digest != instance.digest {
+                        let message = Message::new_authenticated(
                             instance.id,
                             instance.my_id,
                             MessageType::ReliableBroadcast(ReliableBroadcastMessage::Request),
                             &my_node.privkey,
+                            config,
                         );
                         
-                        // Send to first 2*t+1 nodes for request
-                        let request_targets: Vec<String> = config.nodes.iter()
-                            .take(2*t+1)
-                            .map(|n| n.address.clone())
+                        // Any node that echoed the digest holds the message; with weighted
+                        // quorums there is no fixed prefix of nodes sure to contain one, so ask everyone
+                        let request_targets: Vec<u16> = config.nodes.iter()
+                            .map(|n| n.id)
+                            .filter(|id| *id != instance.my_id)
                             .collect();
                         let message_bytes = message.to_bytes();
-                        for dest in request_targets {
-                            socket.send_to(&message_bytes, &dest).await?;
+                        for target in request_targets {
+                            link.send(target, &message_bytes).await?;
                         }
 
                         // Wait for answers
                         while let Some(message) = rx.recv().await {
-                            let rbc_message = match message.payload {
-                                MessageType::ReliableBroadcast(m) => m,
-                            };
+                            let MessageType::ReliableBroadcast(rbc_message) = message.payload else { continue };
                             match rbc_message {
+                                // A late Send from the sender is as good as an answer
+                                ReliableBroadcastMessage::Send(m) if message.sender == instance.id.sender => {
+                                    let d: [u8; 32] = Sha256::digest(&m).into();
+                                    if instance.digest == Some(d) {
+                                        return Ok((m, instance.proof()));
+                                    }
+                                }
                                 ReliableBroadcastMessage::Answer(m) => {
                                     let d: [u8; 32] = Sha256::digest(&m).into();
                                     if instance.digest == Some(d) {
-                                        instance.message = Some(m);
-                                        return Ok(instance.message.as_ref().unwrap().clone());
+                                        return Ok((m, instance.proof()));
                                     }
                                 }
                                 _ => { continue; }
@@ -151,7 +174,7 @@ pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, co
                         }
 
                     } else {
-                        return Ok(instance.message.as_ref().clone().unwrap().to_vec());
+                        return Ok((instance.message.take().unwrap(), instance.proof()));
                     }
                 }
             }
@@ -159,13 +182,14 @@ pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, co
             ReliableBroadcastMessage::Request => {
                 if let Some(m) = &instance.message {
                     let from = message.sender;
-                    let message = Message::new(
+                    let message = Message::new_authenticated(
                         instance.id,
                         instance.my_id,
                         MessageType::ReliableBroadcast(ReliableBroadcastMessage::Answer(m.clone())),
                         &my_node.privkey,
+                        config,
                     );
-                    send_message_to_node(message, from, config, socket.clone()).await?;
+                    send_message_to_node(message, from, &link).await?;
                 }
             }
             ReliableBroadcastMessage::Answer(_) => {
@@ -173,10 +197,6 @@ pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, co
             }
         }
     }
-    Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Channel closed"))
+    Err(Error::InstanceClosed(instance.id))
 }
 
-
-fn calc_t(n: usize) -> usize {
-    (n-1) / 3
-}
\ No newline at end of file
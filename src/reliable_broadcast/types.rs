@@ -1,16 +1,21 @@
-use std::{collections::HashSet, io};
+use std::{collections::{HashMap, HashSet}, sync::Arc};
 
-use crate::Identifier;
+use crate::{wal::Wal, Error, Identifier, Message, MessageType, Result};
 
 
-#[derive(PartialEq, Eq)]
 pub struct Instance {
     pub id: Identifier,
     pub my_id: u16,
     pub message: Option<Vec<u8>>,
     pub digest: Option<[u8; 32]>,
-    pub echo_messages: HashSet<u16>,
-    pub ready_messages: HashSet<u16>,
+    pub echo_messages: HashMap<[u8; 32], HashSet<u16>>,
+    pub ready_messages: HashMap<[u8; 32], HashSet<u16>>,
+    pub echoed: HashSet<u16>,  // senders of any Echo
+    pub readied: HashSet<u16>,  // senders of any Ready
+    pub readies: Vec<Message>,  // the first Ready of every sender, signed
+    pub echo_sent: Option<[u8; 32]>,  // digest this node echoed
+    pub ready_sent: bool,
+    pub wal: Option<Arc<Wal>>,  // where votes are logged before they are sent
 }
 
 
@@ -19,19 +24,45 @@ impl Instance {
         Self {
             id, my_id, message: None,
             digest: None,
-            echo_messages: HashSet::new(),
-            ready_messages: HashSet::new(),
+            echo_messages: HashMap::new(),
+            ready_messages: HashMap::new(),
+            echoed: HashSet::new(),
+            readied: HashSet::new(),
+            readies: Vec::new(),
+            echo_sent: None,
+            ready_sent: false,
+            wal: None,
         }
     }
+
+    /// Take up the votes this node logged for the instance before it restarted
+    pub fn restore(&mut self, votes: &[Message]) {
+        for vote in votes {
+            match vote.payload {
+                MessageType::ReliableBroadcast(ReliableBroadcastMessage::Echo(d)) => self.echo_sent = Some(d),
+                MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(_)) => self.ready_sent = true,
+                _ => {}
+            }
+        }
+    }
+
+    /// The Ready messages for the delivered digest, which prove the delivery to other nodes
+    pub fn proof(&self) -> Vec<Message> {
+        self.readies.iter()
+            .filter(|ready| matches!(ready.payload, MessageType::ReliableBroadcast(ReliableBroadcastMessage::Ready(d)) if Some(d) == self.digest))
+            .cloned()
+            .collect()
+    }
 }
 
 
 
 // Protocol Identifier
 pub const RBC_IDENTIFIER: u8 = 0;
+const PROTOCOL: &str = "reliable broadcast";
 
 // Message Types
-const MSG_BROADCAST: u8 = 0;
+const MSG_BROADCAST: u8 = 0;  // Reserved: broadcasts are submitted in-process, never over the wire
 const MSG_SEND: u8 = 1;
 const MSG_ECHO: u8 = 2;
 const MSG_READY: u8 = 3;
@@ -42,7 +73,6 @@ const DIGEST_SIZE: usize = 32;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ReliableBroadcastMessage {
-    Broadcast(Vec<u8>),
     Send(Vec<u8>),
     Echo([u8; DIGEST_SIZE]),
     Ready([u8; DIGEST_SIZE]),
@@ -52,15 +82,27 @@ pub enum ReliableBroadcastMessage {
 
 
 impl ReliableBroadcastMessage {
+    /// Ready messages are the evidence that an instance may be delivered,
+    /// so they stay signed even on authenticated channels
+    pub fn requires_signature(&self) -> bool {
+        matches!(self, Self::Ready(_))
+    }
+
+    /// Message types an honest node sends at most once per instance
+    pub fn vote_kind(&self) -> Option<u8> {
+        match self {
+            Self::Send(_) => Some(MSG_SEND),
+            Self::Echo(_) => Some(MSG_ECHO),
+            Self::Ready(_) => Some(MSG_READY),
+            _ => None,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut result = Vec::new();
         result.push(RBC_IDENTIFIER);
         
         match self {
-            Self::Broadcast(msg) => {
-                result.push(MSG_BROADCAST);
-                result.extend_from_slice(msg);
-            }
             Self::Send(msg) => {
                 result.push(MSG_SEND);
                 result.extend_from_slice(msg);
@@ -84,68 +126,38 @@ impl ReliableBroadcastMessage {
         result
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, io::Error> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         if bytes.len() < 2 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                "Invalid message: length < 2"
-            ));
+            return Err(Error::decode(PROTOCOL, "length < 2"));
         }
         
         if bytes[0] != RBC_IDENTIFIER {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                "Invalid message: not a Reliable Broadcast message"
-            ));
+            return Err(Error::decode(PROTOCOL, "not a Reliable Broadcast message"));
         }
 
         match bytes[1] {
-            MSG_BROADCAST => Ok(Self::Broadcast(bytes[2..].to_vec())),
+            MSG_BROADCAST => Err(Error::decode(PROTOCOL, "BROADCAST messages are not accepted from the network")),
             MSG_SEND => Ok(Self::Send(bytes[2..].to_vec())),
             MSG_ECHO => {
-                if bytes.len() != 2 + DIGEST_SIZE {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData, 
-                        format!("Invalid ECHO message: expected {} bytes", 2 + DIGEST_SIZE)
-                    ));
-                }
-                let digest = bytes[2..2 + DIGEST_SIZE]
+                let digest = bytes[2..]
                     .try_into()
-                    .map_err(|_| io::Error::new(
-                        io::ErrorKind::InvalidData, 
-                        "Failed to parse ECHO digest"
-                    ))?;
+                    .map_err(|_| Error::decode(PROTOCOL, format!("ECHO: expected {} bytes", 2 + DIGEST_SIZE)))?;
                 Ok(Self::Echo(digest))
             }
             MSG_READY => {
-                if bytes.len() != 2 + DIGEST_SIZE {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData, 
-                        format!("Invalid READY message: expected {} bytes", 2 + DIGEST_SIZE)
-                    ));
-                }
-                let digest = bytes[2..2 + DIGEST_SIZE]
+                let digest = bytes[2..]
                     .try_into()
-                    .map_err(|_| io::Error::new(
-                        io::ErrorKind::InvalidData, 
-                        "Failed to parse READY digest"
-                    ))?;
+                    .map_err(|_| Error::decode(PROTOCOL, format!("READY: expected {} bytes", 2 + DIGEST_SIZE)))?;
                 Ok(Self::Ready(digest))
             }
             MSG_REQUEST => {
                 if bytes.len() != 2 {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData, 
-                        "Invalid REQUEST message: expected 2 bytes"
-                    ));
+                    return Err(Error::decode(PROTOCOL, "REQUEST: expected 2 bytes"));
                 }
                 Ok(Self::Request)
             }
             MSG_ANSWER => Ok(Self::Answer(bytes[2..].to_vec())),
-            _ => Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                format!("Unknown message type: {}", bytes[1])
-            )),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
         }
     }
 }
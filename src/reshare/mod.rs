@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod reshare;
+
+// re-export all public items from reshare module
+pub use reshare::*;
+pub use types::*;
@@ -0,0 +1,246 @@
+use std::{collections::{BTreeSet, HashMap}, sync::Arc};
+
+use bls12_381::{G1Projective, Scalar};
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+    agreement::{Agreement, AgreementMessage, Coin, PROPOSAL_TAG}, avss::{self, AvssMessage, Sharing}, constants::CHANNEL_BUFFER_SIZE,
+    dkg::KeyShare, node::Node, polynomial::{self, Committee}, Config, Error, Identifier, Message, MessageType, Result,
+};
+
+use super::types::{ReshareMessage, ReshareProgress};
+
+
+/// Id of the dealing of old member `dealer` in resharing session `session`
+pub fn dealing_id(dealer: u16, session: u64) -> Identifier {
+    Identifier::new(dealer, session)
+}
+
+
+fn envelope(message: AvssMessage) -> MessageType {
+    MessageType::Reshare(ReshareMessage::Deal(message))
+}
+
+
+async fn send_to(targets: &[u16], payload: ReshareMessage, session: u64, config: &Config, node: &Node) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    let message = Message::new_authenticated(
+        Identifier::new(my_node.id, session),
+        my_node.id,
+        MessageType::Reshare(payload),
+        &my_node.privkey,
+        config,
+    );
+    let bytes = message.to_bytes();
+    for &target in targets {
+        node.link().send(target, &bytes).await?;
+    }
+    Ok(())
+}
+
+
+async fn send_agreement(messages: Vec<AgreementMessage>, new: &Committee, session: u64, config: &Config, node: &Node) -> Result<()> {
+    for message in messages {
+        send_to(&new.nodes, ReshareMessage::Agree(message), session, config, node).await?;
+    }
+    Ok(())
+}
+
+
+fn encode_dealers(dealers: &[u16]) -> Vec<u8> {
+    dealers.iter().flat_map(|dealer| dealer.to_be_bytes()).collect()
+}
+
+
+fn decode_dealers(bytes: &[u8]) -> Option<Vec<u16>> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(bytes.chunks(2).map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]])).collect())
+}
+
+
+/// Whether old member `dealer` shared its own old share: the dealing's constant term is its public share
+fn is_valid(dealer: u16, sharing: &Sharing, old_commitment: &[G1Projective], old: &Committee, new: &Committee) -> bool {
+    let Ok(x) = old.point(dealer) else { return false };
+    sharing.commitment.len() == new.t + 1
+        && sharing.commitment[0] == polynomial::evaluate_commitment(old_commitment, x)
+}
+
+
+/// Lagrange-combine the dealings of `dealers` into this node's new share and the new commitment
+fn combine(dealers: &[u16], completed: &HashMap<u16, Sharing>, old: &Committee, new: &Committee, my_id: u16) -> Option<KeyShare> {
+    let xs = dealers.iter().map(|dealer| old.point(*dealer)).collect::<Result<Vec<_>>>().ok()?;
+    let coefficients = polynomial::lagrange_coefficients(&xs, Scalar::zero());
+
+    let mut commitment = vec![G1Projective::identity(); new.t + 1];
+    let mut share = Scalar::zero();
+    for (dealer, lambda) in dealers.iter().zip(coefficients) {
+        let sharing = completed.get(dealer)?;
+        for (sum, c) in commitment.iter_mut().zip(&sharing.commitment) {
+            *sum += c * lambda;
+        }
+        share += sharing.share * lambda;
+    }
+    Some(KeyShare { node: my_id, commitment, share })
+}
+
+
+/// Hand the shares of a threshold key from the `old` committee over to the `new` one,
+/// keeping the key (after Desmedt and Jajodia, with AVSS as in Cachin et al.).
+///
+/// Every old member deals its own share to the new committee with AVSS, and tells the new
+/// members the key's commitment, which they take once t+1 old members agree on it. A dealing
+/// is valid if its constant term is the dealer's public share. The new members agree on a common
+/// subset of proposals, each listing t+1 or more valid dealings, and interpolate the agreed
+/// dealings at 0: the results are shares of the old key on a fresh polynomial of the new degree.
+/// Old shares are useless together with new ones, so t faulty nodes before and t after the
+/// handover learn nothing. With `new` = `old` this refreshes the shares. Members leaving the
+/// committee erase their share once they have dealt it.
+///
+/// A refresh flips the common coin of the key being refreshed in its agreement, other handovers
+/// a local coin, as the new members do not hold the key yet.
+///
+/// New members keep serving the session for slower nodes until `rx` closes.
+pub async fn run(node: Arc<Node>, session: u64, old: Committee, new: Committee, mut rx: mpsc::Receiver<Message>, progress: watch::Sender<ReshareProgress>) -> Result<()> {
+    let config = node.config();
+    let my_id = config.my_id;
+    let coin = node.key()
+        .filter(|key| old == new && new == Committee::of(&config) && key.verify(&config).is_ok())
+        .map(|key| Coin::new(key, config.clone(), &[b"reshare".as_slice(), &session.to_be_bytes()].concat()));
+
+    if old.contains(my_id) {
+        let key = node.key()
+            .filter(|key| key.commitment.len() == old.t + 1)
+            .ok_or(Error::NoKey)?;
+        send_to(&new.nodes, ReshareMessage::Commitment(key.commitment.clone()), session, &config, &node).await?;
+        avss::deal(dealing_id(my_id, session), key.share, &new, envelope, &config, &node.link()).await?;
+        progress.send_replace(ReshareProgress::Dealt);
+    }
+    if !new.contains(my_id) {
+        // The dealing is on its way to the new members; a share left behind would only be there to steal
+        node.erase_key();
+        progress.send_replace(ReshareProgress::Done);
+        return Ok(());
+    }
+
+    // One AVSS instance per old member
+    let (completed_tx, mut completed_rx) = mpsc::unbounded_channel::<Sharing>();
+    let mut dealings = HashMap::new();
+    for &dealer in &old.nodes {
+        let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        let mut instance = avss::Instance::new(dealing_id(dealer, session), my_id, new.clone());
+        instance.envelope = envelope;
+        let cloned_node = node.clone();
+        let cloned_completed_tx = completed_tx.clone();
+        tokio::spawn(async move {
+            match avss::receive(instance, rx, &cloned_node.config(), cloned_node.link()).await {
+                Ok(sharing) => {
+                    let _ = cloned_completed_tx.send(sharing);
+                }
+                Err(e) => eprintln!("Error in resharing dealing of node {}: {}", dealer, e),
+            }
+        });
+        dealings.insert(dealer, tx);
+    }
+    drop(completed_tx);
+
+    let mut claimed_commitments: HashMap<u16, Vec<G1Projective>> = HashMap::new();
+    let mut old_commitment: Option<Vec<G1Projective>> = None;
+    let mut completed: HashMap<u16, Sharing> = HashMap::new();
+    let mut valid: BTreeSet<u16> = BTreeSet::new();
+    let mut agreement = match coin {
+        Some(coin) => Agreement::with_coin(my_id, &new, coin),
+        None => Agreement::new(my_id, &new),
+    };
+    let mut proposed = false;
+    let mut finished = false;
+
+    loop {
+        if !finished && old_commitment.is_some() {
+            progress.send_if_modified(|current| {
+                let update = ReshareProgress::Dealings(valid.len());
+                let changed = *current != update && matches!(current, ReshareProgress::Started | ReshareProgress::Dealt | ReshareProgress::Dealings(_));
+                if changed {
+                    *current = update;
+                }
+                changed
+            });
+
+            if !proposed && valid.len() > old.t {
+                proposed = true;
+                let dealers: Vec<u16> = valid.iter().copied().collect();
+                let messages = agreement.propose(encode_dealers(&dealers));
+                send_agreement(messages, &new, session, &config, &node).await?;
+            }
+
+            let accepted: Vec<u16> = agreement.pending_proposals().into_iter()
+                .filter(|(_, proposal)| decode_dealers(proposal).is_some_and(|dealers| {
+                    let distinct: BTreeSet<u16> = dealers.iter().copied().collect();
+                    distinct.len() == dealers.len() && dealers.len() > old.t && dealers.iter().all(|dealer| valid.contains(dealer))
+                }))
+                .map(|(proposer, _)| proposer)
+                .collect();
+            for proposer in accepted {
+                let messages = agreement.accept(proposer);
+                send_agreement(messages, &new, session, &config, &node).await?;
+            }
+
+            let dealers = agreement.decided().and_then(|included| {
+                let mut dealers = BTreeSet::new();
+                for proposer in included {
+                    dealers.extend(decode_dealers(agreement.value(proposer, PROPOSAL_TAG)?)?);
+                }
+                Some(dealers.into_iter().collect::<Vec<u16>>())
+            });
+            // Every agreed dealing was valid at some correct node, so it completes here as well
+            if let Some(dealers) = dealers.filter(|dealers| dealers.iter().all(|dealer| valid.contains(dealer))) {
+                let key = combine(&dealers, &completed, &old, &new, my_id).ok_or(Error::NoKey)?;
+                progress.send_replace(ReshareProgress::Agreed(dealers));
+                node.install_key(key);
+                progress.send_replace(ReshareProgress::Done);
+                finished = true;
+            }
+        }
+
+        tokio::select! {
+            message = rx.recv() => {
+                let Some(message) = message else { return Ok(()) };
+                let MessageType::Reshare(reshare_message) = message.payload else { continue };
+                match reshare_message {
+                    ReshareMessage::Commitment(commitment) => {
+                        if !old.contains(message.sender) || old_commitment.is_some() || commitment.len() != old.t + 1 {
+                            continue;
+                        }
+                        claimed_commitments.entry(message.sender).or_insert(commitment.clone());
+                        // t+1 old members include a correct one
+                        if claimed_commitments.values().filter(|claimed| **claimed == commitment).count() > old.t {
+                            valid = completed.iter()
+                                .filter(|(dealer, sharing)| is_valid(**dealer, sharing, &commitment, &old, &new))
+                                .map(|(dealer, _)| *dealer)
+                                .collect();
+                            old_commitment = Some(commitment);
+                        }
+                    }
+                    ReshareMessage::Deal(avss_message) => {
+                        let Some(tx) = dealings.get(&message.id.sender) else { continue };
+                        let message = Message { payload: MessageType::Avss(avss_message), ..message };
+                        let _ = tx.send(message).await;  // The instance is gone once complete
+                    }
+                    ReshareMessage::Agree(agreement_message) => {
+                        let messages = agreement.handle(message.sender, agreement_message);
+                        send_agreement(messages, &new, session, &config, &node).await?;
+                    }
+                }
+            }
+            Some(sharing) = completed_rx.recv() => {
+                let dealer = sharing.id.sender;
+                if old_commitment.as_ref().is_some_and(|commitment| is_valid(dealer, &sharing, commitment, &old, &new)) {
+                    valid.insert(dealer);
+                }
+                completed.insert(dealer, sharing);
+            }
+        }
+    }
+}
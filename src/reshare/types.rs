@@ -0,0 +1,95 @@
+use bls12_381::{G1Affine, G1Projective};
+use group::Curve;
+
+use crate::{agreement::AgreementMessage, avss::AvssMessage, Error, Result};
+
+
+/// How far a resharing session has come on this node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReshareProgress {
+    Started,
+    /// This node handed its share to the new committee
+    Dealt,
+    /// Valid dealings completed here so far; t+1 of the old committee are needed
+    Dealings(usize),
+    /// The new committee agreed on the dealers whose dealings make up the new shares
+    Agreed(Vec<u16>),
+    /// Finished. New committee members hold their new share, see `Node::key`.
+    Done,
+    Failed(String),
+}
+
+
+// Protocol Identifier
+pub const RESHARE_IDENTIFIER: u8 = 8;
+const PROTOCOL: &str = "reshare";
+
+// Commitments have t+1 points
+const MAX_COMMITMENT_LEN: usize = 128;
+
+// Message Types
+const MSG_COMMITMENT: u8 = 0;
+const MSG_DEAL: u8 = 1;
+const MSG_AGREE: u8 = 2;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReshareMessage {
+    Commitment(Vec<G1Projective>),  // the old committee's commitment, as the sending old member knows it
+    Deal(AvssMessage),  // part of the dealing of old member `id.sender`
+    Agree(AgreementMessage),  // the new committee agreeing on the dealers
+}
+
+
+impl ReshareMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(RESHARE_IDENTIFIER);
+
+        match self {
+            Self::Commitment(commitment) => {
+                result.push(MSG_COMMITMENT);
+                for point in commitment {
+                    result.extend_from_slice(&point.to_affine().to_compressed());
+                }
+            }
+            Self::Deal(message) => {
+                result.push(MSG_DEAL);
+                result.extend_from_slice(&message.to_bytes());
+            }
+            Self::Agree(message) => {
+                result.push(MSG_AGREE);
+                result.extend_from_slice(&message.to_bytes());
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != RESHARE_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a reshare message"));
+        }
+
+        match bytes[1] {
+            MSG_COMMITMENT => {
+                let points = &bytes[2..];
+                if points.is_empty() || !points.len().is_multiple_of(48) || points.len() / 48 > MAX_COMMITMENT_LEN {
+                    return Err(Error::decode(PROTOCOL, format!("COMMITMENT: {} bytes", points.len())));
+                }
+                let commitment = points.chunks(48)
+                    .map(|chunk| Option::<G1Affine>::from(G1Affine::from_compressed(chunk.try_into().unwrap()))
+                        .map(G1Projective::from)
+                        .ok_or_else(|| Error::decode(PROTOCOL, "invalid group element")))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Self::Commitment(commitment))
+            }
+            MSG_DEAL => Ok(Self::Deal(AvssMessage::from_bytes(&bytes[2..])?)),
+            MSG_AGREE => Ok(Self::Agree(AgreementMessage::from_bytes(&bytes[2..])?)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod threshold;
+
+// re-export all public items from threshold module
+pub use threshold::*;
+pub use types::*;
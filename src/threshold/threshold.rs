@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve}, pairing, G1Affine, G1Projective, G2Projective, Scalar,
+};
+use group::Curve;
+
+use crate::{dkg::KeyShare, polynomial, Config, Result};
+
+use super::types::{Signature, SignatureShare};
+
+
+// Domain separation tag of the hash to G2
+const HASH_DST: &[u8] = b"asynchronous_broadcast_protocols/threshold-bls/BLS12381G2_XMD:SHA-256_SSWU_RO_";
+
+
+/// H(message), a point in G2 nobody knows the discrete logarithm of
+pub fn hash_to_point(message: &[u8]) -> G2Projective {
+    <G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, HASH_DST)
+}
+
+
+/// e(g, signature) = e(public_key, H(message))
+fn check(public_key: &G1Projective, message: &[u8], signature: &G2Projective) -> bool {
+    pairing(&G1Affine::generator(), &signature.to_affine()) == pairing(&public_key.to_affine(), &hash_to_point(message).to_affine())
+}
+
+
+/// Sign `message` with this node's key share
+pub fn sign_share(key: &KeyShare, message: &[u8]) -> SignatureShare {
+    SignatureShare { node: key.node, signature: hash_to_point(message) * key.share }
+}
+
+
+/// Check a signature share of `share.node` against its public share, computed from the key's commitment
+pub fn verify_share(key: &KeyShare, share: &SignatureShare, message: &[u8], config: &Config) -> bool {
+    let Ok(public_share) = key.public_share(config, share.node) else { return false };
+    check(&public_share, message, &share.signature)
+}
+
+
+/// Interpolate the signature from t+1 shares of distinct nodes, which must have passed `verify_share`.
+/// Any t+1 valid shares give the same signature. None with fewer shares.
+pub fn combine(shares: &[SignatureShare], threshold: usize, config: &Config) -> Option<Signature> {
+    let mut nodes = HashSet::new();
+    let shares: Vec<&SignatureShare> = shares.iter()
+        .filter(|share| nodes.insert(share.node))
+        .take(threshold + 1)
+        .collect();
+    if shares.len() <= threshold {
+        return None;
+    }
+    let xs = shares.iter()
+        .map(|share| polynomial::node_point(config, share.node))
+        .collect::<Result<Vec<Scalar>>>()
+        .ok()?;
+    let signature = polynomial::lagrange_coefficients(&xs, Scalar::zero()).into_iter()
+        .zip(&shares)
+        .fold(G2Projective::identity(), |sum, (lambda, share)| sum + share.signature * lambda);
+    Some(Signature(signature))
+}
+
+
+/// Check a combined signature on `message` against the public key
+pub fn verify(signature: &Signature, public_key: &G1Projective, message: &[u8]) -> bool {
+    check(public_key, message, &signature.0)
+}
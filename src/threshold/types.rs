@@ -0,0 +1,125 @@
+use std::collections::{HashMap, VecDeque};
+
+use bls12_381::{G2Affine, G2Projective};
+use group::Curve;
+
+use crate::{Error, Identifier, Result};
+
+
+/// A threshold BLS signature: H(message)^secret, in G2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature(pub G2Projective);
+
+
+/// Node `node`'s share of a threshold signature: H(message)^share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureShare {
+    pub node: u16,
+    pub signature: G2Projective,
+}
+
+
+/// The most recent threshold signatures, up to a capacity
+pub struct RecentSignatures {
+    signatures: HashMap<Identifier, Signature>,
+    order: VecDeque<Identifier>,  // oldest first
+    capacity: usize,
+}
+
+
+// Protocol Identifier
+pub const THRESHOLD_IDENTIFIER: u8 = 9;
+const PROTOCOL: &str = "threshold";
+
+pub const SIGNATURE_SIZE: usize = 96;
+
+// Message Types
+const MSG_SHARE: u8 = 0;
+const MSG_SIGNATURE: u8 = 1;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThresholdMessage {
+    Share(G2Projective),  // the sender approves the payload of `id` and sends its signature share
+    Signature(Signature),  // the combined signature, for nodes that approved later
+}
+
+
+impl Signature {
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_SIZE] {
+        self.0.to_affine().to_compressed()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        point_from_bytes(bytes).map(Self)
+    }
+}
+
+
+impl RecentSignatures {
+    pub fn new(capacity: usize) -> Self {
+        Self { signatures: HashMap::new(), order: VecDeque::new(), capacity }
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<Signature> {
+        self.signatures.get(id).copied()
+    }
+
+    /// Keep `signature`, forgetting the oldest one beyond the capacity
+    pub fn insert(&mut self, id: Identifier, signature: Signature) {
+        if self.signatures.insert(id, signature).is_some() {
+            return;
+        }
+        self.order.push_back(id);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.signatures.remove(&oldest);
+            }
+        }
+    }
+}
+
+
+impl ThresholdMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(THRESHOLD_IDENTIFIER);
+
+        match self {
+            Self::Share(share) => {
+                result.push(MSG_SHARE);
+                result.extend_from_slice(&share.to_affine().to_compressed());
+            }
+            Self::Signature(signature) => {
+                result.push(MSG_SIGNATURE);
+                result.extend_from_slice(&signature.to_bytes());
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != THRESHOLD_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a threshold signature message"));
+        }
+
+        match bytes[1] {
+            MSG_SHARE => Ok(Self::Share(point_from_bytes(&bytes[2..])?)),
+            MSG_SIGNATURE => Ok(Self::Signature(Signature::from_bytes(&bytes[2..])?)),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
+
+
+fn point_from_bytes(bytes: &[u8]) -> Result<G2Projective> {
+    let bytes: &[u8; SIGNATURE_SIZE] = bytes.try_into()
+        .map_err(|_| Error::decode(PROTOCOL, format!("expected {} bytes, got {}", SIGNATURE_SIZE, bytes.len())))?;
+    Option::<G2Affine>::from(G2Affine::from_compressed(bytes))
+        .map(G2Projective::from)
+        .ok_or_else(|| Error::decode(PROTOCOL, "invalid group element"))
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, net::SocketAddr, time::{Duration, Instant}};
+
+use sha2::{Digest, Sha256};
+use tokio::net::UdpSocket;
+
+use crate::{constants::*, Error, Result};
+
+
+const PROTOCOL: &str = "fragment";
+
+
+/// A datagram carrying one chunk of a (possibly larger) message.
+///
+/// Wire format: `message_id (8) | index (2) | count (2) | chunk`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub message_id: u64,
+    pub index: u16,
+    pub count: u16,
+    pub chunk: Vec<u8>,
+}
+
+
+impl Fragment {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(FRAGMENT_HEADER_SIZE + self.chunk.len());
+        result.extend_from_slice(&self.message_id.to_be_bytes());
+        result.extend_from_slice(&self.index.to_be_bytes());
+        result.extend_from_slice(&self.count.to_be_bytes());
+        result.extend_from_slice(&self.chunk);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < FRAGMENT_HEADER_SIZE {
+            return Err(Error::decode(PROTOCOL, format!("too short: {} bytes, expected at least {}", bytes.len(), FRAGMENT_HEADER_SIZE)));
+        }
+
+        let message_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+        let index = u16::from_be_bytes(bytes[8..10].try_into().unwrap());
+        let count = u16::from_be_bytes(bytes[10..12].try_into().unwrap());
+        let chunk = bytes[FRAGMENT_HEADER_SIZE..].to_vec();
+
+        if count == 0 || count as usize > MAX_FRAGMENTS {
+            return Err(Error::decode(PROTOCOL, format!("invalid count: {} (max {})", count, MAX_FRAGMENTS)));
+        }
+        if index >= count {
+            return Err(Error::decode(PROTOCOL, format!("invalid index: {} of {}", index, count)));
+        }
+        // Every chunk but the last one is full, so the total size can be bounded up front
+        if (index + 1 < count && chunk.len() != FRAGMENT_PAYLOAD_SIZE) || chunk.len() > FRAGMENT_PAYLOAD_SIZE {
+            return Err(Error::decode(PROTOCOL, format!("invalid size: {} bytes at index {} of {}", chunk.len(), index, count)));
+        }
+
+        Ok(Self { message_id, index, count, chunk })
+    }
+}
+
+
+/// Split an encoded message into fragments that each fit into one datagram.
+pub fn fragment(bytes: &[u8]) -> Result<Vec<Fragment>> {
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::PayloadTooLarge { size: bytes.len(), limit: MAX_MESSAGE_SIZE });
+    }
+
+    // Content-derived id: retransmissions of the same message reassemble into the same slot
+    let digest = Sha256::digest(bytes);
+    let message_id = u64::from_be_bytes(digest[0..8].try_into().unwrap());
+
+    let chunks: Vec<&[u8]> = if bytes.is_empty() {
+        vec![&[]]
+    } else {
+        bytes.chunks(FRAGMENT_PAYLOAD_SIZE).collect()
+    };
+    let count = chunks.len() as u16;
+
+    Ok(chunks.into_iter().enumerate().map(|(index, chunk)| Fragment {
+        message_id,
+        index: index as u16,
+        count,
+        chunk: chunk.to_vec(),
+    }).collect())
+}
+
+
+/// Fragment `bytes` and send every fragment to `dest`.
+pub async fn send_to(socket: &UdpSocket, bytes: &[u8], dest: &str) -> Result<()> {
+    for fragment in fragment(bytes)? {
+        socket.send_to(&fragment.to_bytes(), dest).await?;
+    }
+    Ok(())
+}
+
+
+struct PartialMessage {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    size: usize,
+    started: Instant,
+}
+
+
+/// Collects fragments per (source address, message id) until a message is complete.
+///
+/// Incomplete messages are dropped after `REASSEMBLY_TIMEOUT_MS`, and the oldest ones are
+/// evicted first when more than `MAX_REASSEMBLY_BUFFER_SIZE` bytes are buffered.
+pub struct Reassembler {
+    pending: HashMap<(SocketAddr, u64), PartialMessage>,
+    buffered: usize,
+    timeout: Duration,
+}
+
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::with_timeout(Duration::from_millis(REASSEMBLY_TIMEOUT_MS))
+    }
+
+    /// A reassembler that drops incomplete messages after `timeout` instead of `REASSEMBLY_TIMEOUT_MS`
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            buffered: 0,
+            timeout,
+        }
+    }
+
+    /// Number of bytes currently held by incomplete messages
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Feed one datagram. Returns the full message once its last fragment arrives.
+    pub fn insert(&mut self, from: SocketAddr, datagram: &[u8]) -> Result<Option<Vec<u8>>> {
+        let fragment = Fragment::from_bytes(datagram)?;
+        if fragment.count == 1 {
+            return Ok(Some(fragment.chunk));
+        }
+
+        self.expire(Instant::now());
+
+        let key = (from, fragment.message_id);
+        let partial = self.pending.entry(key).or_insert_with(|| PartialMessage {
+            chunks: vec![None; fragment.count as usize],
+            received: 0,
+            size: 0,
+            started: Instant::now(),
+        });
+        if partial.chunks.len() != fragment.count as usize {
+            return Err(Error::decode(PROTOCOL, format!("count mismatch for message {:x}: {} != {}", fragment.message_id, fragment.count, partial.chunks.len())));
+        }
+
+        let slot = &mut partial.chunks[fragment.index as usize];
+        if slot.is_some() {  // Duplicate
+            return Ok(None);
+        }
+        partial.size += fragment.chunk.len();
+        partial.received += 1;
+        self.buffered += fragment.chunk.len();
+        *slot = Some(fragment.chunk);
+
+        if partial.received == partial.chunks.len() {
+            let partial = self.pending.remove(&key).unwrap();
+            self.buffered -= partial.size;
+            let mut message = Vec::with_capacity(partial.size);
+            for chunk in partial.chunks.into_iter().flatten() {
+                message.extend_from_slice(&chunk);
+            }
+            return Ok(Some(message));
+        }
+
+        self.evict_under_pressure();
+        Ok(None)
+    }
+
+    /// Drop incomplete messages older than the reassembly timeout
+    fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let mut freed = 0;
+        self.pending.retain(|_, partial| {
+            let alive = now.duration_since(partial.started) < timeout;
+            if !alive {
+                freed += partial.size;
+            }
+            alive
+        });
+        self.buffered -= freed;
+    }
+
+    /// Drop the oldest incomplete messages until the buffer is back under its limit
+    fn evict_under_pressure(&mut self) {
+        while self.buffered > MAX_REASSEMBLY_BUFFER_SIZE {
+            let oldest = self.pending.iter()
+                .min_by_key(|(_, partial)| partial.started)
+                .map(|(key, _)| *key);
+            match oldest {
+                Some(key) => {
+                    let partial = self.pending.remove(&key).unwrap();
+                    self.buffered -= partial.size;
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod fragmentation;
+pub mod reliable_link;
+pub mod session;
+
+// re-export all public items from transport modules
+pub use fragmentation::*;
+pub use reliable_link::*;
+pub use session::*;
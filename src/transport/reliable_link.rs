@@ -0,0 +1,560 @@
+use std::{collections::{BTreeSet, HashMap}, io, net::SocketAddr, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, Weak}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use ed25519_dalek::SigningKey;
+use tokio::net::UdpSocket;
+
+use crate::{constants::*, Authentication, Config, Error, Result};
+
+use super::{fragmentation::{self, Reassembler}, session::SessionAuth};
+
+
+// Frame Types
+const FRAME_DATA: u8 = 0;
+const FRAME_ACK: u8 = 1;
+const FRAME_HANDSHAKE: u8 = 2;
+
+const HANDSHAKE_PAYLOAD_SIZE: usize = X25519_KEY_SIZE + SIGNATURE_SIZE;
+
+const PROTOCOL: &str = "link frame";
+
+
+/// Link-layer frame exchanged between two peers.
+///
+/// Wire format: `kind (1) | from (2) | session (8) | seq (8) | payload`
+///
+/// `session` identifies one run of the sending process, so that a restarted peer
+/// starting again from sequence number 0 is not mistaken for a stream of duplicates.
+/// An `Ack` carries the session and sequence number of the `Data` frame it acknowledges.
+/// A `Handshake` (seq is always 0) announces the sender's session key, and every other
+/// frame is followed by a MAC under that key, so that nobody can forge the header of another
+/// node's frame. Only `Authentication::Unauthenticated` sends bare frames.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Data { from: u16, session: u64, seq: u64, payload: Vec<u8> },
+    Ack { from: u16, session: u64, seq: u64 },
+    Handshake { from: u16, session: u64, public: [u8; X25519_KEY_SIZE], signature: [u8; SIGNATURE_SIZE] },
+}
+
+
+impl Frame {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let handshake_payload;
+        let (kind, from, session, seq, payload): (u8, u16, u64, u64, &[u8]) = match self {
+            Self::Data { from, session, seq, payload } => (FRAME_DATA, *from, *session, *seq, payload),
+            Self::Ack { from, session, seq } => (FRAME_ACK, *from, *session, *seq, &[]),
+            Self::Handshake { from, session, public, signature } => {
+                handshake_payload = [public.as_slice(), signature.as_slice()].concat();
+                (FRAME_HANDSHAKE, *from, *session, 0, &handshake_payload)
+            }
+        };
+        let mut result = Vec::with_capacity(LINK_HEADER_SIZE + payload.len());
+        result.push(kind);
+        result.extend_from_slice(&from.to_be_bytes());
+        result.extend_from_slice(&session.to_be_bytes());
+        result.extend_from_slice(&seq.to_be_bytes());
+        result.extend_from_slice(payload);
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < LINK_HEADER_SIZE {
+            return Err(Error::decode(PROTOCOL, format!("too short: {} bytes, expected at least {}", bytes.len(), LINK_HEADER_SIZE)));
+        }
+
+        let from = u16::from_be_bytes(bytes[1..3].try_into().unwrap());
+        let session = u64::from_be_bytes(bytes[3..11].try_into().unwrap());
+        let seq = u64::from_be_bytes(bytes[11..LINK_HEADER_SIZE].try_into().unwrap());
+
+        match bytes[0] {
+            FRAME_DATA => Ok(Self::Data { from, session, seq, payload: bytes[LINK_HEADER_SIZE..].to_vec() }),
+            FRAME_ACK => {
+                if bytes.len() != LINK_HEADER_SIZE {
+                    return Err(Error::decode(PROTOCOL, format!("ACK: expected {} bytes", LINK_HEADER_SIZE)));
+                }
+                Ok(Self::Ack { from, session, seq })
+            }
+            FRAME_HANDSHAKE => {
+                if bytes.len() != LINK_HEADER_SIZE + HANDSHAKE_PAYLOAD_SIZE {
+                    return Err(Error::decode(PROTOCOL, format!("HANDSHAKE: expected {} bytes", LINK_HEADER_SIZE + HANDSHAKE_PAYLOAD_SIZE)));
+                }
+                let payload = &bytes[LINK_HEADER_SIZE..];
+                Ok(Self::Handshake {
+                    from,
+                    session,
+                    public: payload[..X25519_KEY_SIZE].try_into().unwrap(),
+                    signature: payload[X25519_KEY_SIZE..].try_into().unwrap(),
+                })
+            }
+            _ => Err(Error::decode(PROTOCOL, format!("unknown type: {}", bytes[0]))),
+        }
+    }
+}
+
+
+struct PendingFrame {
+    bytes: Vec<u8>,
+    attempts: u32,
+    timeout: Duration,
+    deadline: Instant,
+}
+
+
+/// Sequence numbers already delivered from one peer session
+struct ReceiveWindow {
+    session: u64,
+    next: u64,  // every seq below this has been delivered
+    ahead: BTreeSet<u64>,
+}
+
+
+impl ReceiveWindow {
+    fn new(session: u64) -> Self {
+        Self { session, next: 0, ahead: BTreeSet::new() }
+    }
+
+    /// Record `seq` as delivered. Returns false for duplicates.
+    fn mark(&mut self, seq: u64) -> bool {
+        if seq < self.next || !self.ahead.insert(seq) {
+            return false;
+        }
+        while self.ahead.remove(&self.next) {
+            self.next += 1;
+        }
+        true
+    }
+}
+
+
+struct LinkState {
+    next_seq: HashMap<u16, u64>,
+    unacked: HashMap<(u16, u64), PendingFrame>,
+    windows: HashMap<u16, ReceiveWindow>,
+    departed: HashMap<u16, Instant>,  // nodes a reconfiguration removed, with the time they are forgotten
+    last_handshake: Instant,
+}
+
+
+/// Reliable point-to-point links on top of a single UDP socket.
+///
+/// Every payload gets a per-peer sequence number and is retransmitted with exponential
+/// backoff until the peer acknowledges it. Received payloads are acknowledged and
+/// handed out at most once per peer session.
+///
+/// Unless authentication is off, the links are also authenticated: frames are MAC'd with
+/// pairwise session keys, and the peer id returned by `recv` can be trusted. Only with
+/// `Authentication::SessionMac` do messages rely on that instead of their signatures.
+pub struct ReliableLink {
+    socket: UdpSocket,
+    my_id: u16,
+    authentication: Authentication,
+    session: u64,
+    peers: Mutex<Vec<(u16, String)>>,  // follows reconfigurations, see `update_peers`
+    state: Mutex<LinkState>,
+    reassembler: Mutex<Reassembler>,
+    auth: Option<SessionAuth>,
+    bytes_sent: AtomicU64,
+}
+
+
+impl ReliableLink {
+    /// Bind to this node's address and start the retransmission task
+    pub async fn bind(config: &Config) -> Result<Arc<Self>> {
+        let my_node = config.get_my_node()
+            .ok_or(Error::UnknownNode(config.my_id))?;
+        let socket = bind_socket(&my_node.address).await
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to bind to {}: {}", my_node.address, e)))?;
+
+        let session = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+
+        let auth = match config.authentication {
+            Authentication::Unauthenticated => None,
+            Authentication::Signatures | Authentication::SessionMac => SessionAuth::new(config),
+        };
+
+        let link = Arc::new(Self {
+            socket,
+            my_id: config.my_id,
+            authentication: config.authentication,
+            session,
+            peers: Mutex::new(config.nodes.iter().map(|n| (n.id, n.address.clone())).collect()),
+            state: Mutex::new(LinkState {
+                next_seq: HashMap::new(),
+                unacked: HashMap::new(),
+                windows: HashMap::new(),
+                departed: HashMap::new(),
+                last_handshake: Instant::now(),
+            }),
+            reassembler: Mutex::new(Reassembler::new()),
+            auth,
+            bytes_sent: AtomicU64::new(0),
+        });
+        for id in link.peer_ids() {
+            link.send_handshake(id).await;
+        }
+        tokio::spawn(retransmit_loop(Arc::downgrade(&link)));
+        Ok(link)
+    }
+
+    pub fn my_id(&self) -> u16 {
+        self.my_id
+    }
+
+    /// Whether messages may rely on the link to vouch for their sender instead of a signature
+    pub fn is_authenticated(&self) -> bool {
+        self.auth.is_some() && self.authentication == Authentication::SessionMac
+    }
+
+    /// Frame bytes handed to the socket so far, including acks and retransmissions
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    fn address_of(&self, id: u16) -> Option<String> {
+        self.peers.lock().unwrap().iter().find(|(peer, _)| *peer == id).map(|(_, address)| address.clone())
+    }
+
+    fn peer_ids(&self) -> Vec<u16> {
+        self.peers.lock().unwrap().iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Follow the membership of `config`, e.g. after a reconfiguration: talk to added nodes, and to
+    /// nodes whose address or key changed under their new ones. Removed nodes stay reachable for
+    /// `DEPARTED_PEER_GRACE_MS`, so that instances of earlier epochs can finish, and are then forgotten.
+    pub async fn update_peers(&self, config: &Config) {
+        let mut changed: Vec<u16> = {
+            let mut peers = self.peers.lock().unwrap();
+            let mut changed = Vec::new();
+            for node in &config.nodes {
+                match peers.iter_mut().find(|(id, _)| *id == node.id) {
+                    Some((_, address)) if *address == node.address => {}
+                    Some((_, address)) => {
+                        address.clone_from(&node.address);
+                        changed.push(node.id);
+                    }
+                    None => {
+                        peers.push((node.id, node.address.clone()));
+                        changed.push(node.id);
+                    }
+                }
+            }
+            let removed = peers.iter()
+                .map(|(id, _)| *id)
+                .filter(|id| config.get_node(*id).is_none());
+            let forget_at = Instant::now() + Duration::from_millis(DEPARTED_PEER_GRACE_MS);
+            let mut state = self.state.lock().unwrap();
+            state.departed.retain(|id, _| config.get_node(*id).is_none());
+            for id in removed {
+                state.departed.entry(id).or_insert(forget_at);
+            }
+            changed
+        };
+        if let Some(auth) = &self.auth {
+            for node in &config.nodes {
+                if auth.set_peer(node.id, SigningKey::from_bytes(&node.privkey).verifying_key()) && !changed.contains(&node.id) {
+                    changed.push(node.id);
+                }
+            }
+        }
+        for id in changed {
+            self.send_handshake(id).await;
+        }
+    }
+
+    /// Drop what is kept for removed nodes whose grace period is over
+    fn forget_departed(&self, now: Instant) {
+        let forgotten: Vec<u16> = {
+            let mut state = self.state.lock().unwrap();
+            let forgotten: Vec<u16> = state.departed.iter()
+                .filter(|(_, forget_at)| **forget_at <= now)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in &forgotten {
+                state.departed.remove(id);
+                state.next_seq.remove(id);
+                state.windows.remove(id);
+            }
+            state.unacked.retain(|(target, _), _| !forgotten.contains(target));
+            forgotten
+        };
+        if forgotten.is_empty() {
+            return;
+        }
+        self.peers.lock().unwrap().retain(|(id, _)| !forgotten.contains(id));
+        if let Some(auth) = &self.auth {
+            for id in &forgotten {
+                auth.remove_peer(*id);
+            }
+        }
+    }
+
+    /// Reliably send `payload` to node `target`
+    pub async fn send(&self, target: u16, payload: &[u8]) -> Result<()> {
+        let address = self.address_of(target)
+            .ok_or(Error::UnknownNode(target))?;
+
+        let bytes = {
+            let mut state = self.state.lock().unwrap();
+            let next_seq = state.next_seq.entry(target).or_insert(0);
+            let seq = *next_seq;
+            let bytes = Frame::Data { from: self.my_id, session: self.session, seq, payload: payload.to_vec() }.to_bytes();
+            if bytes.len() > MAX_MESSAGE_SIZE {
+                return Err(Error::PayloadTooLarge { size: bytes.len(), limit: MAX_MESSAGE_SIZE });
+            }
+            *next_seq += 1;
+
+            let timeout = Duration::from_millis(INITIAL_RETRANSMIT_TIMEOUT_MS);
+            state.unacked.insert((target, seq), PendingFrame {
+                bytes: bytes.clone(),
+                attempts: 0,
+                timeout,
+                deadline: Instant::now() + timeout,
+            });
+            bytes
+        };
+
+        // The frame is queued for retransmission, so a failed first attempt is not fatal
+        if let Err(e) = self.transmit(target, &address, &bytes).await {
+            eprintln!("Failed to send frame to node {}: {}", target, e);
+        }
+        Ok(())
+    }
+
+    /// Put one frame on the wire, adding the link MAC if session keys are in use
+    async fn transmit(&self, target: u16, address: &str, bytes: &[u8]) -> Result<()> {
+        match &self.auth {
+            None => {
+                self.bytes_sent.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                fragmentation::send_to(&self.socket, bytes, address).await
+            }
+            Some(auth) => match auth.seal(target, bytes) {
+                Some(sealed) => {
+                    self.bytes_sent.fetch_add(sealed.len() as u64, Ordering::Relaxed);
+                    fragmentation::send_to(&self.socket, &sealed, address).await
+                }
+                None => Ok(()),  // No session key yet, the retransmission after the handshake will carry it
+            },
+        }
+    }
+
+    async fn send_handshake(&self, target: u16) {
+        let (Some(auth), Some(address)) = (&self.auth, self.address_of(target)) else { return };
+        let (public, signature) = auth.handshake(self.my_id, self.session);
+        let frame = Frame::Handshake { from: self.my_id, session: self.session, public, signature };
+        if let Err(e) = fragmentation::send_to(&self.socket, &frame.to_bytes(), &address).await {
+            eprintln!("Failed to send handshake to node {}: {}", target, e);
+        }
+    }
+
+    /// Reliably send `payload` to every node, including this one
+    pub async fn send_to_all(&self, payload: &[u8]) -> Result<()> {
+        for id in self.peer_ids() {
+            self.send(id, payload).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next new payload from any peer
+    pub async fn recv(&self) -> Result<(u16, Vec<u8>)> {
+        let mut buffer: [u8; MESSAGE_BUFFER_SIZE] = [0; MESSAGE_BUFFER_SIZE];
+        loop {
+            let (len, source) = self.socket.recv_from(&mut buffer).await?;
+
+            let result = self.reassembler.lock().unwrap().insert(source, &buffer[..len]);
+            let bytes = match result {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => continue,  // Waiting for more fragments
+                Err(e) => {
+                    eprintln!("Failed to reassemble message from {}: {}", source, e);
+                    continue;
+                }
+            };
+
+            let frame = match self.open(&bytes) {
+                Some(frame) => Frame::from_bytes(frame),
+                None => {
+                    eprintln!("Dropping unauthenticated frame from {}", source);
+                    continue;
+                }
+            };
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    eprintln!("Failed to parse frame from {}: {}", source, e);
+                    continue;
+                }
+            };
+
+            match frame {
+                Frame::Ack { from, session, seq } => {
+                    if session == self.session {
+                        self.state.lock().unwrap().unacked.remove(&(from, seq));
+                    }
+                }
+                Frame::Data { from, session, seq, payload } => {
+                    if !self.source_matches(from, source) {
+                        eprintln!("Frame claiming to be from node {} arrived from {}", from, source);
+                        continue;
+                    }
+                    if let Some(is_new) = self.accept(from, session, seq) {
+                        self.send_ack(from, session, seq).await;
+                        if is_new {
+                            return Ok((from, payload));
+                        }
+                    }
+                }
+                Frame::Handshake { from, session, public, signature } => {
+                    let Some(auth) = &self.auth else { continue };
+                    // Answer new or restarted peers so that they learn our key as well,
+                    // and send what waited for the key without waiting for its retransmission
+                    if auth.accept_handshake(self.my_id, from, session, &public, &signature) {
+                        self.send_handshake(from).await;
+                        self.resend_to(from).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check and strip the link MAC. Handshakes are signed instead and pass through unchanged.
+    fn open<'a>(&self, bytes: &'a [u8]) -> Option<&'a [u8]> {
+        let Some(auth) = &self.auth else { return Some(bytes) };
+        if bytes.first() == Some(&FRAME_HANDSHAKE) {
+            return Some(bytes);
+        }
+        if bytes.len() < LINK_HEADER_SIZE {
+            return None;
+        }
+        let from = u16::from_be_bytes(bytes[1..3].try_into().unwrap());
+        auth.open(from, bytes)
+    }
+
+    /// Without authentication the claimed peer id is only checked against the datagram's
+    /// source address, where the configured address is a concrete socket address.
+    fn source_matches(&self, id: u16, source: SocketAddr) -> bool {
+        if self.auth.is_some() {
+            return true;
+        }
+        match self.address_of(id).and_then(|address| address.parse::<SocketAddr>().ok()) {
+            Some(expected) if !expected.ip().is_unspecified() => expected == source,
+            _ => true,
+        }
+    }
+
+    /// Duplicate suppression. Returns None if the frame must be dropped without an ACK,
+    /// otherwise whether it has not been delivered before.
+    fn accept(&self, from: u16, session: u64, seq: u64) -> Option<bool> {
+        if self.address_of(from).is_none() {
+            eprintln!("Frame from unknown node: {}", from);
+            return None;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let window = state.windows.entry(from).or_insert_with(|| ReceiveWindow::new(session));
+        if session < window.session {  // Stale frame from a previous run of the peer
+            return None;
+        }
+        if session > window.session {  // Peer restarted
+            *window = ReceiveWindow::new(session);
+        }
+        if seq >= window.next + RECEIVE_WINDOW_SIZE {
+            return None;
+        }
+        Some(window.mark(seq))
+    }
+
+    async fn send_ack(&self, target: u16, session: u64, seq: u64) {
+        let Some(address) = self.address_of(target) else { return };
+        let ack = Frame::Ack { from: self.my_id, session, seq };
+        if let Err(e) = self.transmit(target, &address, &ack.to_bytes()).await {
+            eprintln!("Failed to send ACK to node {}: {}", target, e);
+        }
+    }
+
+    /// Resend every unacknowledged frame to `target` now, in the order they were sent
+    async fn resend_to(&self, target: u16) {
+        let Some(address) = self.address_of(target) else { return };
+        let mut pending: Vec<(u64, Vec<u8>)> = self.state.lock().unwrap().unacked.iter()
+            .filter(|((peer, _), _)| *peer == target)
+            .map(|((_, seq), pending)| (*seq, pending.bytes.clone()))
+            .collect();
+        pending.sort_unstable_by_key(|(seq, _)| *seq);
+        for (_, bytes) in pending {
+            if let Err(e) = self.transmit(target, &address, &bytes).await {
+                eprintln!("Failed to resend frame to node {}: {}", target, e);
+            }
+        }
+    }
+
+    /// Resend every frame whose retransmission deadline has passed
+    async fn retransmit_due(&self) {
+        let now = Instant::now();
+        self.forget_departed(now);
+        let mut due = Vec::new();
+        let mut handshake_due = false;
+        {
+            let mut state = self.state.lock().unwrap();
+            if now.duration_since(state.last_handshake) >= Duration::from_millis(HANDSHAKE_RETRY_MS) {
+                state.last_handshake = now;
+                handshake_due = true;
+            }
+            state.unacked.retain(|(target, seq), pending| {
+                if pending.deadline > now {
+                    return true;
+                }
+                pending.attempts += 1;
+                if pending.attempts > MAX_RETRANSMIT_ATTEMPTS {
+                    eprintln!("Giving up on frame {} to node {} after {} attempts", seq, target, MAX_RETRANSMIT_ATTEMPTS);
+                    return false;
+                }
+                pending.timeout = (pending.timeout * 2).min(Duration::from_millis(MAX_RETRANSMIT_TIMEOUT_MS));
+                pending.deadline = now + pending.timeout;
+                due.push((*target, pending.bytes.clone()));
+                true
+            });
+        }
+
+        if let (true, Some(auth)) = (handshake_due, &self.auth) {
+            for id in self.peer_ids() {
+                if !auth.has_key(id) {
+                    self.send_handshake(id).await;
+                }
+            }
+        }
+
+        for (target, bytes) in due {
+            if let Some(address) = self.address_of(target) {
+                if let Err(e) = self.transmit(target, &address, &bytes).await {
+                    eprintln!("Failed to retransmit frame to node {}: {}", target, e);
+                }
+            }
+        }
+    }
+}
+
+
+/// Bind a UDP socket with a receive buffer large enough for a full-sized message.
+/// The kernel may cap the buffer size (`net.core.rmem_max` on Linux).
+async fn bind_socket(address: &str) -> io::Result<UdpSocket> {
+    let address = tokio::net::lookup_host(address).await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::AddrNotAvailable, "no addresses"))?;
+    let socket = socket2::Socket::new(socket2::Domain::for_address(address), socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    socket.set_recv_buffer_size(SOCKET_RECEIVE_BUFFER_SIZE)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+
+async fn retransmit_loop(link: Weak<ReliableLink>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(RETRANSMIT_TICK_MS));
+    loop {
+        interval.tick().await;
+        match link.upgrade() {
+            Some(link) => link.retransmit_due().await,
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use ed25519::signature::Signer;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hmac::{Hmac, Mac};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{constants::*, Config};
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+const HANDSHAKE_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/handshake";
+const SESSION_KEY_CONTEXT: &[u8] = b"asynchronous_broadcast_protocols/session-key";
+
+
+struct SessionKey {
+    session: u64,
+    public: [u8; X25519_KEY_SIZE],
+    key: [u8; MAC_KEY_SIZE],
+}
+
+
+/// Pairwise session keys for MAC-authenticated channels.
+///
+/// Every process run draws a fresh X25519 key and announces it in a handshake signed
+/// with its long-term ed25519 key. Both ends of a link hash the Diffie-Hellman result
+/// into the same HMAC-SHA256 key, which then authenticates every frame on that link.
+pub struct SessionAuth {
+    signing_key: SigningKey,
+    verifying_keys: Mutex<HashMap<u16, VerifyingKey>>,
+    secret: StaticSecret,
+    public: PublicKey,
+    keys: Mutex<HashMap<u16, SessionKey>>,
+}
+
+
+impl SessionAuth {
+    pub fn new(config: &Config) -> Option<Self> {
+        let my_node = config.get_my_node()?;
+        let secret = StaticSecret::random_from_rng(OsRng);
+        Some(Self {
+            signing_key: SigningKey::from_bytes(&my_node.privkey),
+            verifying_keys: Mutex::new(config.nodes.iter()
+                .map(|n| (n.id, SigningKey::from_bytes(&n.privkey).verifying_key()))
+                .collect()),
+            public: PublicKey::from(&secret),
+            secret,
+            keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Accept handshakes from `id` under `verifying_key`. Returns true if the key changed,
+    /// in which case the session key derived under the old one is dropped.
+    pub fn set_peer(&self, id: u16, verifying_key: VerifyingKey) -> bool {
+        if self.verifying_keys.lock().unwrap().insert(id, verifying_key) == Some(verifying_key) {
+            return false;
+        }
+        self.keys.lock().unwrap().remove(&id);
+        true
+    }
+
+    /// Forget `id` and its session key
+    pub fn remove_peer(&self, id: u16) {
+        self.verifying_keys.lock().unwrap().remove(&id);
+        self.keys.lock().unwrap().remove(&id);
+    }
+
+    fn handshake_bytes(from: u16, session: u64, public: &[u8; X25519_KEY_SIZE]) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(HANDSHAKE_CONTEXT);
+        result.extend_from_slice(&from.to_be_bytes());
+        result.extend_from_slice(&session.to_be_bytes());
+        result.extend_from_slice(public);
+        result
+    }
+
+    /// Our ephemeral public key and its signature, to be sent to a peer
+    pub fn handshake(&self, my_id: u16, session: u64) -> ([u8; X25519_KEY_SIZE], [u8; SIGNATURE_SIZE]) {
+        let public = self.public.to_bytes();
+        let signature = self.signing_key.sign(&Self::handshake_bytes(my_id, session, &public));
+        (public, signature.to_bytes())
+    }
+
+    /// Verify a peer's handshake and derive the session key.
+    /// Returns true if the key changed, i.e. the peer is new or has restarted.
+    pub fn accept_handshake(&self, my_id: u16, from: u16, session: u64, public: &[u8; X25519_KEY_SIZE], signature: &[u8; SIGNATURE_SIZE]) -> bool {
+        let Some(verifying_key) = self.verifying_keys.lock().unwrap().get(&from).copied() else {
+            return false;
+        };
+        let signature = ed25519::Signature::from_bytes(signature);
+        if verifying_key.verify_strict(&Self::handshake_bytes(from, session, public), &signature).is_err() {
+            eprintln!("Failed to verify handshake signature from node {}", from);
+            return false;
+        }
+
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(current) = keys.get(&from) {
+            if session < current.session || (session == current.session && current.public == *public) {
+                return false;
+            }
+        }
+
+        let shared = self.secret.diffie_hellman(&PublicKey::from(*public));
+        let mine = self.public.to_bytes();
+        let (low, high) = if my_id <= from { (&mine, public) } else { (public, &mine) };
+        let mut hasher = Sha256::new();
+        hasher.update(SESSION_KEY_CONTEXT);
+        hasher.update(my_id.min(from).to_be_bytes());
+        hasher.update(my_id.max(from).to_be_bytes());
+        hasher.update(shared.as_bytes());
+        hasher.update(low);
+        hasher.update(high);
+        let key: [u8; MAC_KEY_SIZE] = hasher.finalize().into();
+
+        keys.insert(from, SessionKey { session, public: *public, key });
+        true
+    }
+
+    pub fn has_key(&self, peer: u16) -> bool {
+        self.keys.lock().unwrap().contains_key(&peer)
+    }
+
+    fn mac(key: &[u8; MAC_KEY_SIZE], bytes: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(bytes);
+        mac
+    }
+
+    /// Append the MAC for the link to `peer`. None if no session key exists yet.
+    pub fn seal(&self, peer: u16, bytes: &[u8]) -> Option<Vec<u8>> {
+        let keys = self.keys.lock().unwrap();
+        let key = &keys.get(&peer)?.key;
+        let tag = Self::mac(key, bytes).finalize().into_bytes();
+        let mut result = Vec::with_capacity(bytes.len() + MAC_SIZE);
+        result.extend_from_slice(bytes);
+        result.extend_from_slice(&tag);
+        Some(result)
+    }
+
+    /// Check the MAC of a frame received from `peer` and strip it
+    pub fn open<'a>(&self, peer: u16, bytes: &'a [u8]) -> Option<&'a [u8]> {
+        if bytes.len() < MAC_SIZE {
+            return None;
+        }
+        let (frame, tag) = bytes.split_at(bytes.len() - MAC_SIZE);
+        let keys = self.keys.lock().unwrap();
+        let key = &keys.get(&peer)?.key;
+        Self::mac(key, frame).verify_slice(tag).ok()?;
+        Some(frame)
+    }
+}
@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod two_step_broadcast;
+
+// re-export all public items from two_step_broadcast module
+pub use two_step_broadcast::*;
+pub use types::*;
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::{transport::ReliableLink, Config, Error, Identifier, Message, MessageType, Result};
+
+use super::types::{Instance, TwoStepBroadcastMessage};
+
+
+async fn send_message_to_all(message: Message, link: &ReliableLink) -> Result<()> {
+    link.send_to_all(&message.to_bytes()).await
+}
+
+
+/// Start instance `id` as its sender by sending `Init(message)` to every node (including this one)
+pub async fn broadcast(id: Identifier, message: Vec<u8>, config: &Config, link: &ReliableLink) -> Result<()> {
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+    if id.sender != my_node.id {
+        return Err(Error::NotSender(id.sender));
+    }
+
+    let message = Message::new_authenticated(
+        id,
+        my_node.id,
+        MessageType::TwoStepBroadcast(TwoStepBroadcastMessage::Init(message)),
+        &my_node.privkey,
+        config,
+    );
+    send_message_to_all(message, link).await
+}
+
+
+/// Two-step reliable broadcast (Imbs and Raynal) for n ≥ 5t+1.
+///
+/// A node witnesses the first `Init` of the sender, and any message witnessed by n-3t nodes
+/// (at least one of them correct). It delivers a message once n-t nodes witnessed it.
+pub async fn receive(mut instance: Instance, mut rx: mpsc::Receiver<Message>, config: &Config, link: Arc<ReliableLink>) -> Result<Vec<u8>> {
+    let n = config.nodes.len();
+    let t = config.fault_tolerance();
+    let my_node = config.get_my_node()
+        .ok_or(Error::UnknownNode(config.my_id))?;
+
+    while let Some(message) = rx.recv().await {
+        let MessageType::TwoStepBroadcast(two_step_message) = message.payload else { continue };
+
+        let (digest, witness) = match two_step_message {
+            TwoStepBroadcastMessage::Init(m) => {
+                if message.sender != instance.id.sender || !instance.witness_sent.is_empty() {
+                    continue;
+                }
+                (Sha256::digest(&m).into(), m)
+            }
+            TwoStepBroadcastMessage::Witness(m) => {
+                let digest: [u8; 32] = Sha256::digest(&m).into();
+                // Every message a correct node witnesses was first witnessed by some correct node
+                // on its Init, so a correct node witnesses fewer than n messages
+                let count = instance.witness_counts.entry(message.sender).or_insert(0);
+                if *count >= n {
+                    continue;
+                }
+                let (m, voters) = instance.witnesses.entry(digest).or_insert_with(|| (m, Default::default()));
+                if !voters.insert(message.sender) {  // Not first time
+                    continue;
+                }
+                *count += 1;
+                if voters.len() >= n - t {
+                    return Ok(m.clone());
+                }
+                if voters.len() < n - 3 * t {
+                    continue;
+                }
+                (digest, m.clone())
+            }
+        };
+
+        if instance.witness_sent.insert(digest) {
+            let message = Message::new_authenticated(
+                instance.id,
+                instance.my_id,
+                MessageType::TwoStepBroadcast(TwoStepBroadcastMessage::Witness(witness)),
+                &my_node.privkey,
+                config,
+            );
+            send_message_to_all(message, &link).await?;
+        }
+    }
+    Err(Error::InstanceClosed(instance.id))
+}
@@ -0,0 +1,85 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Error, Identifier, Result};
+
+
+pub struct Instance {
+    pub id: Identifier,
+    pub my_id: u16,
+    pub witnesses: HashMap<[u8; 32], (Vec<u8>, HashSet<u16>)>,  // digest -> (message, senders)
+    pub witness_counts: HashMap<u16, usize>,  // sender -> distinct messages witnessed
+    pub witness_sent: HashSet<[u8; 32]>,
+}
+
+
+impl Instance {
+    pub fn new(id: Identifier, my_id: u16) -> Self {
+        Self {
+            id, my_id,
+            witnesses: HashMap::new(),
+            witness_counts: HashMap::new(),
+            witness_sent: HashSet::new(),
+        }
+    }
+}
+
+
+// Protocol Identifier
+pub const TWO_STEP_IDENTIFIER: u8 = 3;
+const PROTOCOL: &str = "two-step broadcast";
+
+// Message Types
+const MSG_INIT: u8 = 0;
+const MSG_WITNESS: u8 = 1;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TwoStepBroadcastMessage {
+    Init(Vec<u8>),
+    Witness(Vec<u8>),
+}
+
+
+impl TwoStepBroadcastMessage {
+    /// Message types an honest node sends at most once per instance.
+    /// An honest node may witness several messages of a faulty sender.
+    pub fn vote_kind(&self) -> Option<u8> {
+        match self {
+            Self::Init(_) => Some(MSG_INIT),
+            Self::Witness(_) => None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.push(TWO_STEP_IDENTIFIER);
+
+        match self {
+            Self::Init(msg) => {
+                result.push(MSG_INIT);
+                result.extend_from_slice(msg);
+            }
+            Self::Witness(msg) => {
+                result.push(MSG_WITNESS);
+                result.extend_from_slice(msg);
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(Error::decode(PROTOCOL, "length < 2"));
+        }
+
+        if bytes[0] != TWO_STEP_IDENTIFIER {
+            return Err(Error::decode(PROTOCOL, "not a Two-step Broadcast message"));
+        }
+
+        match bytes[1] {
+            MSG_INIT => Ok(Self::Init(bytes[2..].to_vec())),
+            MSG_WITNESS => Ok(Self::Witness(bytes[2..].to_vec())),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown message type: {}", bytes[1]))),
+        }
+    }
+}
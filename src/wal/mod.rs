@@ -0,0 +1,7 @@
+pub mod types;
+#[allow(clippy::module_inception)]
+pub mod wal;
+
+// re-export all public items from wal module
+pub use wal::*;
+pub use types::*;
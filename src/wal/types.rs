@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+use crate::{constants::IDENTIFIER_SIZE, merkle::ProvenFragment, Error, Identifier, Message, Result};
+
+
+const PROTOCOL: &str = "write-ahead log record";
+
+// Record types
+const RECORD_BROADCAST: u8 = 0;
+const RECORD_VOTE: u8 = 1;
+const RECORD_DELIVERED: u8 = 2;
+const RECORD_FRAGMENT: u8 = 3;
+const RECORD_TAKEN: u8 = 4;
+
+
+/// Write-ahead log settings
+#[derive(Deserialize, Clone, Debug)]
+pub struct WalConfig {
+    /// Log file, created if missing
+    pub path: String,
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    /// Time between syncs with `FsyncPolicy::Periodic`, in milliseconds
+    #[serde(default = "default_fsync_interval_ms")]
+    pub fsync_interval_ms: u64,
+}
+
+
+fn default_fsync_interval_ms() -> u64 {
+    100
+}
+
+
+/// When appended records are forced to disk. Records reach the operating system before
+/// anything is sent, so every policy survives a crash of the process; only `Always`
+/// also survives a crash of the machine.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Sync every record before sending what it records
+    #[default]
+    Always,
+    /// Sync at most once per `fsync_interval_ms`
+    Periodic,
+    /// Leave it to the operating system
+    Never,
+}
+
+
+/// The counters a node draws its own instance ids from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    Broadcast = 0,
+    Dispersal = 1,
+    Sharing = 2,
+}
+
+
+impl Counter {
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::Broadcast),
+            1 => Ok(Self::Dispersal),
+            2 => Ok(Self::Sharing),
+            _ => Err(Error::decode(PROTOCOL, format!("unknown counter: {}", byte))),
+        }
+    }
+}
+
+
+/// One entry of the write-ahead log
+#[derive(Debug, Clone)]
+pub enum Record {
+    /// This node started broadcasting `payload` as `id`
+    Broadcast { id: Identifier, payload: Vec<u8> },
+    /// A vote this node sent, exactly as signed
+    Vote(Message),
+    /// This node delivered `payload` in instance `id`
+    Delivered { id: Identifier, payload: Vec<u8> },
+    /// This node stored its fragment of dispersed blob `id`
+    Fragment { id: Identifier, fragment: ProvenFragment },
+    /// This node took `id` from `counter`, so it is not handed out again after a restart
+    Taken { counter: Counter, id: Identifier },
+}
+
+
+impl Record {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        match self {
+            Self::Broadcast { id, payload } => {
+                result.push(RECORD_BROADCAST);
+                result.extend_from_slice(&id.to_bytes());
+                result.extend_from_slice(payload);
+            }
+            Self::Vote(message) => {
+                result.push(RECORD_VOTE);
+                result.extend_from_slice(&message.to_bytes());
+            }
+            Self::Delivered { id, payload } => {
+                result.push(RECORD_DELIVERED);
+                result.extend_from_slice(&id.to_bytes());
+                result.extend_from_slice(payload);
+            }
+            Self::Fragment { id, fragment } => {
+                result.push(RECORD_FRAGMENT);
+                result.extend_from_slice(&id.to_bytes());
+                fragment.to_bytes(&mut result);
+            }
+            Self::Taken { counter, id } => {
+                result.push(RECORD_TAKEN);
+                result.push(*counter as u8);
+                result.extend_from_slice(&id.to_bytes());
+            }
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some((&kind, rest)) = bytes.split_first() else {
+            return Err(Error::decode(PROTOCOL, "empty record"));
+        };
+        let id_and_payload = |rest: &[u8]| -> Result<(Identifier, Vec<u8>)> {
+            if rest.len() < IDENTIFIER_SIZE {
+                return Err(Error::decode(PROTOCOL, "missing identifier"));
+            }
+            let (id, payload) = rest.split_at(IDENTIFIER_SIZE);
+            Ok((Identifier::from_bytes(id.try_into().unwrap()), payload.to_vec()))
+        };
+        match kind {
+            RECORD_BROADCAST => {
+                let (id, payload) = id_and_payload(rest)?;
+                Ok(Self::Broadcast { id, payload })
+            }
+            RECORD_VOTE => Ok(Self::Vote(Message::from_bytes(rest)?)),
+            RECORD_DELIVERED => {
+                let (id, payload) = id_and_payload(rest)?;
+                Ok(Self::Delivered { id, payload })
+            }
+            RECORD_FRAGMENT => {
+                let (id, fragment) = id_and_payload(rest)?;
+                Ok(Self::Fragment { id, fragment: ProvenFragment::from_bytes(&fragment)? })
+            }
+            RECORD_TAKEN => {
+                let Some((&counter, rest)) = rest.split_first() else {
+                    return Err(Error::decode(PROTOCOL, "missing counter"));
+                };
+                let (id, rest) = id_and_payload(rest)?;
+                if !rest.is_empty() {
+                    return Err(Error::decode(PROTOCOL, "trailing bytes after identifier"));
+                }
+                Ok(Self::Taken { counter: Counter::from_byte(counter)?, id })
+            }
+            _ => Err(Error::decode(PROTOCOL, format!("unknown record type: {}", kind))),
+        }
+    }
+}
+
+
+/// The state of the previous run, as far as the log knows it
+#[derive(Debug, Default)]
+pub struct Recovery {
+    pub broadcasts: BTreeMap<Identifier, Vec<u8>>,  // own broadcasts
+    pub votes: HashMap<Identifier, Vec<Message>>,  // sent votes by instance
+    pub delivered: BTreeMap<Identifier, Vec<u8>>,
+    pub fragments: Vec<(Identifier, ProvenFragment)>,  // stored for clients, in the order they came
+    pub taken: HashMap<Counter, Identifier>,  // the last id taken from each counter
+}
+
+
+impl Recovery {
+    pub fn new(records: Vec<Record>) -> Self {
+        let mut recovery = Self::default();
+        for record in records {
+            match record {
+                Record::Broadcast { id, payload } => {
+                    recovery.take(Counter::Broadcast, id);
+                    recovery.broadcasts.insert(id, payload);
+                }
+                Record::Vote(message) => recovery.votes.entry(message.id).or_default().push(message),
+                Record::Delivered { id, payload } => {
+                    recovery.delivered.insert(id, payload);
+                }
+                Record::Fragment { id, fragment } => recovery.fragments.push((id, fragment)),
+                Record::Taken { counter, id } => recovery.take(counter, id),
+            }
+        }
+        recovery
+    }
+
+    fn take(&mut self, counter: Counter, id: Identifier) {
+        let last = self.taken.entry(counter).or_insert(id);
+        *last = (*last).max(id);
+    }
+
+    /// The first sequence number of `counter` that was not taken yet
+    pub fn next(&self, counter: Counter) -> u64 {
+        self.taken.get(&counter).map_or(0, |id| id.sequence + 1)
+    }
+
+    /// What is left of the log once finished instances are dropped: the votes and broadcasts of
+    /// delivered instances are not needed again, nor are the payloads they delivered. Stored
+    /// fragments and the last id of every counter are kept.
+    pub fn compacted(&self) -> Vec<Record> {
+        let mut records: Vec<Record> = self.taken.iter()
+            .map(|(counter, id)| Record::Taken { counter: *counter, id: *id })
+            .collect();
+        records.extend(self.broadcasts.iter()
+            .filter(|(id, _)| !self.delivered.contains_key(id))
+            .map(|(id, payload)| Record::Broadcast { id: *id, payload: payload.clone() }));
+        records.extend(self.votes.iter()
+            .filter(|(id, _)| !self.delivered.contains_key(id))
+            .flat_map(|(_, votes)| votes.iter().cloned().map(Record::Vote)));
+        records.extend(self.delivered.keys().map(|id| Record::Delivered { id: *id, payload: Vec::new() }));
+        records.extend(self.fragments.iter().map(|(id, fragment)| Record::Fragment { id: *id, fragment: fragment.clone() }));
+        records
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+use super::types::{FsyncPolicy, Record, WalConfig};
+
+
+// Every record is framed as length (4 bytes) || checksum (first 8 bytes of its SHA-256) || record
+const FRAME_HEADER_SIZE: usize = 4 + 8;
+
+
+fn checksum(bytes: &[u8]) -> [u8; 8] {
+    Sha256::digest(bytes)[..8].try_into().unwrap()
+}
+
+
+fn frame(record: &Record) -> Vec<u8> {
+    let body = record.to_bytes();
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&checksum(&body));
+    frame.extend_from_slice(&body);
+    frame
+}
+
+
+/// What the log holds at the current position
+enum Frame {
+    Record(Record, usize),
+    /// The last frame, cut short or failing its checksum: an append interrupted by a crash
+    Torn,
+    /// A frame that cannot be read back although the log goes on, or whose record does not decode
+    Corrupt(String),
+    End,
+}
+
+
+fn read_frame(reader: &mut impl BufRead) -> std::io::Result<Frame> {
+    let mut header = Vec::with_capacity(FRAME_HEADER_SIZE);
+    reader.take(FRAME_HEADER_SIZE as u64).read_to_end(&mut header)?;
+    if header.is_empty() {
+        return Ok(Frame::End);
+    }
+    if header.len() < FRAME_HEADER_SIZE {
+        return Ok(Frame::Torn);
+    }
+    let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let mut body = Vec::new();
+    reader.take(len as u64).read_to_end(&mut body)?;
+    if body.len() < len {
+        return Ok(Frame::Torn);
+    }
+    if checksum(&body) != header[4..] {
+        if reader.fill_buf()?.is_empty() {
+            return Ok(Frame::Torn);
+        }
+        return Ok(Frame::Corrupt("checksum mismatch".to_string()));
+    }
+    // A checksummed record was written whole; if it does not decode, it must not be dropped
+    // with everything after it, or the votes among them would be forgotten
+    Ok(match Record::from_bytes(&body) {
+        Ok(record) => Frame::Record(record, FRAME_HEADER_SIZE + len),
+        Err(e) => Frame::Corrupt(e.to_string()),
+    })
+}
+
+
+/// Append-only log of what a node sent and delivered, written before it acts on it,
+/// so that a restarted node neither takes back a vote nor delivers twice.
+pub struct Wal {
+    path: String,
+    policy: FsyncPolicy,
+    interval: Duration,
+    file: Mutex<Arc<File>>,
+    last_sync: Mutex<Instant>,
+}
+
+
+impl Wal {
+    /// Open the log at `config.path` and read back its records. A record torn by a crash
+    /// in the middle of an append is cut off; everything before it is kept. Any other record
+    /// that cannot be read back fails the open rather than being dropped.
+    pub fn open(config: &WalConfig) -> Result<(Self, Vec<Record>)> {
+        let error = |reason: String| Error::Wal { path: config.path.clone(), reason };
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(|e| error(e.to_string()))?;
+
+        let mut reader = BufReader::new(&file);
+        let mut records = Vec::new();
+        let mut offset = 0;
+        loop {
+            match read_frame(&mut reader).map_err(|e| error(e.to_string()))? {
+                Frame::Record(record, len) => {
+                    records.push(record);
+                    offset += len as u64;
+                }
+                Frame::Corrupt(reason) => return Err(error(format!("record at offset {}: {}", offset, reason))),
+                Frame::Torn | Frame::End => break,
+            }
+        }
+        let size = file.metadata().map_err(|e| error(e.to_string()))?.len();
+        if offset < size {
+            eprintln!("Cutting {} bytes of torn records off {}", size - offset, config.path);
+            file.set_len(offset).map_err(|e| error(e.to_string()))?;
+            file.sync_all().map_err(|e| error(e.to_string()))?;
+        }
+
+        let wal = Self {
+            path: config.path.clone(),
+            policy: config.fsync,
+            interval: Duration::from_millis(config.fsync_interval_ms),
+            file: Mutex::new(Arc::new(file)),
+            last_sync: Mutex::new(Instant::now()),
+        };
+        Ok((wal, records))
+    }
+
+    /// Replace the log with `records`, e.g. those of `Recovery::compacted`. The new log is
+    /// written next to the old one and renamed over it, so a crash leaves one or the other.
+    pub fn compact(&self, records: &[Record]) -> Result<()> {
+        let tmp = format!("{}.tmp", self.path);
+        let mut file = File::create(&tmp).map_err(|e| self.error(e))?;
+        for record in records {
+            file.write_all(&frame(record)).map_err(|e| self.error(e))?;
+        }
+        file.sync_all().map_err(|e| self.error(e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| self.error(e))?;
+        if let Some(dir) = Path::new(&self.path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir).and_then(|dir| dir.sync_all()).map_err(|e| self.error(e))?;
+        }
+        let file = OpenOptions::new().append(true).open(&self.path).map_err(|e| self.error(e))?;
+        *self.file.lock().unwrap() = Arc::new(file);
+        Ok(())
+    }
+
+    /// Hand `record` to the operating system. It survives a crash of the process from here on,
+    /// and one of the machine after the next `sync`.
+    pub fn write(&self, record: &Record) -> Result<()> {
+        let file = self.file.lock().unwrap();
+        (&**file).write_all(&frame(record)).map_err(|e| self.error(e))
+    }
+
+    /// Sync what was written as the policy demands. The sync runs on a blocking thread,
+    /// so that it does not hold up the tasks receiving messages.
+    pub async fn sync(&self) -> Result<()> {
+        let due = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Periodic => {
+                let mut last_sync = self.last_sync.lock().unwrap();
+                let due = last_sync.elapsed() >= self.interval;
+                if due {
+                    *last_sync = Instant::now();
+                }
+                due
+            }
+            FsyncPolicy::Never => false,
+        };
+        if !due {
+            return Ok(());
+        }
+        let file = self.file.lock().unwrap().clone();
+        tokio::task::spawn_blocking(move || file.sync_data()).await
+            .map_err(|e| Error::Wal { path: self.path.clone(), reason: e.to_string() })?
+            .map_err(|e| self.error(e))
+    }
+
+    /// Append `record`, synced as the policy demands. Returns once it is safe to act on it.
+    pub async fn append(&self, record: &Record) -> Result<()> {
+        self.write(record)?;
+        self.sync().await
+    }
+
+    fn error(&self, e: std::io::Error) -> Error {
+        Error::Wal { path: self.path.clone(), reason: e.to_string() }
+    }
+}
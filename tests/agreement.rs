@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use asynchronous_broadcast_protocols::{agreement::{Agreement, AgreementMessage, Coin}, dkg::KeyShare, polynomial::Committee, Config};
+use bls12_381::Scalar;
+use ff::Field;
+use rand_core::{OsRng, RngCore};
+
+
+fn committee(n: u16) -> Committee {
+    Committee::new(0..n, (n as usize - 1) / 3)
+}
+
+
+/// Sessions of `n` nodes flipping local coins
+fn local(n: u16) -> Vec<Agreement> {
+    (0..n).map(|id| Agreement::new(id, &committee(n))).collect()
+}
+
+
+/// Sessions of `n` nodes flipping the common coin of a freshly dealt key
+fn common(n: u16) -> Vec<Agreement> {
+    let nodes: Vec<serde_json::Value> = (0..n)
+        .map(|id| serde_json::json!({
+            "id": id,
+            "address": format!("127.0.0.1:{}", 39300 + id),
+            "privkey": vec![id as u8 + 1; 32],
+        }))
+        .collect();
+    let config: Config = serde_json::from_value(serde_json::json!({ "my_id": 0, "nodes": nodes })).unwrap();
+    let keys = KeyShare::deal(&config, Scalar::random(OsRng));
+    let config = Arc::new(config);
+    keys.into_iter().zip(0..n)
+        .map(|(key, id)| Agreement::with_coin(id, &committee(n), Coin::new(key, config.clone(), b"test")))
+        .collect()
+}
+
+
+/// Run common subset agreement in memory, delivering messages in random order.
+/// Nodes in `silent` never send anything. Returns every other node's decision.
+fn run(mut sessions: Vec<Agreement>, silent: &[u16]) -> Vec<Vec<u16>> {
+    let n = sessions.len() as u16;
+    let live: Vec<u16> = (0..n).filter(|id| !silent.contains(id)).collect();
+    let mut in_flight: Vec<(u16, u16, AgreementMessage)> = Vec::new();
+    let send = |in_flight: &mut Vec<(u16, u16, AgreementMessage)>, from: u16, messages: Vec<AgreementMessage>| {
+        for message in messages {
+            in_flight.extend((0..n).map(|to| (from, to, message.clone())));
+        }
+    };
+
+    for &id in &live {
+        let messages = sessions[id as usize].propose(vec![id as u8]);
+        send(&mut in_flight, id, messages);
+    }
+    for _ in 0..1_000_000 {
+        if live.iter().all(|&id| sessions[id as usize].decided().is_some()) {
+            return live.iter().map(|&id| sessions[id as usize].decided().unwrap()).collect();
+        }
+        assert!(!in_flight.is_empty(), "agreement stalled");
+        let (from, to, message) = in_flight.swap_remove(OsRng.next_u32() as usize % in_flight.len());
+        if silent.contains(&to) {
+            continue;
+        }
+        let session = &mut sessions[to as usize];
+        let mut messages = session.handle(from, message);
+        let pending: Vec<u16> = session.pending_proposals().into_iter().map(|(proposer, _)| proposer).collect();
+        for proposer in pending {
+            messages.extend(session.accept(proposer));
+        }
+        send(&mut in_flight, to, messages);
+    }
+    panic!("agreement did not terminate");
+}
+
+
+#[test]
+fn nodes_decide_the_same_subset() {
+    for _ in 0..10 {
+        let decisions = run(local(4), &[]);
+        assert!(decisions.iter().all(|decision| *decision == decisions[0]));
+        assert!(decisions[0].len() >= 3);
+    }
+}
+
+
+#[test]
+fn silent_node_cannot_block_agreement() {
+    for _ in 0..10 {
+        let decisions = run(local(7), &[5, 6]);
+        assert!(decisions.iter().all(|decision| *decision == decisions[0]));
+        assert!(decisions[0].len() >= 5);
+        assert!(!decisions[0].contains(&5) && !decisions[0].contains(&6));
+    }
+}
+
+
+#[test]
+fn common_coin_agrees_and_tolerates_silent_nodes() {
+    for _ in 0..3 {
+        let decisions = run(common(4), &[]);
+        assert!(decisions.iter().all(|decision| *decision == decisions[0]));
+        assert!(decisions[0].len() >= 3);
+
+        let decisions = run(common(7), &[5, 6]);
+        assert!(decisions.iter().all(|decision| *decision == decisions[0]));
+        assert!(decisions[0].len() >= 5);
+        assert!(!decisions[0].contains(&5) && !decisions[0].contains(&6));
+    }
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    avid::{code_parameters, AvidMessage, Commitment, FragmentStore}, erasure, merkle::{MerkleTree, ProvenFragment}, node::Node,
+    transport::ReliableLink, Error, Identifier, Message, MessageType,
+};
+use tokio::sync::mpsc;
+
+use common::{config, start_all};
+
+
+async fn start(n: u16, ids: std::ops::Range<u16>, base_port: u16) -> Vec<Arc<Node>> {
+    start_all(ids.map(|id| config(n, id, base_port))).await
+}
+
+
+#[tokio::test]
+async fn disperse_and_retrieve_from_another_node() {
+    let nodes = start(4, 0..4, 40100).await;
+    let blob: Vec<u8> = (0..50_000u32).map(|b| (b % 253) as u8).collect();
+
+    let commitment = tokio::time::timeout(Duration::from_secs(10), nodes[0].disperse(&blob)).await.unwrap().unwrap();
+    assert!(commitment.certificate.len() >= 3);
+    commitment.verify(&nodes[3].config()).unwrap();
+
+    // Commitments survive serialization, e.g. when handed to another client
+    let commitment = Commitment::from_bytes(&commitment.to_bytes()).unwrap();
+    let retrieved = tokio::time::timeout(Duration::from_secs(10), nodes[2].retrieve(&commitment)).await.unwrap().unwrap();
+    assert_eq!(retrieved, blob);
+}
+
+
+#[tokio::test]
+async fn works_with_t_nodes_down() {
+    // n = 4, t = 1: node 3 never starts, the other three are exactly the 2t+1 acknowledgements needed
+    let nodes = start(4, 0..3, 40110).await;
+    let blob = b"dispersed while node 3 is down".to_vec();
+
+    let commitment = tokio::time::timeout(Duration::from_secs(10), nodes[1].disperse(&blob)).await.unwrap().unwrap();
+    let retrieved = tokio::time::timeout(Duration::from_secs(10), nodes[0].retrieve(&commitment)).await.unwrap().unwrap();
+    assert_eq!(retrieved, blob);
+}
+
+
+#[tokio::test]
+async fn broadcasts_go_on_after_a_dispersal() {
+    // Dispersals must not take sequence numbers that receivers then wait on forever
+    let nodes = start_all((0..4).map(|id| {
+        let mut config = config(4, id, 40130);
+        config.limits.sequence_window = 8;
+        config
+    })).await;
+    let mut rx = nodes[1].subscribe_deliveries();
+
+    tokio::time::timeout(Duration::from_secs(10), nodes[0].disperse(b"blob")).await.unwrap().unwrap();
+    for i in 0..20u8 {
+        let id = nodes[0].broadcast(vec![i]).await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+            .expect("payload was not delivered")
+            .unwrap();
+        assert_eq!((delivery.id, delivery.payload), (id, vec![i]));
+    }
+}
+
+
+#[tokio::test]
+async fn forged_certificates_are_rejected() {
+    let nodes = start(4, 0..4, 40120).await;
+    let commitment = tokio::time::timeout(Duration::from_secs(10), nodes[0].disperse(b"blob")).await.unwrap().unwrap();
+
+    let mut too_few = commitment.clone();
+    too_few.certificate.truncate(2);
+    assert!(matches!(too_few.verify(&nodes[1].config()), Err(Error::InvalidCertificate(_))));
+    assert!(matches!(nodes[1].retrieve(&too_few).await, Err(Error::InvalidCertificate(_))));
+
+    let mut duplicated = commitment.clone();
+    duplicated.certificate[1] = duplicated.certificate[0].clone();
+    assert!(matches!(duplicated.verify(&nodes[1].config()), Err(Error::InvalidCertificate(_))));
+
+    let mut other_root = commitment.clone();
+    other_root.root[0] ^= 1;
+    assert!(matches!(other_root.verify(&nodes[1].config()), Err(Error::InvalidCertificate(_))));
+}
+
+
+/// Messages a scripted client receives, read in the background so that its link keeps answering handshakes
+fn received(link: Arc<ReliableLink>) -> mpsc::UnboundedReceiver<Message> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((_, bytes)) = link.recv().await {
+            if let Ok(message) = Message::from_bytes(&bytes) {
+                let _ = tx.send(message);
+            }
+        }
+    });
+    rx
+}
+
+
+#[tokio::test]
+async fn nodes_that_missed_their_fragment_recover_it() {
+    // n = 7, t = 2: client 6 stores fragments at nodes 0 to 4 only, which is 2t+1, and none at node 5
+    let base_port = 40140;
+    let _nodes = start(7, 0..6, base_port).await;
+    let config = config(7, 6, base_port);
+    let client = ReliableLink::bind(&config).await.unwrap();
+    let mut rx = received(client.clone());
+    let id = Identifier::new(6, 0);
+    let message = |payload: AvidMessage| {
+        Message::new_authenticated(id, 6, MessageType::Avid(payload), &config.get_my_node().unwrap().privkey, &config).to_bytes()
+    };
+
+    let blob: Vec<u8> = (0..10_000u32).map(|b| (b % 251) as u8).collect();
+    let (k, n) = code_parameters(&config);
+    let fragments = erasure::encode(&blob, k, n).unwrap();
+    let tree = MerkleTree::new(&fragments);
+    let root = tree.root();
+    for (index, data) in fragments.iter().enumerate().take(5) {
+        let fragment = ProvenFragment { root, proof: tree.proof(index), data: data.clone() };
+        client.send(index as u16, &message(AvidMessage::Store(fragment))).await.unwrap();
+    }
+    let mut certificate = Vec::new();
+    while certificate.len() < 5 {
+        let ack = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("not acknowledged").unwrap();
+        if matches!(ack.payload, MessageType::Avid(AvidMessage::Stored(_))) {
+            certificate.push(ack);
+        }
+    }
+    let commitment = Commitment { id, root, certificate };
+    client.send(5, &message(AvidMessage::Commit(commitment))).await.unwrap();
+
+    // Node 5 rebuilds its fragment from the others' and hands it out like the rest
+    let expected = ProvenFragment { root, proof: tree.proof(5), data: fragments[5].clone() };
+    let answer = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            client.send(5, &message(AvidMessage::Request)).await.unwrap();
+            let wait = tokio::time::sleep(Duration::from_millis(200));
+            tokio::pin!(wait);
+            loop {
+                tokio::select! {
+                    _ = &mut wait => break,
+                    Some(message) = rx.recv() => {
+                        if let MessageType::Avid(AvidMessage::Answer(fragment)) = message.payload {
+                            return fragment;
+                        }
+                    }
+                }
+            }
+        }
+    }).await.expect("fragment was not recovered");
+    assert_eq!(answer, expected);
+}
+
+
+#[tokio::test]
+async fn repeated_stores_are_acknowledged_again() {
+    // A client whose acknowledgement was lost sends its fragment again, and needs another one
+    let base_port = 40150;
+    let _nodes = start(4, 0..3, base_port).await;
+    let config = config(4, 3, base_port);
+    let client = ReliableLink::bind(&config).await.unwrap();
+    let mut rx = received(client.clone());
+    let id = Identifier::new(3, 0);
+    let (k, n) = code_parameters(&config);
+    let fragments = erasure::encode(b"stored twice", k, n).unwrap();
+    let tree = MerkleTree::new(&fragments);
+    let fragment = ProvenFragment { root: tree.root(), proof: tree.proof(0), data: fragments[0].clone() };
+    let store = Message::new_authenticated(
+        id, 3, MessageType::Avid(AvidMessage::Store(fragment)), &config.get_my_node().unwrap().privkey, &config,
+    ).to_bytes();
+
+    for _ in 0..2 {
+        client.send(0, &store).await.unwrap();
+        let ack = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("not acknowledged").unwrap();
+        assert!(matches!(ack.payload, MessageType::Avid(AvidMessage::Stored(root)) if root == tree.root()));
+    }
+}
+
+
+#[test]
+fn fragments_are_stored_within_a_quota_per_client() {
+    let fragment = |size: usize| ProvenFragment { root: [0; 32], proof: vec![[1; 32]], data: vec![2; size] };
+    let mut store = FragmentStore::new(1000);
+    assert!(store.insert(Identifier::new(1, 0), fragment(400)));
+    assert!(!store.insert(Identifier::new(1, 0), fragment(10)));
+    assert_eq!(store.get(&Identifier::new(1, 0)), Some(&fragment(400)));
+    assert!(store.insert(Identifier::new(1, 1), fragment(400)));
+
+    // Node 1 has used 2 * (64 + 400) bytes
+    assert!(!store.insert(Identifier::new(1, 2), fragment(100)));
+    assert!(store.insert(Identifier::new(1, 2), fragment(8)));
+    assert!(store.insert(Identifier::new(2, 0), fragment(900)));
+    assert!(!store.contains(&Identifier::new(1, 3)));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    avss::{self, AvssMessage, BivariateCommitment, Sharing}, node::Node, polynomial::{self, Polynomial},
+    Identifier, Message, MessageType,
+};
+use bls12_381::{G1Projective, Scalar};
+
+use common::{config, start_all};
+
+
+async fn start(n: u16, base_port: u16) -> Vec<Arc<Node>> {
+    start_all((0..n).map(|id| config(n, id, base_port))).await
+}
+
+
+async fn wait_for_sharing(node: &Node, id: Identifier) -> Sharing {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(sharing) = node.sharing(id) {
+                return sharing;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }).await.expect("sharing did not complete")
+}
+
+
+#[tokio::test]
+async fn share_and_reconstruct() {
+    let nodes = start(4, 40200).await;
+    let secret = Scalar::from(42u64);
+    let id = nodes[0].share(secret).await.unwrap();
+
+    let mut sharings = Vec::new();
+    for node in &nodes {
+        sharings.push(wait_for_sharing(node, id).await);
+    }
+    for (node, sharing) in nodes.iter().zip(&sharings) {
+        assert_eq!(sharing.commitment, sharings[0].commitment);
+        assert_eq!(sharing.public_key(), G1Projective::generator() * secret);
+        assert!(avss::verify_share(sharing, node.config().my_id, sharing.share, &node.config()));
+    }
+
+    // t+1 = 2 nodes reconstruct together, a third one catches up from their answers
+    let first: Vec<_> = nodes[..2].iter().map(|node| {
+        let node = node.clone();
+        tokio::spawn(async move { node.reconstruct(id).await })
+    }).collect();
+    for handle in first {
+        assert_eq!(tokio::time::timeout(Duration::from_secs(10), handle).await.unwrap().unwrap().unwrap(), secret);
+    }
+    let late = tokio::time::timeout(Duration::from_secs(10), nodes[3].reconstruct(id)).await.unwrap().unwrap();
+    assert_eq!(late, secret);
+}
+
+
+#[tokio::test]
+async fn sharings_and_broadcasts_are_numbered_apart() {
+    let nodes = start_all((0..4).map(|id| {
+        let mut config = config(4, id, 40230);
+        config.limits.sequence_window = 8;
+        config
+    })).await;
+    let mut rx = nodes[1].subscribe_deliveries();
+
+    // Both start at sequence number 0, and neither leaves a gap in the other's numbers
+    let id = nodes[0].share(Scalar::from(7u64)).await.unwrap();
+    assert_eq!(id, Identifier::new(0, 0));
+    for i in 0..20u8 {
+        let broadcast = nodes[0].broadcast(vec![i]).await.unwrap();
+        assert_eq!(broadcast, Identifier::new(0, i as u64));
+        let delivery = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+            .expect("payload was not delivered")
+            .unwrap();
+        assert_eq!((delivery.id, delivery.payload), (broadcast, vec![i]));
+    }
+    let sharing = wait_for_sharing(&nodes[1], id).await;
+    assert_eq!(sharing.public_key(), G1Projective::generator() * Scalar::from(7u64));
+}
+
+
+#[tokio::test]
+async fn completes_when_a_faulty_dealer_skips_a_node() {
+    // n = 7, t = 2. Node 0 deals by hand: node 6 gets no Send at all and node 5 gets a
+    // corrupted row, which leaves exactly the ⌈(n+t+1)/2⌉ = 5 echoes needed
+    let nodes = start(7, 40210).await;
+    let config = nodes[0].config();
+    let t = config.fault_tolerance();
+    let secret = Scalar::from(7u64);
+    let mut coefficients: Vec<Vec<Scalar>> = (0..=t)
+        .map(|j| (0..=t).map(|l| Scalar::from((j * l + j + l) as u64)).collect())
+        .collect();
+    coefficients[0][0] = secret;
+    let commitment = BivariateCommitment::new(&coefficients);
+
+    let id = Identifier::new(0, 0);
+    for target in 0..6u16 {
+        let x = polynomial::node_point(&config, target).unwrap();
+        let mut row: Vec<Scalar> = (0..=t)
+            .map(|l| Polynomial { coefficients: coefficients.iter().map(|c| c[l]).collect() }.evaluate(x))
+            .collect();
+        if target == 5 {
+            row[0] += Scalar::one();
+        }
+        let message = Message::new(
+            config.domain(),
+            id,
+            0,
+            MessageType::Avss(AvssMessage::Send(commitment.clone(), row)),
+            &config.get_my_node().unwrap().privkey,
+        );
+        nodes[0].link().send(target, &message.to_bytes()).await.unwrap();
+    }
+
+    let shares = Polynomial { coefficients: coefficients[0].clone() };
+    for (index, node) in nodes.iter().enumerate() {
+        let sharing = wait_for_sharing(node, id).await;
+        assert_eq!(sharing.public_key(), G1Projective::generator() * secret);
+        assert_eq!(sharing.share, shares.evaluate(polynomial::node_point(&config, index as u16).unwrap()));
+    }
+}
+
+
+#[test]
+fn reconstruction_skips_bad_shares() {
+    let config = config(4, 0, 40220);
+    let f = Polynomial { coefficients: vec![Scalar::from(99u64), Scalar::from(5u64)] };
+    let sharing = Sharing { id: Identifier::new(0, 0), commitment: f.commit(), share: Scalar::zero() };
+    let share_of = |id: u16| f.evaluate(polynomial::node_point(&config, id).unwrap());
+
+    let shares = vec![(0, share_of(0) + Scalar::one()), (1, share_of(1)), (1, share_of(1)), (3, share_of(3))];
+    assert_eq!(avss::reconstruct(&sharing, &shares, &config), Some(Scalar::from(99u64)));
+    assert_eq!(avss::reconstruct(&sharing, &shares[..3], &config), None);
+}
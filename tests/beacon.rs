@@ -0,0 +1,186 @@
+mod common;
+
+use std::{path::PathBuf, sync::Arc, time::{Duration, Instant}};
+
+use asynchronous_broadcast_protocols::{beacon::{self, BeaconValue}, constants::{HTTP_REQUEST_TIMEOUT_MS, MAX_HTTP_CONNECTIONS}, dkg::KeyShare, node::Node};
+use bls12_381::Scalar;
+use ff::Field;
+use rand_core::OsRng;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+use common::{config, start_with_key};
+
+
+/// Start nodes `0..keys.len()` of `n` with the given key shares, without their beacons
+async fn start_with(n: u16, keys: &[KeyShare], base_port: u16) -> Vec<Arc<Node>> {
+    let mut nodes = Vec::new();
+    for (id, key) in (0..).zip(keys) {
+        nodes.push(start_with_key(config(n, id, base_port), key.clone()).await);
+    }
+    nodes
+}
+
+
+/// Start `n` nodes with dealt key shares, running the beacon on the first `running`
+async fn start(n: u16, running: u16, base_port: u16) -> Vec<Arc<Node>> {
+    let keys = KeyShare::deal(&config(n, 0, base_port), Scalar::random(OsRng));
+    let nodes = start_with(n, &keys[..running as usize], base_port).await;
+    for node in &nodes {
+        tokio::spawn(beacon::run(node.clone(), Duration::from_millis(20), None));
+    }
+    nodes
+}
+
+
+async fn next(node: &Node, round: u64) -> BeaconValue {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(value) = node.beacon(round) {
+                return value;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }).await.expect("beacon round did not finish")
+}
+
+
+async fn get(address: &str, path: &str) -> (String, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address).as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), serde_json::from_str(body).unwrap())
+}
+
+
+#[tokio::test]
+async fn rounds_are_chained_and_verifiable() {
+    let nodes = start(4, 4, 40530).await;
+    let public_key = nodes[0].key().unwrap().public_key();
+
+    let mut previous = [0; 32];
+    for round in 1..=5 {
+        let value = next(&nodes[0], round).await;
+        assert_eq!(value.round, round);
+        assert_eq!(value.previous, previous);
+        assert!(value.verify(&public_key));
+        previous = value.randomness;
+
+        // Every node outputs the same value
+        for node in &nodes[1..] {
+            assert_eq!(next(node, round).await, value);
+        }
+    }
+
+    let mut forged = next(&nodes[0], 2).await;
+    forged.randomness[0] ^= 1;
+    assert!(!forged.verify(&public_key));
+}
+
+
+#[tokio::test]
+async fn runs_with_a_node_down_and_serves_http() {
+    let nodes = start(4, 3, 40540).await;
+    let value = next(&nodes[1], 3).await;
+
+    let address = "127.0.0.1:40549";
+    tokio::spawn(beacon::serve(nodes[1].clone(), address));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (status, body) = get(address, "/beacon/3").await;
+    assert!(status.contains("200"));
+    assert_eq!(body, value.to_json());
+    let (status, body) = get(address, "/beacon/latest").await;
+    assert!(status.contains("200"));
+    assert!(body["round"].as_u64().unwrap() >= 3);
+    let (status, _) = get(address, "/beacon/1000000").await;
+    assert!(status.contains("404"));
+}
+
+
+#[tokio::test]
+async fn idle_http_clients_are_bounded_and_timed_out() {
+    let node = common::start(config(4, 0, 40590)).await;
+    let address = "127.0.0.1:40599";
+    tokio::spawn(beacon::serve(node, address));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Clients that connect and never send a request take up every connection
+    let mut idle = Vec::new();
+    for _ in 0..MAX_HTTP_CONNECTIONS {
+        idle.push(TcpStream::connect(address).await.unwrap());
+    }
+    let mut waiting = TcpStream::connect(address).await.unwrap();
+    waiting.write_all(format!("GET /beacon/latest HTTP/1.1\r\nHost: {}\r\n\r\n", address).as_bytes()).await.unwrap();
+    let mut response = String::new();
+    let read = tokio::time::timeout(Duration::from_millis(500), waiting.read_to_string(&mut response)).await;
+    assert!(read.is_err(), "answered beyond the connection limit");
+
+    // until they time out and are closed, and the waiting request is answered
+    let timeout = Duration::from_millis(HTTP_REQUEST_TIMEOUT_MS + 2000);
+    let mut byte = [0; 1];
+    assert_eq!(tokio::time::timeout(timeout, idle[0].read(&mut byte)).await.unwrap().unwrap(), 0);
+    tokio::time::timeout(timeout, waiting.read_to_string(&mut response)).await.unwrap().unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+
+#[tokio::test]
+async fn late_node_catches_up() {
+    let keys = KeyShare::deal(&config(4, 0, 40550), Scalar::random(OsRng));
+    let nodes = start_with(4, &keys, 40550).await;
+    let interval = Duration::from_millis(200);
+    for node in &nodes[..3] {
+        tokio::spawn(beacon::run(node.clone(), interval, None));
+    }
+    next(&nodes[0], 8).await;
+
+    // Node 3 walks through the past rounds without waiting for its ticks, which would take one per round
+    let started = Instant::now();
+    tokio::spawn(beacon::run(nodes[3].clone(), interval, None));
+    let latest = nodes[0].latest_beacon().unwrap().round;
+    assert_eq!(next(&nodes[3], latest).await, nodes[0].beacon(latest).unwrap());
+    assert!(started.elapsed() < (latest as u32 - 2) * interval, "caught up in {:?}", started.elapsed());
+}
+
+
+#[tokio::test]
+async fn restarted_beacon_continues_its_chain() {
+    let keys = KeyShare::deal(&config(4, 0, 40560), Scalar::random(OsRng));
+    let public_key = keys[0].public_key();
+    let heads: Vec<PathBuf> = (0..4)
+        .map(|id| std::env::temp_dir().join(format!("beacon-head-{}-{}", id, std::process::id())))
+        .collect();
+    for head in &heads {
+        let _ = std::fs::remove_file(head);
+    }
+
+    let nodes = start_with(4, &keys, 40560).await;
+    let beacons: Vec<_> = nodes.iter().zip(&heads)
+        .map(|(node, head)| tokio::spawn(beacon::run(node.clone(), Duration::from_millis(20), Some(head.clone()))))
+        .collect();
+    next(&nodes[0], 3).await;
+    for beacon in beacons {
+        beacon.abort();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let head = BeaconValue::load(&heads[0]).unwrap().unwrap();
+    assert!(head.round >= 3 && head.verify(&public_key));
+
+    // The whole cluster restarts, here on other ports
+    let nodes = start_with(4, &keys, 40570).await;
+    for (node, head) in nodes.iter().zip(&heads) {
+        tokio::spawn(beacon::run(node.clone(), Duration::from_millis(20), Some(head.clone())));
+    }
+    let value = next(&nodes[0], head.round + 1).await;
+    assert_eq!(value.previous, head.randomness);
+    assert!(value.verify(&public_key));
+    assert!(nodes[0].beacon(1).is_none());
+    assert!(BeaconValue::load(&heads[0]).unwrap().unwrap().round > head.round);
+
+    // A head signed with another key is refused
+    let other = KeyShare::deal(&config(4, 0, 40560), Scalar::random(OsRng));
+    let stranger = start_with(4, &other[..1], 40580).await;
+    assert!(beacon::run(stranger[0].clone(), Duration::from_millis(20), Some(heads[1].clone())).await.is_err());
+}
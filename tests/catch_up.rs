@@ -0,0 +1,252 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    catch_up::{self, CatchUpMessage, Certificate, RecentCertificates}, node::{Delivery, Node}, reliable_broadcast::ReliableBroadcastMessage,
+    transport::ReliableLink, BroadcastProtocol, Config, Error, Identifier, Message, MessageType, Protocol,
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+
+
+fn config(my_id: u16, base_port: u16) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({ "protocol": "reliable_broadcast" }))
+}
+
+
+fn signed(sender: u16, id: Identifier, payload: MessageType, config: &Config) -> Message {
+    let privkey = config.get_node(sender).unwrap().privkey;
+    Message::new_authenticated(id, sender, payload, &privkey, config)
+}
+
+
+/// A certificate for `payload` in instance `id` with readies from `voters`
+fn certificate(id: Identifier, payload: &[u8], voters: &[u16], config: &Config) -> Certificate {
+    let ready = ReliableBroadcastMessage::Ready(Sha256::digest(payload).into());
+    Certificate {
+        id,
+        payload: payload.to_vec(),
+        readies: voters.iter()
+            .map(|&voter| signed(voter, id, MessageType::ReliableBroadcast(ready.clone()), config))
+            .collect(),
+    }
+}
+
+
+async fn start(id: u16, base_port: u16) -> Arc<Node> {
+    common::start(config(id, base_port)).await
+}
+
+
+/// The next `n` deliveries, in any order
+async fn deliveries(rx: &mut broadcast::Receiver<Delivery>, n: usize) -> Vec<(Identifier, Vec<u8>)> {
+    let mut result = Vec::new();
+    while result.len() < n {
+        let delivery = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await
+            .expect("payload was not delivered")
+            .unwrap();
+        result.push((delivery.id, delivery.payload));
+    }
+    result.sort();
+    result
+}
+
+
+/// The next catch-up message `peer` receives from node 0 within `wait`
+async fn next_catch_up(peer: &ReliableLink, wait: Duration) -> Option<CatchUpMessage> {
+    while let Ok(Ok((0, bytes))) = tokio::time::timeout(wait, peer.recv()).await {
+        if let Ok(Message { payload: MessageType::CatchUp(message), .. }) = Message::from_bytes(&bytes) {
+            return Some(message);
+        }
+    }
+    None
+}
+
+
+#[test]
+fn certificates_need_a_delivery_quorum_of_readies() {
+    let config = config(0, 40800);
+    let id = Identifier::new(1, 0);
+    let valid = certificate(id, b"payload", &[1, 2, 3], &config);
+    valid.verify(&config).unwrap();
+    assert_eq!(valid.epoch(), Some(0));
+
+    let bytes = MessageType::CatchUp(CatchUpMessage::Response(vec![valid.clone()])).to_bytes();
+    let MessageType::CatchUp(CatchUpMessage::Response(decoded)) = MessageType::from_bytes(&bytes).unwrap() else { panic!("not a response") };
+    decoded[0].verify(&config).unwrap();
+
+    // Two readies, or the same ready twice, are not enough
+    assert!(matches!(certificate(id, b"payload", &[1, 2], &config).verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+    assert!(matches!(certificate(id, b"payload", &[1, 2, 2], &config).verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+
+    let mut other_payload = valid.clone();
+    other_payload.payload = b"other".to_vec();
+    assert!(matches!(other_payload.verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+
+    let mut other_instance = valid.clone();
+    other_instance.id = Identifier::new(1, 1);
+    assert!(matches!(other_instance.verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+
+    let mut forged = valid;
+    forged.readies[0].signature[0] ^= 1;
+    assert!(matches!(forged.verify(&config), Err(Error::BadSignature(1))));
+}
+
+
+#[test]
+fn hybrid_certificates_need_readies_beyond_crash_faulty_nodes() {
+    // b = 1, c = 2: of 2b+1 Readies, two may come from nodes that crashed right after sending
+    let config = common::config_with(&common::ids(8), 0, 40860, serde_json::json!({
+        "protocol": "reliable_broadcast",
+        "hybrid": { "byzantine": 1, "crash": 2 },
+    }));
+    config.validate().unwrap();
+    let id = Identifier::new(1, 0);
+    assert!(matches!(certificate(id, b"payload", &[1, 2, 3], &config).verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+    assert!(matches!(certificate(id, b"payload", &[1, 2, 3, 4], &config).verify(&config), Err(Error::InvalidDeliveryCertificate(_))));
+    certificate(id, b"payload", &[1, 2, 3, 4, 5], &config).verify(&config).unwrap();
+}
+
+
+#[tokio::test]
+async fn offline_node_catches_up() {
+    let base_port = 40810;
+
+    // Node 3 is down: its address only acknowledges what it is sent
+    let absent = ReliableLink::bind(&config(3, base_port)).await.unwrap();
+    let mut nodes = Vec::new();
+    for id in 0..3 {
+        nodes.push(start(id, base_port).await);
+    }
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+    let mut missed = Vec::new();
+    for (node, payload) in [(0, b"a"), (0, b"b"), (1, b"c")] {
+        missed.push((nodes[node].broadcast(payload.to_vec()).await.unwrap(), payload.to_vec()));
+    }
+    missed.sort();
+    for rx in &mut receivers {
+        assert_eq!(deliveries(rx, missed.len()).await, missed);
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    drop(absent);
+
+    let lagging = start(3, base_port).await;
+    let mut rx = lagging.subscribe_deliveries();
+    lagging.catch_up().await.unwrap();
+    assert_eq!(deliveries(&mut rx, missed.len()).await, missed);
+
+    // Nothing is delivered twice, and new instances include node 3 again
+    lagging.catch_up().await.unwrap();
+    let new = nodes[0].broadcast(b"d".to_vec()).await.unwrap();
+    assert_eq!(deliveries(&mut rx, 1).await, [(new, b"d".to_vec())]);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(rx.try_recv().is_err());
+}
+
+
+#[tokio::test]
+async fn certificates_are_verified_before_delivery() {
+    let base_port = 40820;
+    let config = config(0, base_port);
+    let mut peers = Vec::new();
+    for id in 1..4 {
+        peers.push(ReliableLink::bind(&self::config(id, base_port)).await.unwrap());
+    }
+    let node = start(0, base_port).await;
+    let mut rx = node.subscribe_deliveries();
+
+    // An instance far beyond the sequence window shows that node 0 lags behind
+    let ahead = Identifier::new(1, 5000);
+    let send = signed(1, ahead, MessageType::ReliableBroadcast(ReliableBroadcastMessage::Send(b"ahead".to_vec())), &config);
+    peers[0].send(0, &send.to_bytes()).await.unwrap();
+    for peer in &peers {
+        let request = next_catch_up(peer, Duration::from_secs(2)).await;
+        assert!(matches!(request, Some(CatchUpMessage::Request { ref watermarks, after: None }) if watermarks.is_empty()), "{:?}", request);
+    }
+
+    // Readies of two nodes do not prove a delivery
+    let id = Identifier::new(1, 0);
+    let response = |certificate| signed(1, Identifier::new(1, 0), MessageType::CatchUp(CatchUpMessage::Response(vec![certificate])), &config);
+    peers[0].send(0, &response(certificate(id, b"payload", &[1, 2], &config)).to_bytes()).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(500), rx.recv()).await.is_err());
+
+    // Three do; node 0 then asks for the next page
+    peers[0].send(0, &response(certificate(id, b"payload", &[1, 2, 3], &config)).to_bytes()).await.unwrap();
+    let delivery = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(delivery, Delivery { id, payload: b"payload".to_vec() });
+    let request = next_catch_up(&peers[0], Duration::from_secs(2)).await;
+    assert!(matches!(request, Some(CatchUpMessage::Request { ref watermarks, after: Some(after) }) if *watermarks == [(1, 1)] && after == id), "{:?}", request);
+
+    // Node 0 serves what it caught up on to others
+    let request = signed(2, Identifier::new(2, 0), MessageType::CatchUp(CatchUpMessage::Request { watermarks: Vec::new(), after: None }), &config);
+    peers[1].send(0, &request.to_bytes()).await.unwrap();
+    let Some(CatchUpMessage::Response(certificates)) = next_catch_up(&peers[1], Duration::from_secs(2)).await else { panic!("no response") };
+    assert_eq!(certificates.len(), 1);
+    assert_eq!(certificates[0].payload, b"payload");
+    certificates[0].verify(&config).unwrap();
+}
+
+
+#[test]
+fn pages_go_past_certificates_the_requester_has() {
+    let config = config(0, 40830);
+    let mut certificates = RecentCertificates::new(usize::MAX);
+    for sequence in 0..4 {
+        certificates.insert(certificate(Identifier::new(1, sequence), &[sequence as u8], &[1, 2, 3], &config));
+    }
+    let ids = |page: Vec<Certificate>| page.into_iter().map(|certificate| certificate.id.sequence()).collect::<Vec<_>>();
+
+    // The requester misses (1, 0) for good, and has the rest already
+    assert_eq!(ids(catch_up::missing(&[(1, 0)], None, &certificates)), [0, 1, 2, 3]);
+    assert_eq!(ids(catch_up::missing(&[(1, 0)], Some(Identifier::new(1, 1)), &certificates)), [2, 3]);
+    assert!(catch_up::missing(&[(1, 0)], Some(Identifier::new(1, 3)), &certificates).is_empty());
+
+    let bytes = MessageType::CatchUp(CatchUpMessage::Request { watermarks: vec![(1, 0)], after: Some(Identifier::new(1, 3)) }).to_bytes();
+    assert!(matches!(MessageType::from_bytes(&bytes).unwrap(), MessageType::CatchUp(CatchUpMessage::Request { after: Some(after), .. }) if after == Identifier::new(1, 3)));
+}
+
+
+#[test]
+fn only_recent_certificates_are_kept() {
+    let config = config(0, 40830);
+    let size = certificate(Identifier::new(1, 0), b"x", &[1, 2, 3], &config).to_bytes().len();
+    let mut certificates = RecentCertificates::new(2 * size);
+    for sequence in 0..3 {
+        certificates.insert(certificate(Identifier::new(1, sequence), b"x", &[1, 2, 3], &config));
+    }
+    let kept: Vec<u64> = certificates.iter().map(|certificate| certificate.id.sequence()).collect();
+    assert_eq!(kept, [1, 2]);
+}
+
+
+#[tokio::test]
+async fn catch_up_requests_are_rate_limited() {
+    let base_port = 40840;
+    let mut node_config = config(0, base_port);
+    node_config.limits.max_catch_up_requests = 2;
+    let node = common::start(node_config).await;
+    let config = config(1, base_port);
+    let peer = ReliableLink::bind(&config).await.unwrap();
+
+    let request = signed(1, Identifier::new(1, 0), MessageType::CatchUp(CatchUpMessage::Request { watermarks: Vec::new(), after: None }), &config);
+    for _ in 0..4 {
+        peer.send(0, &request.to_bytes()).await.unwrap();
+    }
+    let mut answered = 0;
+    while next_catch_up(&peer, Duration::from_millis(500)).await.is_some() {
+        answered += 1;
+    }
+    assert_eq!(answered, 2);
+    assert!(node.banned_peers().is_empty());
+}
+
+
+#[tokio::test]
+async fn catch_up_needs_reliable_broadcast() {
+    let mut config = config(0, 40850);
+    config.protocol = Protocol::TwoStepBroadcast;
+    config.t = Some(0);
+    let node = Node::start(config).await.unwrap();
+    assert!(matches!(node.catch_up().await, Err(Error::CatchUpUnsupported(BroadcastProtocol::TwoStepBroadcast))));
+}
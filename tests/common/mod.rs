@@ -0,0 +1,70 @@
+//! Cluster setup shared by the integration tests. Node `id` listens on `base_port + id`
+//! and derives its key from `[id + 1; 32]`.
+
+#![allow(dead_code)]  // every test crate uses only part of it
+
+use std::sync::Arc;
+
+use asynchronous_broadcast_protocols::{dkg::KeyShare, node::Node, Config};
+
+
+/// Config of node `my_id` among the nodes `ids`, with the top-level `settings`
+/// (e.g. `{"protocol": "merkle_broadcast"}`) added to the defaults
+pub fn config_with(ids: &[u16], my_id: u16, base_port: u16, settings: serde_json::Value) -> Config {
+    let nodes: Vec<serde_json::Value> = ids.iter()
+        .map(|&id| serde_json::json!({
+            "id": id,
+            "address": format!("127.0.0.1:{}", base_port + id),
+            "privkey": vec![id as u8 + 1; 32],
+        }))
+        .collect();
+    let mut config = serde_json::json!({
+        "my_id": my_id,
+        "nodes": nodes,
+    });
+    if let serde_json::Value::Object(settings) = settings {
+        config.as_object_mut().unwrap().extend(settings);
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+
+/// Config of node `my_id` in a cluster of the nodes 0 to n - 1 with default settings
+pub fn config(n: u16, my_id: u16, base_port: u16) -> Config {
+    config_with(&ids(n), my_id, base_port, serde_json::json!({}))
+}
+
+
+/// The ids 0 to n - 1
+pub fn ids(n: u16) -> Vec<u16> {
+    (0..n).collect()
+}
+
+
+/// Validate `config`, start its node and run it in the background
+pub async fn start(config: Config) -> Arc<Node> {
+    config.validate().unwrap();
+    let node = Node::start(config).await.unwrap();
+    tokio::spawn(node.clone().run());
+    node
+}
+
+
+/// `start`, with `key` installed before the node handles its first message
+pub async fn start_with_key(config: Config, key: KeyShare) -> Arc<Node> {
+    config.validate().unwrap();
+    let node = Node::start(config).await.unwrap();
+    node.install_key(key);
+    tokio::spawn(node.clone().run());
+    node
+}
+
+
+/// `start` every config in turn
+pub async fn start_all(configs: impl IntoIterator<Item = Config>) -> Vec<Arc<Node>> {
+    let mut nodes = Vec::new();
+    for config in configs {
+        nodes.push(start(config).await);
+    }
+    nodes
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::{collections::HashSet, time::Duration};
+
+use asynchronous_broadcast_protocols::{node::Delivery, Config};
+
+
+fn config(my_id: u16, base_port: u16, authentication: &str) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({
+        "protocol": "crash_broadcast",
+        "authentication": authentication,
+    }))
+}
+
+
+async fn deliveries_with_one_crashed_node(base_port: u16, authentication: &str) {
+    // Node 3 never starts
+    let nodes = common::start_all((0..3).map(|id| config(id, base_port, authentication))).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    let mut expected = HashSet::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let payload = format!("from {}", i).into_bytes();
+        let id = node.broadcast(payload.clone()).await.unwrap();
+        expected.insert(Delivery { id, payload });
+    }
+
+    for rx in &mut receivers {
+        let mut delivered = HashSet::new();
+        while delivered.len() < expected.len() {
+            let delivery = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+            assert!(delivered.insert(delivery), "delivered twice");
+        }
+        assert_eq!(delivered, expected);
+    }
+}
+
+
+#[tokio::test]
+async fn crash_broadcast_without_signatures() {
+    deliveries_with_one_crashed_node(39300, "none").await;
+}
+
+
+#[tokio::test]
+async fn crash_broadcast_with_signatures() {
+    deliveries_with_one_crashed_node(39310, "signatures").await;
+}
+
+
+#[test]
+fn unauthenticated_requires_crash_broadcast() {
+    let mut config = config(0, 39320, "none");
+    config.protocol = Default::default();
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::{io::Write, path::PathBuf, time::Duration};
+
+use asynchronous_broadcast_protocols::{delivery_log::{DeliveryLog, ENTRY_HEADER_SIZE}, Config, Error, Identifier};
+use sha2::{Digest, Sha256};
+
+
+fn log_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("delivery-log-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+
+fn config(my_id: u16, base_port: u16, name: &str) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({ "delivery_log": log_path(&format!("{}-{}", name, my_id)) }))
+}
+
+
+#[test]
+fn log_is_hash_chained_and_indexed() {
+    let path = log_path("chain");
+    let path = path.to_str().unwrap();
+    let log = DeliveryLog::open(path).unwrap();
+    assert!(log.is_empty());
+
+    let first = log.append(Identifier::new(1, 0), b"a").unwrap();
+    let second = log.append(Identifier::new(2, 0), b"b").unwrap();
+    let third = log.append(Identifier::new(1, 1), b"c").unwrap();
+    assert_eq!((first.index, first.prev), (0, [0; 32]));
+    assert_eq!(second.prev, first.hash);
+    assert_eq!(third.prev, second.hash);
+
+    // Logging an instance again changes nothing, unless the payload differs
+    assert_eq!(log.append(Identifier::new(2, 0), b"b").unwrap(), second);
+    assert!(matches!(log.append(Identifier::new(2, 0), b"x"), Err(Error::DeliveryLog { .. })));
+    assert_eq!(log.len(), 3);
+
+    assert_eq!(log.digest(Identifier::new(1, 1)), Some(Sha256::digest(b"c").into()));
+    assert_eq!(log.digest(Identifier::new(1, 2)), None);
+    assert_eq!(log.entry(Identifier::new(2, 0)), Some(second));
+    assert_eq!(log.range(1..), [second, third]);
+    assert_eq!(log.range(..1), [first]);
+    assert_eq!(log.by_sender(1, ..), [first, third]);
+    assert_eq!(log.by_sender(1, 1..=5), [third]);
+    assert!(log.by_sender(3, ..).is_empty());
+    assert_eq!(log.payload(&third).unwrap(), b"c");
+    drop(log);
+
+    // Reopened after a crash in the middle of an append
+    let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(&[0, 0, 1, 0, 9, 9]).unwrap();
+    drop(file);
+
+    // An auditor reading meanwhile leaves the half-written entry alone
+    let size = std::fs::metadata(path).unwrap().len();
+    let audited = DeliveryLog::open_read_only(path).unwrap();
+    assert_eq!(audited.head(), Some(third));
+    assert!(matches!(audited.append(Identifier::new(3, 0), b"d"), Err(Error::DeliveryLog { .. })));
+    assert_eq!(std::fs::metadata(path).unwrap().len(), size);
+    assert!(DeliveryLog::open_read_only(&format!("{}.missing", path)).is_err());
+
+    let log = DeliveryLog::open(path).unwrap();
+    assert_eq!(log.head(), Some(third));
+    assert_eq!(log.by_sender(2, ..), [second]);
+    let fourth = log.append(Identifier::new(3, 0), b"d").unwrap();
+    assert_eq!(fourth.prev, third.hash);
+    assert_eq!(log.payload(&fourth).unwrap(), b"d");
+    drop(log);
+
+    // A changed payload breaks the chain
+    let mut bytes = std::fs::read(path).unwrap();
+    let second_payload = 2 * (4 + ENTRY_HEADER_SIZE) + 1;
+    assert_eq!(bytes[second_payload], b'b');
+    bytes[second_payload] = b'x';
+    std::fs::write(path, bytes).unwrap();
+    assert!(matches!(DeliveryLog::open(path), Err(Error::DeliveryLog { .. })));
+}
+
+
+#[tokio::test]
+async fn nodes_record_what_they_deliver() {
+    let base_port = 40900;
+    let nodes = common::start_all((0..4).map(|id| config(id, base_port, "node"))).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    let mut ids = Vec::new();
+    for (sender, payload) in [(0, b"a"), (1, b"b"), (0, b"c")] {
+        ids.push((nodes[sender].broadcast(payload.to_vec()).await.unwrap(), payload));
+    }
+    for rx in &mut receivers {
+        for _ in 0..ids.len() {
+            tokio::time::timeout(Duration::from_secs(10), rx.recv()).await.unwrap().unwrap();
+        }
+    }
+
+    for node in &nodes {
+        let log = node.delivery_log().unwrap();
+        assert_eq!(log.len(), 3);
+        for (id, payload) in &ids {
+            assert_eq!(log.digest(*id), Some(Sha256::digest(payload).into()));
+        }
+        let from_0: Vec<Identifier> = log.by_sender(0, ..).iter().map(|entry| entry.id).collect();
+        assert_eq!(from_0, [ids[0].0, ids[2].0]);
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{dkg::KeyShare, node::Node, polynomial, Config};
+use bls12_381::{G1Projective, Scalar};
+
+use common::{config, start_all};
+
+
+async fn start(n: u16, running: u16, base_port: u16) -> Vec<Arc<Node>> {
+    start_all((0..running).map(|id| config(n, id, base_port))).await
+}
+
+
+async fn generate(nodes: &[Arc<Node>], session: u64) -> Vec<KeyShare> {
+    let handles: Vec<_> = nodes.iter().map(|node| {
+        let node = node.clone();
+        tokio::spawn(async move { node.generate_key(session).await })
+    }).collect();
+    let mut keys = Vec::new();
+    for handle in handles {
+        let key = tokio::time::timeout(Duration::from_secs(30), handle).await
+            .expect("key generation did not finish")
+            .unwrap()
+            .unwrap();
+        keys.push(key);
+    }
+    keys
+}
+
+
+/// The secret behind t+1 shares, which no node ever learns in practice
+fn combine(keys: &[KeyShare], config: &Config) -> Scalar {
+    let points: Vec<(Scalar, Scalar)> = keys.iter()
+        .map(|key| (polynomial::node_point(config, key.node).unwrap(), key.share))
+        .collect();
+    polynomial::interpolate(&points, Scalar::zero())
+}
+
+
+#[tokio::test]
+async fn nodes_agree_on_a_joint_key() {
+    let nodes = start(4, 4, 40300).await;
+    let keys = generate(&nodes, 0).await;
+
+    for (node, key) in nodes.iter().zip(&keys) {
+        assert_eq!(key.node, node.config().my_id);
+        assert_eq!(key.public_key(), keys[0].public_key());
+        assert_eq!(key.commitment, keys[0].commitment);
+        assert_eq!(key.threshold(), 1);
+        key.verify(&node.config()).unwrap();
+    }
+    let config = nodes[0].config();
+    assert_eq!(G1Projective::generator() * combine(&keys[..2], &config), keys[0].public_key());
+    assert_eq!(combine(&keys[1..3], &config), combine(&keys[2..], &config));
+
+    // A later call returns the same share
+    assert_eq!(nodes[1].generate_key(0).await.unwrap(), keys[1]);
+}
+
+
+#[tokio::test]
+async fn completes_with_a_node_down() {
+    let nodes = start(4, 3, 40310).await;
+    let keys = generate(&nodes, 7).await;
+
+    for (node, key) in nodes.iter().zip(&keys) {
+        assert_eq!(key.public_key(), keys[0].public_key());
+        key.verify(&node.config()).unwrap();
+    }
+}
+
+
+#[tokio::test]
+async fn key_file_round_trip() {
+    let nodes = start(4, 4, 40320).await;
+    let keys = generate(&nodes, 1).await;
+    let config = nodes[2].config();
+
+    let path = std::env::temp_dir().join(format!("dkg-key-{}.json", std::process::id()));
+    keys[2].save(&path).unwrap();
+    let loaded = KeyShare::load(&path).unwrap();
+    assert_eq!(loaded, keys[2]);
+    loaded.verify(&config).unwrap();
+
+    // Somebody else's share does not match
+    let mut wrong = loaded.clone();
+    wrong.share = keys[3].share;
+    assert!(wrong.verify(&config).is_err());
+
+    std::fs::write(&path, "{\"node\": 2, \"commitment\": [\"00\"], \"share\": \"00\"}").unwrap();
+    assert!(KeyShare::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+
+#[tokio::test]
+async fn nodes_only_deal_in_sessions_they_started() {
+    let nodes = start(4, 4, 40330).await;
+
+    // Node 0 alone cannot make the others deal
+    let first = nodes[0].clone();
+    let started = tokio::spawn(async move { first.generate_key(3).await });
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(!started.is_finished());
+    assert!(nodes.iter().all(|node| node.key().is_none()));
+
+    // Its messages wait at the others until they start the session too
+    let keys = generate(&nodes[1..], 3).await;
+    let key = tokio::time::timeout(Duration::from_secs(30), started).await.unwrap().unwrap().unwrap();
+    assert_eq!(key.public_key(), keys[0].public_key());
+}
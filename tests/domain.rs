@@ -0,0 +1,80 @@
+mod common;
+
+use std::time::Duration;
+
+use asynchronous_broadcast_protocols::{
+    reliable_broadcast::ReliableBroadcastMessage, transport::ReliableLink, Config, Domain, Error,
+    Identifier, Message, MessageType, quorum::QuorumSystem,
+};
+
+
+fn config(my_id: u16, base_port: u16, epoch: u64) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({ "protocol": "reliable_broadcast", "epoch": epoch }))
+}
+
+
+fn send(domain: Domain, payload: &[u8]) -> Message {
+    let send = ReliableBroadcastMessage::Send(payload.to_vec());
+    Message::new(domain, Identifier::new(3, 0), 3, MessageType::ReliableBroadcast(send), &[4; 32])
+}
+
+
+#[test]
+fn domains_follow_the_config() {
+    let current = config(0, 41320, 0);
+    let other_cluster = config(0, 41330, 0);
+    let next_epoch = config(0, 41320, 1);
+    assert!(send(current.domain(), b"x").domain.check(&current.domain()).is_ok());
+    assert!(matches!(current.domain().check(&other_cluster.domain()), Err(Error::ClusterMismatch { .. })));
+    assert!(matches!(current.domain().check(&next_epoch.domain()), Err(Error::EpochMismatch { got: 0, expected: 1 })));
+
+    // A copy changed after the domain was first taken has the domain of its new fields
+    let mut changed = current.clone();
+    assert_eq!(changed.domain(), current.domain());
+    changed.epoch = 1;
+    changed.nodes[3].address = "127.0.0.1:41333".to_string();
+    assert_eq!(changed.domain().epoch, 1);
+    assert_ne!(changed.domain().cluster_id, current.domain().cluster_id);
+    assert_eq!(changed.quorums(), QuorumSystem::threshold(4, 1));
+    changed.t = Some(0);
+    assert_eq!(changed.quorums(), QuorumSystem::threshold(4, 0));
+
+    // The envelope is signed: moving a message to another domain breaks its signature
+    let pubkey = ed25519_dalek::SigningKey::from_bytes(&[4; 32]).verifying_key();
+    let mut moved = send(other_cluster.domain(), b"x");
+    assert!(moved.verify(pubkey));
+    moved.domain = current.domain();
+    assert!(!moved.verify(pubkey));
+}
+
+
+#[tokio::test]
+async fn messages_of_other_clusters_and_epochs_are_dropped() {
+    let base_port = 41340;
+    let faulty = ReliableLink::bind(&config(3, base_port, 0)).await.unwrap();
+    // Keep reading, so that the link answers handshakes
+    let reader = faulty.clone();
+    tokio::spawn(async move { while reader.recv().await.is_ok() {} });
+    let nodes = common::start_all((0..3).map(|id| config(id, base_port, 0))).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    // Node 3's keys, signing for a cluster with other addresses and for an epoch ahead
+    let other_cluster = config(3, base_port + 10, 0).domain();
+    let later_epoch = config(3, base_port, 2).domain();
+    for domain in [other_cluster, later_epoch] {
+        faulty.send_to_all(&send(domain, b"replayed").to_bytes()).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    for rx in &mut receivers {
+        assert!(rx.try_recv().is_err());
+    }
+
+    // The same message in this cluster's domain is delivered
+    faulty.send_to_all(&send(config(3, base_port, 0).domain(), b"current").to_bytes()).await.unwrap();
+    for rx in &mut receivers {
+        let delivery = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+            .expect("payload was not delivered")
+            .unwrap();
+        assert_eq!(delivery.payload, b"current");
+    }
+}
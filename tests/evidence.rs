@@ -0,0 +1,115 @@
+mod common;
+
+use asynchronous_broadcast_protocols::{
+    constants::MAX_MESSAGE_SIZE, evidence::{EquivocationDetector, Evidence}, reliable_broadcast::ReliableBroadcastMessage,
+    Config, Error, Identifier, Message, MessageType,
+};
+
+
+fn config(epoch: u64, base_port: u16) -> Config {
+    common::config_with(&common::ids(4), 0, base_port, serde_json::json!({ "epoch": epoch }))
+}
+
+
+fn signed(sender: u16, id: Identifier, message: ReliableBroadcastMessage, config: &Config) -> Message {
+    let privkey = config.get_node(sender).unwrap().privkey;
+    Message::new_authenticated(id, sender, MessageType::ReliableBroadcast(message), &privkey, config)
+}
+
+
+fn echo(sender: u16, id: Identifier, byte: u8, config: &Config) -> Message {
+    signed(sender, id, ReliableBroadcastMessage::Echo([byte; 32]), config)
+}
+
+
+#[test]
+fn evidence_of_conflicting_votes_verifies() {
+    let config = config(0, 41200);
+    let id = Identifier::new(2, 0);
+    let evidence = Evidence::new(echo(1, id, 1, &config), echo(1, id, 2, &config));
+    evidence.verify(&config).unwrap();
+    assert_eq!(evidence.offender(), 1);
+
+    let decoded = Evidence::from_bytes(&evidence.to_bytes()).unwrap();
+    decoded.verify(&config).unwrap();
+    assert_eq!(decoded.second.payload.to_bytes(), evidence.second.payload.to_bytes());
+}
+
+
+#[test]
+fn forged_evidence_is_rejected() {
+    let config = config(0, 41200);
+    let id = Identifier::new(2, 0);
+    let invalid = |first, second| matches!(Evidence::new(first, second).verify(&config), Err(Error::InvalidEvidence(_)));
+
+    // The same vote twice, votes in two instances, of two nodes, or of two kinds
+    assert!(invalid(echo(1, id, 1, &config), echo(1, id, 1, &config)));
+    assert!(invalid(echo(1, id, 1, &config), echo(1, Identifier::new(2, 1), 2, &config)));
+    assert!(invalid(echo(1, id, 1, &config), echo(3, id, 2, &config)));
+    assert!(invalid(echo(1, id, 1, &config), signed(1, id, ReliableBroadcastMessage::Ready([2; 32]), &config)));
+    // Messages that are no votes
+    assert!(invalid(signed(1, id, ReliableBroadcastMessage::Request, &config), signed(1, id, ReliableBroadcastMessage::Answer(b"x".to_vec()), &config)));
+
+    // A vote the offender never signed
+    let mut forged = echo(1, id, 2, &config);
+    forged.signature = echo(1, id, 3, &config).signature;
+    assert!(matches!(Evidence::new(echo(1, id, 1, &config), forged).verify(&config), Err(Error::BadSignature(1))));
+    let mut impostor = echo(2, id, 2, &config);
+    impostor.sender = 1;
+    assert!(matches!(Evidence::new(echo(1, id, 1, &config), impostor).verify(&config), Err(Error::BadSignature(1))));
+
+    // Votes signed for another cluster, or one vote from another epoch
+    let other_cluster = self::config(0, 41300);
+    assert!(invalid(echo(1, id, 1, &other_cluster), echo(1, id, 2, &other_cluster)));
+    let next_epoch = self::config(1, 41200);
+    assert!(invalid(echo(1, id, 1, &config), echo(1, id, 2, &next_epoch)));
+}
+
+
+#[test]
+fn detector_turns_conflicts_into_evidence() {
+    let config = config(0, 41200);
+    let id = Identifier::new(2, 0);
+    let mut detector = EquivocationDetector::new();
+    assert!(detector.observe(&echo(1, id, 1, &config)).is_none());
+    assert!(detector.observe(&echo(1, id, 1, &config)).is_none());
+    assert!(detector.observe(&echo(3, id, 2, &config)).is_none());
+
+    let evidence = detector.observe(&echo(1, id, 2, &config)).unwrap();
+    evidence.verify(&config).unwrap();
+    assert_eq!(detector.offenders(), [1]);
+
+    // One proof per offender is enough
+    assert!(detector.observe(&echo(1, id, 3, &config)).is_none());
+    assert!(!detector.record(Evidence::new(echo(1, Identifier::new(2, 1), 1, &config), echo(1, Identifier::new(2, 1), 2, &config))));
+    assert!(detector.record(Evidence::new(echo(3, id, 2, &config), echo(3, id, 3, &config))));
+    assert_eq!(detector.offenders(), [1, 3]);
+    assert_eq!(detector.evidence().len(), 2);
+
+    // Once an instance is forgotten, its votes count as first votes again
+    detector.forget(id, false);
+    assert!(detector.observe(&echo(2, id, 1, &config)).is_none());
+    detector.forget(id, false);
+    assert!(detector.observe(&echo(2, id, 2, &config)).is_none());
+}
+
+
+#[test]
+fn evidence_fits_into_a_message() {
+    let config = config(0, 41200);
+    let id = Identifier::new(1, 0);
+    let mut detector = EquivocationDetector::new();
+
+    // Two sends as large as a message allows cannot both be shown
+    let size = MAX_MESSAGE_SIZE - 1024;
+    assert!(detector.observe(&signed(1, id, ReliableBroadcastMessage::Send(vec![1; size]), &config)).is_none());
+    assert!(detector.observe(&signed(1, id, ReliableBroadcastMessage::Send(vec![2; size]), &config)).is_none());
+
+    // Smaller ones can
+    let id = Identifier::new(1, 1);
+    let size = MAX_MESSAGE_SIZE / 4;
+    assert!(detector.observe(&signed(1, id, ReliableBroadcastMessage::Send(vec![1; size]), &config)).is_none());
+    let evidence = detector.observe(&signed(1, id, ReliableBroadcastMessage::Send(vec![2; size]), &config)).unwrap();
+    assert!(evidence.to_bytes().len() < MAX_MESSAGE_SIZE);
+    evidence.verify(&config).unwrap();
+}
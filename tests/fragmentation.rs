@@ -0,0 +1,115 @@
+use std::{net::SocketAddr, time::Duration};
+
+use asynchronous_broadcast_protocols::{constants::*, transport::{fragment, Fragment, Reassembler}, Error};
+
+
+fn address(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+
+/// A message of `len` bytes that differs from others of the same length by `seed`
+fn message(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+
+#[test]
+fn fragments_reassemble_in_any_order() {
+    let bytes = message(5 * FRAGMENT_PAYLOAD_SIZE + 17, 0);
+    let mut fragments = fragment(&bytes).unwrap();
+    assert_eq!(fragments.len(), 6);
+    assert!(fragments.iter().all(|f| f.to_bytes().len() <= MESSAGE_BUFFER_SIZE));
+
+    fragments.reverse();
+    fragments.swap(1, 3);
+    let mut reassembler = Reassembler::new();
+    let (last, rest) = fragments.split_last().unwrap();
+    for fragment in rest {
+        assert_eq!(reassembler.insert(address(1), &fragment.to_bytes()).unwrap(), None);
+        // A duplicate changes nothing
+        assert_eq!(reassembler.insert(address(1), &fragment.to_bytes()).unwrap(), None);
+    }
+    assert_eq!(reassembler.buffered(), bytes.len() - last.chunk.len());
+    assert_eq!(reassembler.insert(address(1), &last.to_bytes()).unwrap(), Some(bytes));
+    assert_eq!(reassembler.buffered(), 0);
+}
+
+
+#[test]
+fn messages_are_reassembled_per_source() {
+    let bytes = message(2 * FRAGMENT_PAYLOAD_SIZE, 0);
+    let fragments = fragment(&bytes).unwrap();
+    let mut reassembler = Reassembler::new();
+
+    // The same message from two sources is two messages
+    assert_eq!(reassembler.insert(address(1), &fragments[0].to_bytes()).unwrap(), None);
+    assert_eq!(reassembler.insert(address(2), &fragments[1].to_bytes()).unwrap(), None);
+    assert_eq!(reassembler.insert(address(1), &fragments[1].to_bytes()).unwrap(), Some(bytes.clone()));
+    assert_eq!(reassembler.insert(address(2), &fragments[0].to_bytes()).unwrap(), Some(bytes));
+
+    // Small and empty messages fit into a single datagram
+    for bytes in [Vec::new(), b"small".to_vec()] {
+        let fragments = fragment(&bytes).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(reassembler.insert(address(1), &fragments[0].to_bytes()).unwrap(), Some(bytes));
+    }
+}
+
+
+#[test]
+fn incomplete_messages_time_out() {
+    let mut reassembler = Reassembler::with_timeout(Duration::from_millis(50));
+    let fragments = fragment(&message(2 * FRAGMENT_PAYLOAD_SIZE, 0)).unwrap();
+    assert_eq!(reassembler.insert(address(1), &fragments[0].to_bytes()).unwrap(), None);
+    std::thread::sleep(Duration::from_millis(100));
+
+    // The first half is gone, so the second one starts over
+    assert_eq!(reassembler.insert(address(1), &fragments[1].to_bytes()).unwrap(), None);
+    assert_eq!(reassembler.buffered(), FRAGMENT_PAYLOAD_SIZE);
+}
+
+
+#[test]
+fn sizes_are_capped() {
+    assert!(matches!(fragment(&vec![0; MAX_MESSAGE_SIZE + 1]), Err(Error::PayloadTooLarge { .. })));
+    assert_eq!(fragment(&vec![0; MAX_MESSAGE_SIZE]).unwrap().len(), MAX_FRAGMENTS);
+
+    let fragment = |index, count, len| Fragment { message_id: 1, index, count, chunk: vec![0; len] }.to_bytes();
+    assert!(Fragment::from_bytes(&fragment(0, 2, FRAGMENT_PAYLOAD_SIZE)).is_ok());
+    assert!(Fragment::from_bytes(&fragment(1, 2, 1)).is_ok());
+    assert!(Fragment::from_bytes(&[0; FRAGMENT_HEADER_SIZE - 1]).is_err());
+    // Too many fragments, an index beyond the count, a short chunk before the last or an oversized one
+    assert!(Fragment::from_bytes(&fragment(0, MAX_FRAGMENTS as u16 + 1, FRAGMENT_PAYLOAD_SIZE)).is_err());
+    assert!(Fragment::from_bytes(&fragment(0, 0, 0)).is_err());
+    assert!(Fragment::from_bytes(&fragment(2, 2, 1)).is_err());
+    assert!(Fragment::from_bytes(&fragment(0, 2, 1)).is_err());
+    assert!(Fragment::from_bytes(&fragment(1, 2, FRAGMENT_PAYLOAD_SIZE + 1)).is_err());
+
+    // A fragment that disagrees on the count of its message is rejected
+    let mut reassembler = Reassembler::new();
+    assert_eq!(reassembler.insert(address(1), &fragment(0, 3, FRAGMENT_PAYLOAD_SIZE)).unwrap(), None);
+    assert!(reassembler.insert(address(1), &fragment(1, 2, FRAGMENT_PAYLOAD_SIZE)).is_err());
+}
+
+
+#[test]
+fn oldest_incomplete_messages_are_evicted() {
+    let mut reassembler = Reassembler::new();
+    let messages: Vec<Vec<Fragment>> = (0..=MAX_REASSEMBLY_BUFFER_SIZE / MAX_MESSAGE_SIZE)
+        .map(|seed| fragment(&message(MAX_MESSAGE_SIZE, seed as u8)).unwrap())
+        .collect();
+    // All but the last fragment of every message, one message after the other
+    for fragments in &messages {
+        for fragment in &fragments[..fragments.len() - 1] {
+            assert_eq!(reassembler.insert(address(1), &fragment.to_bytes()).unwrap(), None);
+        }
+    }
+    assert!(reassembler.buffered() <= MAX_REASSEMBLY_BUFFER_SIZE);
+
+    // The first message was evicted, the last one is still complete
+    let first = messages.first().unwrap();
+    assert_eq!(reassembler.insert(address(1), &first.last().unwrap().to_bytes()).unwrap(), None);
+    let last = messages.last().unwrap();
+    assert_eq!(reassembler.insert(address(1), &last.last().unwrap().to_bytes()).unwrap(), Some(message(MAX_MESSAGE_SIZE, (messages.len() - 1) as u8)));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::time::Duration;
+
+use asynchronous_broadcast_protocols::{
+    limits::{Admission, Limits, PeerGuard, SequenceWindows}, reliable_broadcast::ReliableBroadcastMessage,
+    transport::ReliableLink, Config, Identifier, Message, MessageType,
+};
+
+
+fn limits() -> Limits {
+    serde_json::from_value(serde_json::json!({
+        "max_invalid_messages": 2,
+        "max_new_instances": 3,
+        "window_ms": 60_000,
+        "ban_duration_ms": 200,
+        "max_open_instances_per_sender": 2,
+        "sequence_window": 8,
+    })).unwrap()
+}
+
+
+fn config(my_id: u16, base_port: u16) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({
+        "protocol": "reliable_broadcast",
+        "limits": { "max_open_instances_per_sender": 4, "sequence_window": 64 },
+    }))
+}
+
+
+#[test]
+fn abusive_peers_are_banned_for_a_while() {
+    let mut guard = PeerGuard::new(limits());
+    assert!(!guard.record_invalid(1));
+    assert!(!guard.record_invalid(1));
+    assert!(guard.record_invalid(1));
+    assert!(guard.is_banned(1));
+    assert_eq!(guard.banned(), [1]);
+
+    // Other peers are not affected
+    for _ in 0..3 {
+        assert!(guard.record_instance_creation(2));
+    }
+    assert!(!guard.record_instance_creation(2));
+    assert_eq!(guard.banned(), [1, 2]);
+    assert!(!guard.is_banned(3));
+
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(!guard.is_banned(1));
+    assert!(guard.banned().is_empty());
+}
+
+
+#[test]
+fn instances_are_admitted_within_the_window() {
+    let mut windows = SequenceWindows::new(limits());
+    let id = |sequence| Identifier::new(1, sequence);
+
+    assert_eq!(windows.admit(id(0), 1), Admission::Admitted);
+    assert_eq!(windows.admit(id(8), 1), Admission::OutOfWindow);
+    windows.finish(id(0));
+    assert_eq!(windows.admit(id(0), 1), Admission::Stale);
+    // The window moves with the lowest unfinished sequence number
+    assert_eq!(windows.admit(id(8), 1), Admission::Admitted);
+    assert_eq!(windows.admit(id(9), 1), Admission::OutOfWindow);
+
+    // Instances finished out of order, or never opened here, move it once the gap closes
+    windows.skip(id(2));
+    assert_eq!(windows.admit(id(2), 1), Admission::Stale);
+    assert_eq!(windows.admit(id(10), 1), Admission::OutOfWindow);
+    windows.skip(id(1));
+    assert_eq!(windows.admit(id(10), 2), Admission::Admitted);
+}
+
+
+#[test]
+fn open_instances_are_counted_per_opener() {
+    let mut windows = SequenceWindows::new(limits());
+    let id = |sequence| Identifier::new(1, sequence);
+
+    // Node 3 opens node 1's future instances with echoes until its quota is used up
+    assert_eq!(windows.admit(id(5), 3), Admission::Admitted);
+    assert_eq!(windows.admit(id(6), 3), Admission::Admitted);
+    assert_eq!(windows.admit(id(7), 3), Admission::TooManyOpen);
+
+    // Node 1's own instances are still admitted, up to its own quota
+    assert_eq!(windows.admit(id(0), 1), Admission::Admitted);
+    assert_eq!(windows.admit(id(1), 1), Admission::Admitted);
+    assert_eq!(windows.admit(id(2), 1), Admission::TooManyOpen);
+    // Another node's messages may open it
+    assert_eq!(windows.admit(id(2), 2), Admission::Admitted);
+
+    // Finishing or releasing an instance frees a slot of the peer that opened it
+    windows.finish(id(0));
+    assert_eq!(windows.admit(id(3), 3), Admission::TooManyOpen);
+    assert_eq!(windows.admit(id(3), 1), Admission::Admitted);
+    windows.release(id(5));
+    assert_eq!(windows.admit(id(7), 3), Admission::Admitted);
+}
+
+
+#[tokio::test]
+async fn future_echoes_of_a_faulty_peer_do_not_block_a_sender() {
+    let base_port = 41300;
+    let faulty = ReliableLink::bind(&config(3, base_port)).await.unwrap();
+    // Keep reading, so that the link answers handshakes
+    let reader = faulty.clone();
+    tokio::spawn(async move { while reader.recv().await.is_ok() {} });
+    let nodes = common::start_all((0..3).map(|id| config(id, base_port))).await;
+    let mut rx = nodes[0].subscribe_deliveries();
+
+    // Node 3 opens node 1's instances 10 and up at node 0
+    let config = config(3, base_port);
+    for sequence in 10..20 {
+        let echo = ReliableBroadcastMessage::Echo([sequence as u8; 32]);
+        let message = Message::new_authenticated(Identifier::new(1, sequence), 3, MessageType::ReliableBroadcast(echo), &[4; 32], &config);
+        faulty.send(0, &message.to_bytes()).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    for sequence in 0..5u8 {
+        let id = nodes[1].broadcast(vec![sequence]).await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+            .expect("payload was not delivered")
+            .unwrap();
+        assert_eq!((delivery.id, delivery.payload), (id, vec![sequence]));
+    }
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::{collections::HashSet, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    erasure, merkle::{self, MerkleTree}, merkle_broadcast::MerkleBroadcastMessage,
+    node::{Delivery, Node}, Config, Identifier, Message, MessageType,
+};
+
+
+fn config(n: u16, my_id: u16, base_port: u16) -> Config {
+    common::config_with(&common::ids(n), my_id, base_port, serde_json::json!({ "protocol": "merkle_broadcast" }))
+}
+
+
+async fn start(n: u16, ids: std::ops::Range<u16>, base_port: u16) -> Vec<std::sync::Arc<Node>> {
+    common::start_all(ids.map(|id| config(n, id, base_port))).await
+}
+
+
+#[test]
+fn any_k_fragments_decode() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let shards = erasure::encode(&data, 3, 7).unwrap();
+    assert_eq!(shards.len(), 7);
+
+    for chosen in [[0, 1, 2], [4, 5, 6], [6, 0, 3], [1, 5, 2]] {
+        let subset: Vec<(usize, Vec<u8>)> = chosen.iter().map(|i| (*i, shards[*i].clone())).collect();
+        assert_eq!(erasure::decode(&subset, 3, 7).unwrap(), data);
+    }
+    let too_few: Vec<(usize, Vec<u8>)> = vec![(0, shards[0].clone()), (0, shards[0].clone()), (4, shards[4].clone())];
+    assert!(erasure::decode(&too_few, 3, 7).is_err());
+    assert_eq!(erasure::decode(&[(0, erasure::encode(&[], 1, 1).unwrap()[0].clone())], 1, 1).unwrap(), Vec::<u8>::new());
+}
+
+
+#[test]
+fn merkle_proofs_bind_index_and_leaf() {
+    let leaves: Vec<Vec<u8>> = (0..7u8).map(|i| vec![i; 10]).collect();
+    let tree = MerkleTree::new(&leaves);
+    for (i, leaf) in leaves.iter().enumerate() {
+        assert!(merkle::verify(&tree.root(), i, leaf, &tree.proof(i), leaves.len()));
+    }
+    assert!(!merkle::verify(&tree.root(), 1, &leaves[0], &tree.proof(0), leaves.len()));
+    assert!(!merkle::verify(&tree.root(), 2, &leaves[3], &tree.proof(2), leaves.len()));
+    assert!(!merkle::verify(&tree.root(), 6, &leaves[6], &tree.proof(6), 8));
+}
+
+
+#[tokio::test]
+async fn delivers_large_payloads_with_one_node_down() {
+    // n = 4, t = 1: node 3 never starts, the other three hold exactly the k = 3 fragments needed
+    let nodes = start(4, 0..3, 40000).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    let mut expected = HashSet::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let payload: Vec<u8> = (0..100_000u32).map(|b| (b as u8).wrapping_mul(i as u8 + 3)).collect();
+        let id = node.broadcast(payload.clone()).await.unwrap();
+        expected.insert(Delivery { id, payload });
+    }
+
+    for rx in &mut receivers {
+        let mut delivered = HashSet::new();
+        while delivered.len() < expected.len() {
+            let delivery = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await.unwrap().unwrap();
+            assert!(delivered.insert(delivery), "delivered twice");
+        }
+        assert_eq!(delivered, expected);
+    }
+}
+
+
+#[tokio::test]
+async fn node_without_proposal_decodes_fragments() {
+    let nodes = start(4, 0..4, 40010).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    // Node 0 proposes to everyone but node 3, which can only learn the payload from fragments
+    let payload = vec![0x5a; 20_000];
+    let sender = nodes[0].config();
+    let id = Identifier::new(0, 0);
+    let propose = Message::new(
+        sender.domain(),
+        id,
+        0,
+        MessageType::MerkleBroadcast(MerkleBroadcastMessage::Propose(payload.clone())),
+        &sender.get_my_node().unwrap().privkey,
+    ).to_bytes();
+    for target in 0..3 {
+        nodes[0].link().send(target, &propose).await.unwrap();
+    }
+
+    for rx in &mut receivers {
+        let delivery = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(delivery, Delivery { id, payload: payload.clone() });
+    }
+}
+
+
+#[test]
+fn needs_a_threshold_cluster() {
+    assert!(config(4, 0, 40000).validate().is_ok());
+
+    let weighted = serde_json::json!({
+        "my_id": 0,
+        "nodes": (0..4u16).map(|id| serde_json::json!({
+            "id": id,
+            "address": format!("127.0.0.1:{}", 40000 + id),
+            "privkey": vec![id as u8 + 1; 32],
+            "weight": id + 1,
+        })).collect::<Vec<_>>(),
+        "protocol": "merkle_broadcast",
+    });
+    let weighted: Config = serde_json::from_value(weighted).unwrap();
+    assert!(weighted.validate().is_err());
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use asynchronous_broadcast_protocols::{quorum::{HybridFaults, QuorumSystem}, Config, Error, Protocol};
+
+
+fn set(ids: &[u16]) -> HashSet<u16> {
+    ids.iter().copied().collect()
+}
+
+
+fn config(weights: &[u64]) -> Config {
+    let nodes: Vec<serde_json::Value> = weights.iter().enumerate()
+        .map(|(id, weight)| serde_json::json!({
+            "id": id,
+            "address": format!("127.0.0.1:{}", 39200 + id),
+            "privkey": vec![id as u8 + 1; 32],
+            "weight": weight,
+        }))
+        .collect();
+    serde_json::from_value(serde_json::json!({ "my_id": 0, "nodes": nodes })).unwrap()
+}
+
+
+/// Every subset of the nodes `0..n`
+fn subsets(n: u16) -> impl Iterator<Item = HashSet<u16>> {
+    (0u32..1 << n).map(move |mask| (0..n).filter(|id| mask & (1 << id) != 0).collect())
+}
+
+
+#[test]
+fn unit_weights_use_counts() {
+    let config = config(&[1, 1, 1, 1, 1, 1, 1]);
+    assert!(config.validate().is_ok());
+    let quorums = config.quorums();
+    assert_eq!(quorums, QuorumSystem::threshold(7, 2));
+
+    assert!(!quorums.is_echo_quorum(&set(&[0, 1, 2, 3])));
+    assert!(quorums.is_echo_quorum(&set(&[0, 1, 2, 3, 4])));
+    assert!(!quorums.is_ready_amplification(&set(&[0, 1])));
+    assert!(quorums.is_ready_amplification(&set(&[0, 1, 2])));
+    assert!(!quorums.is_delivery_quorum(&set(&[0, 1, 2, 3])));
+    assert!(quorums.is_delivery_quorum(&set(&[0, 1, 2, 3, 4])));
+}
+
+
+#[test]
+fn one_heavy_node() {
+    // Total 8: node 0 alone holds more than 1/3 but not 2/3
+    let config = config(&[5, 1, 1, 1]);
+    assert!(config.validate().is_ok());
+    let quorums = config.quorums();
+
+    assert!(quorums.is_ready_amplification(&set(&[0])));
+    assert!(!quorums.is_delivery_quorum(&set(&[0])));
+    assert!(quorums.is_delivery_quorum(&set(&[0, 1])));
+    assert!(quorums.is_echo_quorum(&set(&[0, 1])));
+
+    // The three light nodes together are 3/8: enough to amplify, never enough to deliver
+    assert!(quorums.is_ready_amplification(&set(&[1, 2, 3])));
+    assert!(!quorums.is_echo_quorum(&set(&[1, 2, 3])));
+    assert!(!quorums.is_delivery_quorum(&set(&[1, 2, 3])));
+}
+
+
+#[test]
+fn exact_thirds_are_not_enough() {
+    // Total 9: 3 is exactly 1/3 and 6 exactly 2/3, both must be exceeded
+    let quorums = QuorumSystem::weighted([(0, 3), (1, 3), (2, 2), (3, 1)]);
+    assert!(!quorums.is_ready_amplification(&set(&[0])));
+    assert!(quorums.is_ready_amplification(&set(&[2, 3, 1])));
+    assert!(!quorums.is_delivery_quorum(&set(&[0, 1])));
+    assert!(quorums.is_delivery_quorum(&set(&[0, 1, 3])));
+}
+
+
+#[test]
+fn unknown_voters_carry_no_weight() {
+    let quorums = QuorumSystem::weighted([(0, 1), (1, 1), (2, 4)]);
+    assert!(!quorums.is_ready_amplification(&set(&[0, 7, 8, 9])));
+}
+
+
+#[test]
+fn huge_weights_do_not_overflow() {
+    let config = config(&[u64::MAX, u64::MAX, u64::MAX, 1]);
+    assert!(config.validate().is_ok());
+    let quorums = config.quorums();
+    assert!(quorums.is_ready_amplification(&set(&[0, 3])));
+    assert!(!quorums.is_delivery_quorum(&set(&[0, 1])));
+    assert!(quorums.is_delivery_quorum(&set(&[0, 1, 3])));
+    assert!(quorums.is_echo_quorum(&set(&[0, 1, 2])));
+}
+
+
+#[test]
+fn weighted_quorums_intersect_in_an_honest_node() {
+    // Any two delivery quorums overlap in more than 1/3 of the weight, so in some
+    // node that is not Byzantine while Byzantine nodes hold less than 1/3
+    for weights in [&[5, 1, 1, 1][..], &[10, 3, 3, 2, 1, 1], &[1, 2, 4, 8, 16], &[7, 7, 1, 1, 1, 1, 1]] {
+        let quorums = config(weights).quorums();
+        let total: u64 = weights.iter().sum();
+        let weight = |s: &HashSet<u16>| s.iter().map(|id| weights[*id as usize]).sum::<u64>();
+
+        let quorum_sets: Vec<HashSet<u16>> = subsets(weights.len() as u16)
+            .filter(|s| quorums.is_delivery_quorum(s))
+            .collect();
+        for a in &quorum_sets {
+            for b in &quorum_sets {
+                let common: HashSet<u16> = a.intersection(b).copied().collect();
+                assert!(3 * weight(&common) > total, "{:?}: {:?} and {:?}", weights, a, b);
+            }
+        }
+    }
+}
+
+
+#[test]
+fn weights_are_validated() {
+    let mut config = config(&[2, 1, 1, 1]);
+    config.t = Some(1);
+    assert!(config.validate().is_err());
+    assert!(self::config(&[0, 0, 0, 0]).validate().is_err());
+}
+
+
+#[test]
+fn members_are_validated() {
+    let mut config = config(&[1, 1, 1, 1]);
+    config.nodes[3].id = 1;
+    assert!(matches!(config.validate(), Err(Error::DuplicateNodeId(1))));
+
+    let mut config = self::config(&[1, 1, 1, 1]);
+    config.nodes[3].privkey = config.nodes[1].privkey;
+    assert!(matches!(config.validate(), Err(Error::DuplicateKey(1, 3))));
+
+    let mut config = self::config(&[1, 1, 1, 1]);
+    config.nodes[3].address = config.nodes[1].address.clone();
+    assert!(matches!(config.validate(), Err(Error::DuplicateAddress(1, 3))));
+}
+
+
+#[test]
+fn huge_fault_counts_are_rejected() {
+    let mut config = config(&[1, 1, 1, 1]);
+    config.t = Some(usize::MAX);
+    let error = config.validate().unwrap_err();
+    assert!(matches!(error, Error::TooFewNodes { n: 4, t: usize::MAX }));
+    assert!(error.to_string().ends_with(&format!("at least {} are needed", 3 * usize::MAX as u128 + 1)));
+    config.protocol = Protocol::TwoStepBroadcast;
+    assert!(matches!(config.validate(), Err(Error::TwoStepTooFewNodes { n: 4, t: usize::MAX })));
+
+    let error = hybrid_config(4, usize::MAX, usize::MAX).validate().unwrap_err();
+    assert!(matches!(error, Error::TooFewNodesHybrid { n: 4, .. }));
+    assert!(error.to_string().ends_with(&format!("at least {} are needed", 5 * usize::MAX as u128 + 1)));
+}
+
+
+fn structure_config(n: usize, fail_prone_sets: &[&[u16]]) -> Config {
+    let mut config = config(&vec![1; n]);
+    config.fail_prone_sets = fail_prone_sets.iter().map(|set| set.to_vec()).collect();
+    config
+}
+
+
+#[test]
+fn provider_outage_is_tolerated() {
+    // Nodes 0, 1 and 2 share a hosting provider; any other node may fail alone.
+    // A threshold of t = 2 out of 7 cannot survive the provider going rogue.
+    let config = structure_config(7, &[&[0, 1, 2], &[3], &[4], &[5], &[6]]);
+    assert!(config.validate().is_ok());
+    let quorums = config.quorums();
+
+    assert!(quorums.is_echo_quorum(&set(&[3, 4, 5, 6])));
+    assert!(quorums.is_delivery_quorum(&set(&[0, 1, 2, 3, 4, 5])));
+    assert!(!quorums.is_delivery_quorum(&set(&[0, 1, 2, 3, 4])));
+
+    // The whole provider is not enough to make an honest node join in
+    assert!(!quorums.is_ready_amplification(&set(&[0, 1, 2])));
+    assert!(quorums.is_ready_amplification(&set(&[0, 3])));
+    assert!(quorums.is_ready_amplification(&set(&[3, 4])));
+}
+
+
+#[test]
+fn structure_quorums_intersect_outside_any_fail_prone_set() {
+    let sets: &[&[u16]] = &[&[0, 1, 2], &[3], &[4], &[5], &[6]];
+    let quorums = structure_config(7, sets).quorums();
+    let quorum_sets: Vec<HashSet<u16>> = subsets(7).filter(|s| quorums.is_delivery_quorum(s)).collect();
+    for a in &quorum_sets {
+        for b in &quorum_sets {
+            let common: HashSet<u16> = a.intersection(b).copied().collect();
+            assert!(quorums.is_ready_amplification(&common), "{:?} and {:?}", a, b);
+        }
+    }
+}
+
+
+#[test]
+fn structures_must_be_q3() {
+    assert!(structure_config(7, &[&[0, 1, 2], &[3, 4], &[5, 6]]).validate().is_err());
+    // The same set may appear more than once among the three
+    assert!(structure_config(4, &[&[0, 1], &[2, 3]]).validate().is_err());
+    assert!(structure_config(4, &[&[0], &[9]]).validate().is_err());
+    assert!(structure_config(4, &[&[0], &[1], &[2], &[3]]).validate().is_ok());
+}
+
+
+fn hybrid_config(n: usize, byzantine: usize, crash: usize) -> Config {
+    let mut config = config(&vec![1; n]);
+    config.hybrid = Some(HybridFaults { byzantine, crash });
+    config
+}
+
+
+#[test]
+fn hybrid_without_crash_faults_matches_threshold() {
+    for n in 1..=10u16 {
+        let t = (n as usize - 1) / 3;
+        let threshold = QuorumSystem::threshold(n as usize, t);
+        let hybrid = QuorumSystem::hybrid(n as usize, HybridFaults { byzantine: t, crash: 0 });
+        for voters in subsets(n) {
+            assert_eq!(threshold.is_echo_quorum(&voters), hybrid.is_echo_quorum(&voters));
+            assert_eq!(threshold.is_ready_amplification(&voters), hybrid.is_ready_amplification(&voters));
+            assert_eq!(threshold.is_delivery_quorum(&voters), hybrid.is_delivery_quorum(&voters));
+        }
+    }
+}
+
+
+#[test]
+fn hybrid_needs_fewer_replicas() {
+    // 1 Byzantine + 2 crash faults: 8 nodes suffice, where treating all three as
+    // Byzantine would need 10
+    assert!(hybrid_config(8, 1, 2).validate().is_ok());
+    assert!(hybrid_config(7, 1, 2).validate().is_err());
+    let mut byzantine = config(&[1; 8]);
+    byzantine.t = Some(3);
+    assert!(byzantine.validate().is_err());
+
+    // With three nodes down, the five live ones still form every quorum
+    let live = set(&[0, 1, 2, 3, 4]);
+    let hybrid = hybrid_config(8, 1, 2).quorums();
+    assert!(hybrid.is_echo_quorum(&live));
+    assert!(hybrid.is_delivery_quorum(&live));
+    // Four Readies may come from the Byzantine node and two crash-faulty ones that crash
+    // before anyone else hears them, leaving a single correct one, too few to amplify
+    assert!(!hybrid.is_delivery_quorum(&set(&[0, 1, 2, 3])));
+    // while the pure Byzantine thresholds for 8 nodes (t = 2) stall
+    assert!(!QuorumSystem::threshold(8, 2).is_echo_quorum(&live));
+}
+
+
+#[test]
+fn hybrid_echo_quorums_intersect_beyond_byzantine_nodes() {
+    for (b, c) in [(1, 2), (2, 1), (0, 3), (1, 1)] {
+        let n = 3 * b + 2 * c + 1;
+        let quorums = QuorumSystem::hybrid(n, HybridFaults { byzantine: b, crash: c });
+        let quorum_sets: Vec<HashSet<u16>> = subsets(n as u16).filter(|s| quorums.is_echo_quorum(s)).collect();
+        for a in &quorum_sets {
+            for other in &quorum_sets {
+                assert!(a.intersection(other).count() > b, "b={} c={}: {:?} and {:?}", b, c, a, other);
+            }
+        }
+        // Readies from a delivery quorum include enough from correct nodes to amplify,
+        // even if the crash-faulty senders among them crash before anyone else hears them
+        let delivery = subsets(n as u16).filter(|s| quorums.is_delivery_quorum(s)).map(|s| s.len()).min().unwrap();
+        assert!(delivery - b - c > b);
+        assert!(delivery <= n - b - c);
+    }
+}
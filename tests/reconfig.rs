@@ -0,0 +1,190 @@
+mod common;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    node::{Delivery, Node}, reconfig::{ConfigChange, SignedConfigChange}, Config, Error, Identifier,
+};
+use tokio::sync::broadcast;
+
+
+fn config(ids: &[u16], my_id: u16, base_port: u16, epoch: u64) -> Config {
+    common::config_with(ids, my_id, base_port, serde_json::json!({ "epoch": epoch }))
+}
+
+
+async fn start(ids: &[u16], running: &[u16], base_port: u16, epoch: u64) -> Vec<Arc<Node>> {
+    common::start_all(running.iter().map(|&id| config(ids, id, base_port, epoch))).await
+}
+
+
+/// Reconfigure every node to `ids` at the next epoch
+async fn reconfigure(nodes: &[Arc<Node>], ids: &[u16], base_port: u16) -> Vec<Result<u64, Error>> {
+    let handles: Vec<_> = nodes.iter().map(|node| {
+        let node = node.clone();
+        let config = config(ids, node.config().my_id, base_port, node.epoch() + 1);
+        tokio::spawn(async move { node.reconfigure(config).await })
+    }).collect();
+    let mut results = Vec::new();
+    for handle in handles {
+        results.push(tokio::time::timeout(Duration::from_secs(20), handle).await
+            .expect("reconfiguration did not finish")
+            .unwrap());
+    }
+    results
+}
+
+
+async fn wait_for(rx: &mut broadcast::Receiver<Delivery>, id: Identifier) -> Vec<u8> {
+    wait_for_all(rx, &[id]).await.remove(&id).unwrap()
+}
+
+
+/// The payloads of instances `ids`, delivered in any order
+async fn wait_for_all(rx: &mut broadcast::Receiver<Delivery>, ids: &[Identifier]) -> HashMap<Identifier, Vec<u8>> {
+    tokio::time::timeout(Duration::from_secs(20), async {
+        let mut payloads = HashMap::new();
+        while payloads.len() < ids.len() {
+            let delivery = rx.recv().await.unwrap();
+            if ids.contains(&delivery.id) {
+                payloads.insert(delivery.id, delivery.payload);
+            }
+        }
+        payloads
+    }).await.expect("payload was not delivered")
+}
+
+
+#[test]
+fn commands_are_bound_to_the_issuer_and_epoch() {
+    let current = config(&[0, 1, 2, 3], 1, 40600, 0);
+    let next = config(&[0, 1, 2, 3, 4], 1, 40600, 1);
+    let command = SignedConfigChange::sign(ConfigChange::of(&next), &current).unwrap();
+    assert!(command.verify(&current));
+    assert_eq!(SignedConfigChange::from_bytes(&command.to_bytes()).unwrap(), command);
+
+    // Not a command for the epoch after the next one
+    assert!(!command.verify(&next));
+    let mut forged = command;
+    forged.issuer = 2;
+    assert!(!forged.verify(&current));
+    let mut forged = command;
+    forged.change.cluster_id[0] ^= 1;
+    assert!(!forged.verify(&current));
+}
+
+
+#[tokio::test]
+async fn adds_a_node_at_the_next_epoch() {
+    let base_port = 40610;
+    let mut nodes = start(&[0, 1, 2, 3], &[0, 1, 2, 3], base_port, 0).await;
+    let joining = start(&[0, 1, 2, 3, 4], &[4], base_port, 1).await;
+
+    for result in reconfigure(&nodes, &[0, 1, 2, 3, 4], base_port).await {
+        assert_eq!(result.unwrap(), 1);
+    }
+    nodes.extend(joining);
+    for node in &nodes {
+        assert_eq!(node.epoch(), 1);
+        assert_eq!(node.config().nodes.len(), 5);
+    }
+
+    // The new node broadcasts to the old ones and the other way round
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+    let from_new = nodes[4].broadcast(b"from 4".to_vec()).await.unwrap();
+    let from_old = nodes[0].broadcast(b"from 0".to_vec()).await.unwrap();
+    for rx in &mut receivers {
+        let payloads = wait_for_all(rx, &[from_new, from_old]).await;
+        assert_eq!(payloads[&from_new], b"from 4");
+        assert_eq!(payloads[&from_old], b"from 0");
+    }
+}
+
+
+#[tokio::test]
+async fn removed_node_finishes_old_instances() {
+    let base_port = 40620;
+    let nodes = start(&[0, 1, 2, 3, 4], &[0, 1, 2, 3, 4], base_port, 0).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    // Started in epoch 0, among all five nodes
+    let old = nodes[1].broadcast(b"old".to_vec()).await.unwrap();
+    for result in reconfigure(&nodes[..4], &[0, 1, 2, 3], base_port).await {
+        assert_eq!(result.unwrap(), 1);
+    }
+    for rx in &mut receivers {
+        assert_eq!(wait_for(rx, old).await, b"old");
+    }
+
+    // Epoch 1 goes on without node 4
+    let new = nodes[0].broadcast(b"new".to_vec()).await.unwrap();
+    for rx in &mut receivers[..4] {
+        assert_eq!(wait_for(rx, new).await, b"new");
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(receivers[4].try_recv().is_err());
+    assert_eq!(nodes[4].epoch(), 0);
+}
+
+
+#[tokio::test]
+async fn concurrent_changes_are_ordered() {
+    let base_port = 40630;
+    let nodes = start(&[0, 1, 2, 3], &[0, 1, 2, 3], base_port, 0).await;
+
+    let skipping = config(&[0, 1, 2, 3], 0, base_port, 2);
+    assert!(matches!(nodes[0].reconfigure(skipping).await, Err(Error::EpochMismatch { got: 2, expected: 1 })));
+
+    // Nodes 0 and 1 add node 4, nodes 2 and 3 add node 5
+    let (first, second) = tokio::join!(
+        reconfigure(&nodes[..2], &[0, 1, 2, 3, 4], base_port),
+        reconfigure(&nodes[2..], &[0, 1, 2, 3, 5], base_port),
+    );
+    let results: Vec<_> = first.into_iter().chain(second).collect();
+    let installed: Vec<usize> = (0..4).filter(|&i| results[i].is_ok()).collect();
+    assert!(installed == [0, 1] || installed == [2, 3], "installed at {:?}", installed);
+    for (node, result) in nodes.iter().zip(&results) {
+        match result {
+            Ok(epoch) => assert_eq!(*epoch, 1),
+            Err(e) => {
+                assert!(matches!(e, Error::EpochTaken(1)));
+                assert_eq!(node.epoch(), 0);
+            }
+        }
+    }
+
+    // The others follow once given the agreed config
+    let winner = if installed == [0, 1] { [0, 1, 2, 3, 4] } else { [0, 1, 2, 3, 5] };
+    let others: Vec<Arc<Node>> = nodes.iter().filter(|node| node.epoch() == 0).cloned().collect();
+    for result in reconfigure(&others, &winner, base_port).await {
+        assert_eq!(result.unwrap(), 1);
+    }
+    for node in &nodes {
+        assert_eq!(node.config().cluster_id(), nodes[installed[0]].config().cluster_id());
+    }
+}
+
+
+#[tokio::test]
+async fn one_member_cannot_move_the_cluster() {
+    let base_port = 40640;
+    let nodes = start(&[0, 1, 2, 3], &[0, 1, 2, 3], base_port, 0).await;
+    let next = |node: &Arc<Node>| config(&[0, 1, 2, 3, 4], node.config().my_id, base_port, 1);
+
+    // Nobody else was given the config node 0 asks for: t = 1, so one command is not enough
+    let node = nodes[0].clone();
+    let config = next(&node);
+    let first = tokio::spawn(async move { node.reconfigure(config).await });
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(!first.is_finished());
+    assert!(nodes.iter().all(|node| node.epoch() == 0));
+
+    // t+1 commands move it, also at members that were not given the config yet
+    let node = nodes[1].clone();
+    let config = next(&node);
+    assert_eq!(tokio::time::timeout(Duration::from_secs(20), node.reconfigure(config)).await.unwrap().unwrap(), 1);
+    assert_eq!(tokio::time::timeout(Duration::from_secs(20), first).await.unwrap().unwrap().unwrap(), 1);
+    for result in reconfigure(&nodes[2..], &[0, 1, 2, 3, 4], base_port).await {
+        assert_eq!(result.unwrap(), 1);
+    }
+}
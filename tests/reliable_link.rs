@@ -0,0 +1,175 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{transport::{fragment, Frame, ReliableLink}, Config};
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+
+fn config(my_id: u16, base_port: u16, authentication: &str) -> Config {
+    common::config_with(&common::ids(2), my_id, base_port, serde_json::json!({
+        "t": 0,
+        "authentication": authentication,
+        "protocol": "crash_broadcast",
+    }))
+}
+
+
+/// Send `frame` as a single datagram to node `to` from a socket that is no link
+async fn inject(socket: &UdpSocket, frame: Frame, base_port: u16, to: u16) {
+    for fragment in fragment(&frame.to_bytes()).unwrap() {
+        socket.send_to(&fragment.to_bytes(), ("127.0.0.1", base_port + to)).await.unwrap();
+    }
+}
+
+
+async fn recv(link: &ReliableLink, wait: Duration) -> Option<(u16, Vec<u8>)> {
+    tokio::time::timeout(wait, link.recv()).await.ok().map(|received| received.unwrap())
+}
+
+
+/// Keep `link` acknowledging in the background, until the task is aborted
+fn serve(link: Arc<ReliableLink>) -> JoinHandle<()> {
+    tokio::spawn(async move { while link.recv().await.is_ok() {} })
+}
+
+
+#[tokio::test]
+async fn lost_frames_are_retransmitted() {
+    let base_port = 41000;
+    let sender = ReliableLink::bind(&config(0, base_port, "signatures")).await.unwrap();
+    serve(sender.clone());
+    // Node 1 is not listening yet, so the first transmissions are lost
+    sender.send(1, b"first").await.unwrap();
+    sender.send(1, b"second").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let receiver = ReliableLink::bind(&config(1, base_port, "signatures")).await.unwrap();
+    let mut received = vec![
+        recv(&receiver, Duration::from_secs(5)).await.unwrap(),
+        recv(&receiver, Duration::from_secs(5)).await.unwrap(),
+    ];
+    received.sort();
+    assert_eq!(received, [(0, b"first".to_vec()), (0, b"second".to_vec())]);
+    assert_eq!(recv(&receiver, Duration::from_millis(500)).await, None);
+}
+
+
+#[tokio::test]
+async fn duplicates_are_delivered_once() {
+    let base_port = 41010;
+    let receiver = ReliableLink::bind(&config(1, base_port, "none")).await.unwrap();
+    // A bare socket at node 0's address sends the same frame twice, as after a lost ACK
+    let socket = UdpSocket::bind(("127.0.0.1", base_port)).await.unwrap();
+    let data = |seq, payload: &[u8]| Frame::Data { from: 0, session: 7, seq, payload: payload.to_vec() };
+    for _ in 0..2 {
+        inject(&socket, data(0, b"once"), base_port, 1).await;
+    }
+    inject(&socket, data(1, b"next"), base_port, 1).await;
+    assert_eq!(recv(&receiver, Duration::from_secs(2)).await, Some((0, b"once".to_vec())));
+    assert_eq!(recv(&receiver, Duration::from_secs(2)).await, Some((0, b"next".to_vec())));
+    assert_eq!(recv(&receiver, Duration::from_millis(300)).await, None);
+
+    // Every copy is acknowledged
+    let mut acks = Vec::new();
+    let mut buffer = [0; 64];
+    while let Ok(Ok((len, _))) = tokio::time::timeout(Duration::from_millis(300), socket.recv_from(&mut buffer)).await {
+        if let Ok(Frame::Ack { seq, .. }) = Frame::from_bytes(&buffer[12..len]) {
+            acks.push(seq);
+        }
+    }
+    acks.sort();
+    assert_eq!(acks, [0, 0, 1]);
+
+    // A frame of an earlier session of node 0 is dropped
+    inject(&socket, Frame::Data { from: 0, session: 6, seq: 5, payload: b"stale".to_vec() }, base_port, 1).await;
+    assert_eq!(recv(&receiver, Duration::from_millis(300)).await, None);
+}
+
+
+#[tokio::test]
+async fn restarted_peers_start_over() {
+    for (base_port, authentication) in [(41020, "signatures"), (41030, "session_mac")] {
+        let receiver = ReliableLink::bind(&config(1, base_port, authentication)).await.unwrap();
+        let sender = ReliableLink::bind(&config(0, base_port, authentication)).await.unwrap();
+        let task = serve(sender.clone());
+        sender.send(1, b"before").await.unwrap();
+        assert_eq!(recv(&receiver, Duration::from_secs(5)).await, Some((0, b"before".to_vec())));
+        task.abort();
+        let _ = task.await;
+        drop(sender);
+
+        // The new run sends from sequence number 0 again, which is not a duplicate
+        let sender = ReliableLink::bind(&config(0, base_port, authentication)).await.unwrap();
+        serve(sender.clone());
+        sender.send(1, b"after").await.unwrap();
+        assert_eq!(recv(&receiver, Duration::from_secs(5)).await, Some((0, b"after".to_vec())));
+    }
+}
+
+
+#[tokio::test]
+async fn forged_headers_are_dropped() {
+    let base_port = 41040;
+    let receiver = ReliableLink::bind(&config(1, base_port, "signatures")).await.unwrap();
+
+    // A frame from node 0's address claiming a later run of node 0 must not reset its window
+    let spoofer = UdpSocket::bind(("127.0.0.1", base_port)).await.unwrap();
+    inject(&spoofer, Frame::Data { from: 0, session: u64::MAX, seq: 0, payload: b"forged".to_vec() }, base_port, 1).await;
+    assert_eq!(recv(&receiver, Duration::from_millis(300)).await, None);
+    drop(spoofer);
+
+    let sender = ReliableLink::bind(&config(0, base_port, "signatures")).await.unwrap();
+    serve(sender.clone());
+    sender.send(1, b"genuine").await.unwrap();
+    assert_eq!(recv(&receiver, Duration::from_secs(5)).await, Some((0, b"genuine".to_vec())));
+    drop(receiver);
+
+    // Nor can an ACK from node 1's address that echoes the frame's header cancel its retransmission
+    let spoofer = UdpSocket::bind(("127.0.0.1", base_port + 1)).await.unwrap();
+    sender.send(1, b"retransmitted").await.unwrap();
+    let mut buffer = [0; 2048];
+    loop {
+        let (len, _) = tokio::time::timeout(Duration::from_secs(2), spoofer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        if let Ok(Frame::Data { session, seq, .. }) = Frame::from_bytes(&buffer[12..len]) {
+            inject(&spoofer, Frame::Ack { from: 1, session, seq }, base_port, 0).await;
+            break;
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    drop(spoofer);
+
+    let receiver = ReliableLink::bind(&config(1, base_port, "signatures")).await.unwrap();
+    assert_eq!(recv(&receiver, Duration::from_secs(5)).await, Some((0, b"retransmitted".to_vec())));
+}
+
+
+#[tokio::test]
+async fn peers_follow_address_and_key_changes() {
+    // Node 1 moves to another address with another key
+    let moved = |my_id: u16, base_port: u16| {
+        let mut config = config(my_id, base_port, "session_mac");
+        config.nodes[1].address = format!("127.0.0.1:{}", base_port + 2);
+        config.nodes[1].privkey = [9; 32];
+        config
+    };
+    let base_port = 41050;
+    let link = ReliableLink::bind(&config(0, base_port, "session_mac")).await.unwrap();
+    let old = ReliableLink::bind(&config(1, base_port, "session_mac")).await.unwrap();
+    serve(old.clone());
+    link.send(1, b"before").await.unwrap();
+    assert_eq!(recv(&link, Duration::from_millis(500)).await, None);
+
+    link.update_peers(&moved(0, base_port)).await;
+    let new = ReliableLink::bind(&moved(1, base_port)).await.unwrap();
+    link.send(1, b"after").await.unwrap();
+    new.send(0, b"from the new address").await.unwrap();
+    let (at_new, at_link) = tokio::join!(recv(&new, Duration::from_secs(5)), recv(&link, Duration::from_secs(5)));
+    assert_eq!(at_new, Some((0, b"after".to_vec())));
+    assert_eq!(at_link, Some((1, b"from the new address".to_vec())));
+    serve(new.clone());
+
+    // The old key no longer authenticates node 1
+    old.send(0, b"stale").await.unwrap();
+    assert_eq!(recv(&link, Duration::from_millis(500)).await, None);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::time::Duration;
+
+use asynchronous_broadcast_protocols::{
+    constants::*, node::Node, reliable_broadcast::ReliableBroadcastMessage, transport::ReliableLink,
+    wal::{FsyncPolicy, Record, Wal, WalConfig}, Config, Error, Identifier, Message, MessageType,
+};
+use ed25519::signature::Signer;
+
+
+const PRIVKEYS: [[u8; 32]; 4] = [[1; 32], [2; 32], [3; 32], [4; 32]];
+
+
+fn config(my_id: u16) -> Config {
+    let nodes: Vec<serde_json::Value> = PRIVKEYS.iter().enumerate()
+        .map(|(id, privkey)| serde_json::json!({
+            "id": id,
+            "address": format!("127.0.0.1:{}", 39100 + id),
+            "privkey": privkey,
+        }))
+        .collect();
+    serde_json::from_value(serde_json::json!({ "my_id": my_id, "nodes": nodes })).unwrap()
+}
+
+
+/// A signed wire message carrying the retired BROADCAST type (RBC message type 0)
+fn wire_broadcast(config: &Config, sender: u16, target: (u16, u64), payload: &[u8]) -> Vec<u8> {
+    let domain = config.domain();
+    let mut bytes = vec![domain.version];
+    bytes.extend_from_slice(&domain.cluster_id);
+    bytes.extend_from_slice(&domain.epoch.to_be_bytes());
+    bytes.extend_from_slice(&target.0.to_be_bytes());
+    bytes.extend_from_slice(&target.1.to_be_bytes());
+    bytes.extend_from_slice(&sender.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+    bytes.extend_from_slice(payload);
+
+    let mut signed = b"asynchronous_broadcast_protocols/message".to_vec();
+    signed.extend_from_slice(&bytes);
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&PRIVKEYS[sender as usize]);
+    bytes.extend_from_slice(&signing_key.sign(&signed).to_bytes());
+    assert_eq!(bytes.len(), MIN_MESSAGE_SIZE + 2 + payload.len());
+    bytes
+}
+
+
+#[tokio::test]
+async fn remote_broadcast_does_not_trigger_send() {
+    let node = Node::start(config(0)).await.unwrap();
+    tokio::spawn(node.clone().run());
+
+    let peer_config = config(1);
+    let peer = ReliableLink::bind(&peer_config).await.unwrap();
+
+    // Ask node 0 to start an instance in its own name
+    let forged = wire_broadcast(&peer_config, 1, (0, 0), b"forged");
+    assert!(Message::from_bytes(&forged).is_err());
+    peer.send(0, &forged).await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(1), peer.recv()).await;
+    assert!(received.is_err(), "node 0 reacted to a remote BROADCAST: {:?}", received);
+
+    // A local submission still starts an instance
+    let id = node.broadcast(b"genuine".to_vec()).await.unwrap();
+    let (from, bytes) = tokio::time::timeout(Duration::from_secs(5), peer.recv()).await.unwrap().unwrap();
+    let message = Message::from_bytes(&bytes).unwrap();
+    assert_eq!(from, 0);
+    assert_eq!(message.id, id);
+    assert!(matches!(
+        message.payload,
+        MessageType::ReliableBroadcast(ReliableBroadcastMessage::Send(ref m)) if m == b"genuine"
+    ));
+}
+
+
+#[tokio::test]
+async fn oversized_payloads_take_no_sequence_number() {
+    let path = std::env::temp_dir().join(format!("remote-broadcast-{}.wal", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = common::config_with(&common::ids(4), 0, 39110, serde_json::json!({ "wal": { "path": path, "fsync": "always" } }));
+    let node = common::start(config).await;
+
+    let too_large = node.broadcast(vec![0; MAX_BROADCAST_PAYLOAD_SIZE + 1]).await;
+    assert!(matches!(too_large, Err(Error::PayloadTooLarge { .. })));
+    // The largest payload still fits into a link frame, and goes out under the number the other one did not take
+    assert_eq!(node.broadcast(vec![0; MAX_BROADCAST_PAYLOAD_SIZE]).await.unwrap(), Identifier::new(0, 0));
+
+    let wal = WalConfig { path: path.to_str().unwrap().to_string(), fsync: FsyncPolicy::Always, fsync_interval_ms: 0 };
+    let (_, records) = Wal::open(&wal).unwrap();
+    let logged: Vec<Identifier> = records.iter()
+        .filter_map(|record| match record { Record::Broadcast { id, .. } => Some(*id), _ => None })
+        .collect();
+    assert_eq!(logged, [Identifier::new(0, 0)]);
+}
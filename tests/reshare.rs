@@ -0,0 +1,124 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{dkg::KeyShare, node::Node, polynomial, reshare::ReshareProgress, Config};
+use bls12_381::{G1Projective, Scalar};
+use ff::Field;
+use rand_core::OsRng;
+use tokio::sync::watch;
+
+
+fn config(ids: &[u16], my_id: u16, base_port: u16) -> Config {
+    common::config_with(ids, my_id, base_port, serde_json::json!({}))
+}
+
+
+async fn start(n: u16, base_port: u16) -> Vec<Arc<Node>> {
+    common::start_all((0..n).map(|id| config(&common::ids(n), id, base_port))).await
+}
+
+
+async fn finish(mut progress: watch::Receiver<ReshareProgress>) -> ReshareProgress {
+    tokio::time::timeout(Duration::from_secs(30), progress.wait_for(|progress| {
+        matches!(progress, ReshareProgress::Done | ReshareProgress::Failed(_))
+    })).await
+        .expect("resharing did not finish")
+        .unwrap()
+        .clone()
+}
+
+
+/// The secret behind t+1 shares
+fn combine(keys: &[KeyShare], config: &Config) -> Scalar {
+    let points: Vec<(Scalar, Scalar)> = keys.iter()
+        .map(|key| (polynomial::node_point(config, key.node).unwrap(), key.share))
+        .collect();
+    polynomial::interpolate(&points, Scalar::zero())
+}
+
+
+#[tokio::test]
+async fn refresh_keeps_the_public_key() {
+    let nodes = start(4, 40400).await;
+    let handles: Vec<_> = nodes.iter().map(|node| {
+        let node = node.clone();
+        tokio::spawn(async move { node.generate_key(0).await })
+    }).collect();
+    for handle in handles {
+        tokio::time::timeout(Duration::from_secs(30), handle).await
+            .expect("key generation did not finish")
+            .unwrap()
+            .unwrap();
+    }
+    let old: Vec<KeyShare> = nodes.iter().map(|node| node.key().unwrap()).collect();
+
+    let progress: Vec<_> = nodes.iter().map(|node| node.refresh(0).unwrap()).collect();
+    for progress in progress {
+        assert_eq!(finish(progress).await, ReshareProgress::Done);
+    }
+
+    let config = nodes[0].config();
+    let new: Vec<KeyShare> = nodes.iter().map(|node| node.key().unwrap()).collect();
+    for (node, (old, new)) in nodes.iter().zip(old.iter().zip(&new)) {
+        assert_eq!(new.public_key(), old.public_key());
+        assert_eq!(new.commitment, nodes[0].key().unwrap().commitment);
+        assert_ne!(new.share, old.share);
+        new.verify(&node.config()).unwrap();
+    }
+    assert_eq!(G1Projective::generator() * combine(&new[..2], &config), old[0].public_key());
+    // Old and new shares lie on different polynomials
+    assert_ne!(combine(&[old[0].clone(), new[1].clone()], &config), combine(&new[2..], &config));
+
+    // A session runs once
+    assert!(nodes[0].refresh(0).is_err());
+}
+
+
+#[tokio::test]
+async fn reshare_to_a_new_node_set() {
+    // Nodes 0-3 hand the key over to nodes 2-6
+    let nodes = start(7, 40410).await;
+    let old_ids: Vec<u16> = (0..4).collect();
+    let new_ids: Vec<u16> = (2..7).collect();
+    let old_config = config(&old_ids, 0, 40410);
+    let new_config = config(&new_ids, 2, 40410);
+
+    let secret = Scalar::random(OsRng);
+    for key in KeyShare::deal(&old_config, secret) {
+        key.verify(&old_config).unwrap();
+        nodes[key.node as usize].install_key(key);
+    }
+    let public_key = G1Projective::generator() * secret;
+
+    let progress: Vec<_> = nodes.iter()
+        .map(|node| node.reshare(3, &old_config, &new_config).unwrap())
+        .collect();
+    for progress in progress {
+        assert_eq!(finish(progress).await, ReshareProgress::Done);
+    }
+
+    let new: Vec<KeyShare> = new_ids.iter().map(|&id| nodes[id as usize].key().unwrap()).collect();
+    for key in &new {
+        assert_eq!(key.public_key(), public_key);
+        assert_eq!(key.threshold(), 1);
+        key.verify(&new_config).unwrap();
+    }
+    assert_eq!(combine(&new[..2], &new_config), secret);
+    assert_eq!(combine(&new[3..], &new_config), secret);
+
+    // Leaving nodes erased their old share
+    assert!(nodes[0].key().is_none());
+    assert!(nodes[1].key().is_none());
+}
+
+
+#[tokio::test]
+async fn fails_without_a_key() {
+    let nodes = start(4, 40430).await;
+    let progress = nodes[0].refresh(0).unwrap();
+    assert_eq!(finish(progress).await, ReshareProgress::Failed("no key share installed".into()));
+
+    let outsider = config(&[0, 1, 2, 9], 0, 40430);
+    assert!(nodes[1].reshare(1, &nodes[1].config(), &outsider).is_err());
+}
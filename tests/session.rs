@@ -0,0 +1,104 @@
+mod common;
+
+use asynchronous_broadcast_protocols::{transport::SessionAuth, Config};
+
+
+fn config(my_id: u16) -> Config {
+    common::config_with(&common::ids(4), my_id, 41100, serde_json::json!({ "authentication": "session_mac" }))
+}
+
+
+/// Let `a` (node `a_id`, run `a_session`) and `b` learn each other's key
+fn connect(a: &SessionAuth, a_id: u16, a_session: u64, b: &SessionAuth, b_id: u16, b_session: u64) {
+    let (public, signature) = a.handshake(a_id, a_session);
+    assert!(b.accept_handshake(b_id, a_id, a_session, &public, &signature));
+    let (public, signature) = b.handshake(b_id, b_session);
+    assert!(a.accept_handshake(a_id, b_id, b_session, &public, &signature));
+}
+
+
+#[test]
+fn handshake_derives_a_shared_key() {
+    let a = SessionAuth::new(&config(0)).unwrap();
+    let b = SessionAuth::new(&config(1)).unwrap();
+    assert!(!a.has_key(1));
+    assert_eq!(a.seal(1, b"frame"), None);
+
+    connect(&a, 0, 10, &b, 1, 20);
+    assert!(a.has_key(1) && b.has_key(0));
+    let sealed = a.seal(1, b"frame").unwrap();
+    assert_eq!(b.open(0, &sealed), Some(&b"frame"[..]));
+    let sealed = b.seal(0, b"reply").unwrap();
+    assert_eq!(a.open(1, &sealed), Some(&b"reply"[..]));
+
+    // The same handshake again changes nothing
+    let (public, signature) = a.handshake(0, 10);
+    assert!(!b.accept_handshake(1, 0, 10, &public, &signature));
+    assert_eq!(b.open(0, &a.seal(1, b"frame").unwrap()), Some(&b"frame"[..]));
+}
+
+
+#[test]
+fn forged_handshakes_are_rejected() {
+    let a = SessionAuth::new(&config(0)).unwrap();
+    let b = SessionAuth::new(&config(1)).unwrap();
+    let (public, signature) = a.handshake(0, 10);
+
+    // Claimed by another node, for another run, or with a changed key or signature
+    assert!(!b.accept_handshake(1, 2, 10, &public, &signature));
+    assert!(!b.accept_handshake(1, 0, 11, &public, &signature));
+    let mut other_public = public;
+    other_public[0] ^= 1;
+    assert!(!b.accept_handshake(1, 0, 10, &other_public, &signature));
+    let mut other_signature = signature;
+    other_signature[0] ^= 1;
+    assert!(!b.accept_handshake(1, 0, 10, &public, &other_signature));
+    // From a node outside the cluster
+    assert!(!b.accept_handshake(1, 9, 10, &public, &signature));
+    assert!(!b.has_key(0) && !b.has_key(2) && !b.has_key(9));
+}
+
+
+#[test]
+fn frames_need_the_mac_of_their_link() {
+    let a = SessionAuth::new(&config(0)).unwrap();
+    let b = SessionAuth::new(&config(1)).unwrap();
+    let c = SessionAuth::new(&config(2)).unwrap();
+    connect(&a, 0, 10, &b, 1, 20);
+    connect(&c, 2, 30, &b, 1, 20);
+
+    let sealed = a.seal(1, b"frame").unwrap();
+    // A changed frame or tag, a truncated frame, or the tag of another link
+    let mut changed = sealed.clone();
+    changed[0] ^= 1;
+    assert_eq!(b.open(0, &changed), None);
+    let mut changed = sealed.clone();
+    *changed.last_mut().unwrap() ^= 1;
+    assert_eq!(b.open(0, &changed), None);
+    assert_eq!(b.open(0, &sealed[..sealed.len() - 1]), None);
+    assert_eq!(b.open(0, &sealed[..4]), None);
+    assert_eq!(b.open(2, &sealed), None);
+    assert_eq!(b.open(0, &c.seal(1, b"frame").unwrap()), None);
+    // No key with node 3 yet
+    assert_eq!(b.open(3, &sealed), None);
+}
+
+
+#[test]
+fn restarted_peers_get_a_new_key() {
+    let a = SessionAuth::new(&config(0)).unwrap();
+    let b = SessionAuth::new(&config(1)).unwrap();
+    connect(&a, 0, 10, &b, 1, 20);
+    let old = a.seal(1, b"old").unwrap();
+    let (old_public, old_signature) = a.handshake(0, 10);
+
+    // Node 0 restarts with a fresh key
+    let a = SessionAuth::new(&config(0)).unwrap();
+    connect(&a, 0, 11, &b, 1, 20);
+    assert_eq!(b.open(0, &old), None);
+    assert_eq!(b.open(0, &a.seal(1, b"new").unwrap()), Some(&b"new"[..]));
+
+    // A replayed handshake of the earlier run does not bring the old key back
+    assert!(!b.accept_handshake(1, 0, 10, &old_public, &old_signature));
+    assert_eq!(b.open(0, &old), None);
+}
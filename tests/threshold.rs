@@ -0,0 +1,109 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{dkg::KeyShare, node::Node, threshold::{self, RecentSignatures, Signature}, Identifier};
+use bls12_381::Scalar;
+use ff::Field;
+use rand_core::OsRng;
+
+use common::{config, start_with_key};
+
+
+async fn start(n: u16, base_port: u16) -> Vec<Arc<Node>> {
+    let keys = KeyShare::deal(&config(n, 0, base_port), Scalar::random(OsRng));
+    let mut nodes = Vec::new();
+    for (id, key) in (0..n).zip(keys) {
+        nodes.push(start_with_key(config(n, id, base_port), key).await);
+    }
+    nodes
+}
+
+
+#[test]
+fn any_shares_combine_to_the_same_signature() {
+    let config = config(4, 0, 40500);
+    let keys = KeyShare::deal(&config, Scalar::random(OsRng));
+    let message = b"transfer 10 coins";
+
+    let shares: Vec<_> = keys.iter().map(|key| threshold::sign_share(key, message)).collect();
+    for share in &shares {
+        assert!(threshold::verify_share(&keys[0], share, message, &config));
+        assert!(!threshold::verify_share(&keys[0], share, b"transfer 1000 coins", &config));
+    }
+    let signature = threshold::combine(&shares[..2], 1, &config).unwrap();
+    assert_eq!(threshold::combine(&shares[2..], 1, &config), Some(signature));
+    assert!(threshold::verify(&signature, &keys[0].public_key(), message));
+    assert!(!threshold::verify(&signature, &keys[0].public_key(), b"transfer 1000 coins"));
+    assert_eq!(Signature::from_bytes(&signature.to_bytes()).unwrap(), signature);
+
+    // One node's share is not enough, however often it is sent
+    assert_eq!(threshold::combine(&[shares[0], shares[0]], 1, &config), None);
+
+    // A share claimed for another node does not verify
+    let mut forged = shares[1];
+    forged.node = 2;
+    assert!(!threshold::verify_share(&keys[0], &forged, message, &config));
+}
+
+
+#[tokio::test]
+async fn cluster_signs_once_enough_nodes_approve() {
+    let nodes = start(4, 40510).await;
+    let id = Identifier::new(0, 1);
+    let payload = b"block 1".to_vec();
+
+    // A single approval does not make a signature
+    let first = {
+        let node = nodes[0].clone();
+        let payload = payload.clone();
+        tokio::spawn(async move { node.sign(id, &payload).await })
+    };
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!first.is_finished());
+
+    let signature = tokio::time::timeout(Duration::from_secs(10), nodes[1].sign(id, &payload)).await
+        .expect("signing did not finish")
+        .unwrap();
+    let first = tokio::time::timeout(Duration::from_secs(10), first).await
+        .expect("signing did not finish")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first, signature);
+    assert!(threshold::verify(&signature, &nodes[0].key().unwrap().public_key(), &payload));
+
+    // A late approver gets the same signature
+    let late = tokio::time::timeout(Duration::from_secs(10), nodes[3].sign(id, &payload)).await
+        .expect("signing did not finish")
+        .unwrap();
+    assert_eq!(late, signature);
+}
+
+
+#[tokio::test]
+async fn approvals_of_different_payloads_do_not_combine() {
+    let nodes = start(4, 40520).await;
+    let id = Identifier::new(1, 1);
+
+    let handles: Vec<_> = nodes[..2].iter().zip([b"yes".to_vec(), b"no".to_vec()]).map(|(node, payload)| {
+        let node = node.clone();
+        tokio::spawn(async move { node.sign(id, &payload).await })
+    }).collect();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(handles.iter().all(|handle| !handle.is_finished()));
+}
+
+
+#[test]
+fn only_recent_signatures_are_kept() {
+    let signature = |i: u64| Signature(threshold::hash_to_point(&i.to_be_bytes()));
+    let mut signatures = RecentSignatures::new(3);
+    for i in 0..5 {
+        signatures.insert(Identifier::new(0, i), signature(i));
+    }
+    signatures.insert(Identifier::new(0, 3), signature(3));
+    assert_eq!(signatures.get(&Identifier::new(0, 1)), None);
+    for i in 2..5 {
+        assert_eq!(signatures.get(&Identifier::new(0, i)), Some(signature(i)));
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::{collections::HashSet, time::Duration};
+
+use asynchronous_broadcast_protocols::{node::Delivery, BroadcastProtocol, Config};
+
+
+fn config(n: u16, my_id: u16, base_port: u16, protocol: &str) -> Config {
+    common::config_with(&common::ids(n), my_id, base_port, serde_json::json!({ "protocol": protocol }))
+}
+
+
+#[test]
+fn auto_selects_two_step_when_n_allows() {
+    assert_eq!(config(4, 0, 39400, "auto").broadcast_protocol(), BroadcastProtocol::ReliableBroadcast);
+    assert_eq!(config(5, 0, 39400, "auto").broadcast_protocol(), BroadcastProtocol::ReliableBroadcast);
+    assert_eq!(config(6, 0, 39400, "auto").broadcast_protocol(), BroadcastProtocol::TwoStepBroadcast);
+    assert_eq!(config(6, 0, 39400, "reliable_broadcast").broadcast_protocol(), BroadcastProtocol::ReliableBroadcast);
+
+    let mut config = config(7, 0, 39400, "auto");
+    config.t = Some(2);
+    assert_eq!(config.broadcast_protocol(), BroadcastProtocol::ReliableBroadcast);
+}
+
+
+#[test]
+fn explicit_two_step_needs_5t_plus_1() {
+    assert!(config(6, 0, 39400, "two_step_broadcast").validate().is_ok());
+    assert!(config(5, 0, 39400, "two_step_broadcast").validate().is_err());
+}
+
+
+#[tokio::test]
+async fn delivers_with_one_node_down() {
+    // n = 6, t = 1: node 5 never starts, the other five are exactly the n-t witnesses needed
+    let nodes = common::start_all((0..5).map(|id| config(6, id, 39410, "two_step_broadcast"))).await;
+    let mut receivers: Vec<_> = nodes.iter().map(|node| node.subscribe_deliveries()).collect();
+
+    let mut expected = HashSet::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let payload = format!("from {}", i).into_bytes();
+        let id = node.broadcast(payload.clone()).await.unwrap();
+        expected.insert(Delivery { id, payload });
+    }
+
+    for rx in &mut receivers {
+        let mut delivered = HashSet::new();
+        while delivered.len() < expected.len() {
+            let delivery = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await.unwrap().unwrap();
+            assert!(delivered.insert(delivery), "delivered twice");
+        }
+        assert_eq!(delivered, expected);
+    }
+}
@@ -0,0 +1,345 @@
+mod common;
+
+use std::{io::Write, path::{Path, PathBuf}, sync::Arc, time::Duration};
+
+use asynchronous_broadcast_protocols::{
+    avid::AvidMessage, merkle::ProvenFragment, node::Node, reliable_broadcast::ReliableBroadcastMessage, transport::ReliableLink,
+    wal::{Counter, FsyncPolicy, Record, Wal, WalConfig}, Config, Error, Identifier, Message, MessageType, Protocol,
+};
+use bls12_381::Scalar;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+
+fn config(my_id: u16, base_port: u16, wal: &Path) -> Config {
+    common::config_with(&common::ids(4), my_id, base_port, serde_json::json!({ "wal": { "path": wal, "fsync": "always" } }))
+}
+
+
+fn wal_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("wal-{}-{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+
+/// The log as node 0 left it when it crashed
+fn write_log(path: &Path, records: &[Record]) {
+    let config = WalConfig { path: path.to_str().unwrap().to_string(), fsync: FsyncPolicy::Always, fsync_interval_ms: 0 };
+    let (wal, recovered) = Wal::open(&config).unwrap();
+    assert!(recovered.is_empty());
+    for record in records {
+        wal.write(record).unwrap();
+    }
+}
+
+
+fn rbc(sender: u16, id: Identifier, message: ReliableBroadcastMessage, config: &Config) -> Message {
+    let privkey = config.get_node(sender).unwrap().privkey;
+    Message::new_authenticated(id, sender, MessageType::ReliableBroadcast(message), &privkey, config)
+}
+
+
+/// A scripted peer. Its link is read in the background, so that it answers handshakes
+/// and acknowledges frames even while the test does not look at what it receives.
+struct Peer {
+    link: Arc<ReliableLink>,
+    rx: mpsc::UnboundedReceiver<(u16, Vec<u8>)>,
+}
+
+
+impl Peer {
+    async fn bind(config: &Config) -> Self {
+        let link = ReliableLink::bind(config).await.unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let reader = link.clone();
+        tokio::spawn(async move {
+            while let Ok(received) = reader.recv().await {
+                if tx.send(received).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { link, rx }
+    }
+
+    async fn send(&self, target: u16, payload: &[u8]) -> Result<(), Error> {
+        self.link.send(target, payload).await
+    }
+}
+
+
+/// Restart node 0 from its log next to three scripted peers
+async fn restart(base_port: u16, wal: &Path) -> (Arc<Node>, Vec<Peer>) {
+    let mut peers = Vec::new();
+    for id in 1..4 {
+        peers.push(Peer::bind(&config(id, base_port, wal)).await);
+    }
+    let node = common::start(config(0, base_port, wal)).await;
+    (node, peers)
+}
+
+
+/// Reliable broadcast messages `peer` receives from node 0 within `wait`
+async fn received(peer: &mut Peer, wait: Duration) -> Vec<(Identifier, ReliableBroadcastMessage)> {
+    let mut messages = Vec::new();
+    while let Ok(Some((0, bytes))) = tokio::time::timeout(wait, peer.rx.recv()).await {
+        if let Ok(Message { id, payload: MessageType::ReliableBroadcast(message), .. }) = Message::from_bytes(&bytes) {
+            messages.push((id, message));
+        }
+    }
+    messages
+}
+
+
+#[test]
+fn torn_records_are_cut_off() {
+    let path = wal_path("torn");
+    let config = config(0, 40700, &path);
+    let id = Identifier::new(1, 0);
+    write_log(&path, &[
+        Record::Broadcast { id: Identifier::new(0, 0), payload: b"a".to_vec() },
+        Record::Vote(rbc(0, id, ReliableBroadcastMessage::Echo([7; 32]), &config)),
+        Record::Delivered { id, payload: b"b".to_vec() },
+    ]);
+    let intact = std::fs::metadata(&path).unwrap().len();
+
+    // Crash in the middle of the next append
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[0, 0, 0, 40, 1, 2, 3]).unwrap();
+    drop(file);
+
+    let wal_config: WalConfig = serde_json::from_value(serde_json::json!({ "path": path, "fsync": "periodic" })).unwrap();
+    assert_eq!(wal_config.fsync, FsyncPolicy::Periodic);
+    let (wal, records) = Wal::open(&wal_config).unwrap();
+    assert_eq!(records.len(), 3);
+    assert!(matches!(&records[1], Record::Vote(vote) if vote.id == id));
+    assert!(matches!(&records[2], Record::Delivered { payload, .. } if payload == b"b"));
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), intact);
+
+    wal.write(&Record::Delivered { id: Identifier::new(1, 1), payload: Vec::new() }).unwrap();
+    drop(wal);
+    let (_, records) = Wal::open(&wal_config).unwrap();
+    assert_eq!(records.len(), 4);
+}
+
+
+#[test]
+fn unreadable_records_are_not_cut_off() {
+    let frame = |body: &[u8], checksum: &[u8]| {
+        let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&checksum[..8]);
+        frame.extend_from_slice(body);
+        frame
+    };
+    let undecodable = [0xff, 1, 2, 3];
+    let delivered = Record::Delivered { id: Identifier::new(1, 0), payload: Vec::new() }.to_bytes();
+    let cases = [
+        // Written whole, as its checksum shows, so even at the end it was not torn by a crash
+        ("undecodable", frame(&undecodable, &Sha256::digest(undecodable))),
+        ("undecodable-inside", [frame(&undecodable, &Sha256::digest(undecodable)), frame(&delivered, &Sha256::digest(&delivered))].concat()),
+        // Failing its checksum, but followed by another record
+        ("corrupt-inside", [frame(&undecodable, &[0; 8]), frame(&delivered, &Sha256::digest(&delivered))].concat()),
+    ];
+    for (name, bad) in cases {
+        let path = wal_path(name);
+        write_log(&path, &[Record::Broadcast { id: Identifier::new(0, 0), payload: b"a".to_vec() }]);
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&bad).unwrap();
+        drop(file);
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let wal_config = WalConfig { path: path.to_str().unwrap().to_string(), fsync: FsyncPolicy::Always, fsync_interval_ms: 0 };
+        assert!(matches!(Wal::open(&wal_config), Err(Error::Wal { .. })), "{}", name);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size, "{}", name);
+    }
+}
+
+
+#[tokio::test]
+async fn restarted_node_keeps_its_echo() {
+    let path = wal_path("echo");
+    let base_port = 40710;
+    let config = config(0, base_port, &path);
+    let id = Identifier::new(1, 0);
+    let first: [u8; 32] = Sha256::digest(b"first").into();
+    let second: [u8; 32] = Sha256::digest(b"second").into();
+
+    // Crashed after logging its echo of "first", before sending it
+    write_log(&path, &[Record::Vote(rbc(0, id, ReliableBroadcastMessage::Echo(first), &config))]);
+    let (_node, mut peers) = restart(base_port, &path).await;
+    assert_eq!(received(&mut peers[0], Duration::from_millis(500)).await, [(id, ReliableBroadcastMessage::Echo(first))]);
+
+    // The faulty sender now sends "second": no echo for it
+    peers[0].send(0, &rbc(1, id, ReliableBroadcastMessage::Send(b"second".to_vec()), &config).to_bytes()).await.unwrap();
+    assert!(received(&mut peers[0], Duration::from_millis(500)).await.iter().all(|(_, message)| *message != ReliableBroadcastMessage::Echo(second)));
+
+    // Its echo still counts towards a ready for "first"
+    for (peer, sender) in peers[1..].iter().zip(2..) {
+        peer.send(0, &rbc(sender, id, ReliableBroadcastMessage::Echo(first), &config).to_bytes()).await.unwrap();
+    }
+    assert_eq!(received(&mut peers[1], Duration::from_millis(500)).await, [
+        (id, ReliableBroadcastMessage::Echo(first)),
+        (id, ReliableBroadcastMessage::Ready(first)),
+    ]);
+}
+
+
+#[tokio::test]
+async fn restarted_node_sends_ready_once_and_logs_delivery() {
+    let path = wal_path("ready");
+    let base_port = 40720;
+    let config = config(0, base_port, &path);
+    let id = Identifier::new(1, 0);
+    let digest: [u8; 32] = Sha256::digest(b"payload").into();
+
+    // Crashed after sending its ready
+    write_log(&path, &[
+        Record::Vote(rbc(0, id, ReliableBroadcastMessage::Echo(digest), &config)),
+        Record::Vote(rbc(0, id, ReliableBroadcastMessage::Ready(digest), &config)),
+    ]);
+    let (node, mut peers) = restart(base_port, &path).await;
+    let mut deliveries = node.subscribe_deliveries();
+    assert_eq!(received(&mut peers[0], Duration::from_millis(500)).await, [
+        (id, ReliableBroadcastMessage::Echo(digest)),
+        (id, ReliableBroadcastMessage::Ready(digest)),
+    ]);
+
+    // Readies of two more nodes complete the instance, without a second ready
+    for (peer, sender) in peers[..2].iter().zip(1..) {
+        peer.send(0, &rbc(sender, id, ReliableBroadcastMessage::Ready(digest), &config).to_bytes()).await.unwrap();
+    }
+    assert_eq!(received(&mut peers[2], Duration::from_millis(500)).await, [
+        (id, ReliableBroadcastMessage::Echo(digest)),
+        (id, ReliableBroadcastMessage::Ready(digest)),
+        (id, ReliableBroadcastMessage::Request),
+    ]);
+
+    peers[2].send(0, &rbc(3, id, ReliableBroadcastMessage::Answer(b"payload".to_vec()), &config).to_bytes()).await.unwrap();
+    let delivery = tokio::time::timeout(Duration::from_secs(5), deliveries.recv()).await.unwrap().unwrap();
+    assert_eq!(delivery.payload, b"payload");
+
+    let (_, records) = Wal::open(config.wal.as_ref().unwrap()).unwrap();
+    assert!(matches!(records.last(), Some(Record::Delivered { id: delivered, payload }) if *delivered == id && payload == b"payload"));
+}
+
+
+#[tokio::test]
+async fn restarted_node_does_not_deliver_or_broadcast_twice() {
+    let path = wal_path("delivered");
+    let base_port = 40730;
+    let config = config(0, base_port, &path);
+    let delivered = Identifier::new(1, 0);
+
+    // Crashed after delivering (0, 0) and (1, 0), while broadcasting (0, 1)
+    write_log(&path, &[
+        Record::Broadcast { id: Identifier::new(0, 0), payload: b"a".to_vec() },
+        Record::Broadcast { id: Identifier::new(0, 1), payload: b"b".to_vec() },
+        Record::Delivered { id: Identifier::new(0, 0), payload: b"a".to_vec() },
+        Record::Delivered { id: delivered, payload: b"x".to_vec() },
+    ]);
+    let (node, mut peers) = restart(base_port, &path).await;
+    let resumed = Identifier::new(0, 1);
+    assert_eq!(received(&mut peers[0], Duration::from_millis(500)).await, [
+        (resumed, ReliableBroadcastMessage::Send(b"b".to_vec())),
+        (resumed, ReliableBroadcastMessage::Echo(Sha256::digest(b"b").into())),
+    ]);
+
+    // New broadcasts do not reuse a logged sequence number
+    assert_eq!(node.broadcast(b"c".to_vec()).await.unwrap(), Identifier::new(0, 2));
+
+    // A delivered instance is not opened again
+    peers[0].send(0, &rbc(1, delivered, ReliableBroadcastMessage::Send(b"y".to_vec()), &config).to_bytes()).await.unwrap();
+    assert!(received(&mut peers[1], Duration::from_millis(500)).await.iter().all(|(id, _)| *id != delivered));
+}
+
+
+#[tokio::test]
+async fn restarted_node_keeps_stored_fragments() {
+    let path = wal_path("fragment");
+    let base_port = 40740;
+    let config = config(0, base_port, &path);
+    let blob = Identifier::new(1, 0);
+    let fragment = ProvenFragment { root: [3; 32], proof: vec![[4; 32], [5; 32]], data: b"fragment".to_vec() };
+    write_log(&path, &[Record::Fragment { id: blob, fragment: fragment.clone() }]);
+
+    let (_node, mut peers) = restart(base_port, &path).await;
+    let request = Message::new_authenticated(blob, 2, MessageType::Avid(AvidMessage::Request), &[3; 32], &config);
+    peers[1].send(0, &request.to_bytes()).await.unwrap();
+    let answer = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (_, bytes) = peers[1].rx.recv().await.unwrap();
+            if let Ok(Message { id, payload: MessageType::Avid(AvidMessage::Answer(answer)), .. }) = Message::from_bytes(&bytes) {
+                return (id, answer);
+            }
+        }
+    }).await.expect("no answer");
+    assert_eq!(answer, (blob, fragment));
+}
+
+
+#[tokio::test]
+async fn restarted_node_does_not_reuse_dispersal_or_sharing_ids() {
+    let path = wal_path("taken");
+    let base_port = 40750;
+    write_log(&path, &[
+        Record::Taken { counter: Counter::Dispersal, id: Identifier::new(0, 3) },
+        Record::Taken { counter: Counter::Sharing, id: Identifier::new(0, 1) },
+    ]);
+    let (node, _peers) = restart(base_port, &path).await;
+    assert_eq!(node.share(Scalar::from(7u64)).await.unwrap(), Identifier::new(0, 2));
+    // The scripted peers never acknowledge, but the id is logged before anything is sent
+    let _ = tokio::time::timeout(Duration::from_millis(200), node.disperse(b"blob")).await;
+
+    let wal_config = config(0, base_port, &path).wal.unwrap();
+    let (_, records) = Wal::open(&wal_config).unwrap();
+    assert!(records.iter().any(|record| matches!(record, Record::Taken { counter: Counter::Dispersal, id } if *id == Identifier::new(0, 4))));
+    assert!(records.iter().any(|record| matches!(record, Record::Taken { counter: Counter::Sharing, id } if *id == Identifier::new(0, 2))));
+}
+
+
+#[tokio::test]
+async fn restarted_node_compacts_its_log() {
+    let path = wal_path("compact");
+    let base_port = 40760;
+    let config = config(0, base_port, &path);
+    let (delivered, open) = (Identifier::new(0, 0), Identifier::new(1, 0));
+    let fragment = ProvenFragment { root: [3; 32], proof: vec![[4; 32]], data: b"fragment".to_vec() };
+    write_log(&path, &[
+        Record::Broadcast { id: delivered, payload: b"a".to_vec() },
+        Record::Vote(rbc(0, delivered, ReliableBroadcastMessage::Echo(Sha256::digest(b"a").into()), &config)),
+        Record::Vote(rbc(0, open, ReliableBroadcastMessage::Echo([7; 32]), &config)),
+        Record::Delivered { id: delivered, payload: b"a".to_vec() },
+        Record::Fragment { id: Identifier::new(2, 0), fragment: fragment.clone() },
+    ]);
+    let before = std::fs::metadata(&path).unwrap().len();
+    let (node, _peers) = restart(base_port, &path).await;
+    assert!(std::fs::metadata(&path).unwrap().len() < before);
+
+    let (_, records) = Wal::open(config.wal.as_ref().unwrap()).unwrap();
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().any(|record| matches!(record, Record::Taken { counter: Counter::Broadcast, id } if *id == delivered)));
+    assert!(records.iter().any(|record| matches!(record, Record::Vote(vote) if vote.id == open)));
+    assert!(records.iter().any(|record| matches!(record, Record::Delivered { id, payload } if *id == delivered && payload.is_empty())));
+    assert!(records.iter().any(|record| matches!(record, Record::Fragment { fragment: kept, .. } if *kept == fragment)));
+    assert_eq!(node.broadcast(b"b".to_vec()).await.unwrap(), Identifier::new(0, 1));
+}
+
+
+#[test]
+fn wal_requires_reliable_broadcast() {
+    let path = wal_path("protocol");
+    let config = config(0, 40770, &path);
+    config.validate().unwrap();
+
+    let mut merkle = config.clone();
+    merkle.protocol = Protocol::MerkleBroadcast;
+    assert!(matches!(merkle.validate(), Err(Error::Config(_))));
+
+    // With t = 0, four nodes are enough for two-step broadcast, which `auto` then picks
+    let mut auto = config.clone();
+    auto.t = Some(0);
+    assert!(matches!(auto.validate(), Err(Error::Config(_))));
+    auto.wal = None;
+    auto.validate().unwrap();
+}